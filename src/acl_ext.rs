@@ -0,0 +1,222 @@
+use std::io;
+
+#[cfg(feature = "impl-ssh-common")]
+use crate::filesystem::LinuxAcl;
+#[cfg(feature = "impl-native")]
+use crate::filesystem::LinuxPermissions;
+use crate::filesystem::{LinuxAclEntry, LinuxAclPermissions, LinuxAclTag};
+
+// the xattrs through which the kernel exposes the access and default ACLs
+#[cfg(feature = "impl-native")]
+pub(crate) const ACCESS_XATTR: &str = "system.posix_acl_access";
+#[cfg(feature = "impl-native")]
+pub(crate) const DEFAULT_XATTR: &str = "system.posix_acl_default";
+
+#[cfg(feature = "impl-native")]
+const XATTR_VERSION: u32 = 2;
+// the id stored for entries that don't name a user or group
+#[cfg(feature = "impl-native")]
+const UNDEFINED_ID: u32 = u32::MAX;
+
+// the kernel's xattr representation: a version header, then a tag, permissions and id per entry in little endian,
+// sorted by tag and id
+#[cfg(feature = "impl-native")]
+pub(crate) fn encode_xattr(entries: &[LinuxAclEntry]) -> Vec<u8> {
+    let mut entries = entries.to_vec();
+    entries.sort();
+
+    let mut value = Vec::with_capacity(4 + entries.len() * 8);
+    value.extend_from_slice(&XATTR_VERSION.to_le_bytes());
+    for entry in entries {
+        let (tag, id) = match entry.tag {
+            LinuxAclTag::UserOwner => (0x01u16, UNDEFINED_ID),
+            LinuxAclTag::User(user_id) => (0x02, user_id),
+            LinuxAclTag::GroupOwner => (0x04, UNDEFINED_ID),
+            LinuxAclTag::Group(group_id) => (0x08, group_id),
+            LinuxAclTag::Mask => (0x10, UNDEFINED_ID),
+            LinuxAclTag::Other => (0x20, UNDEFINED_ID),
+        };
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&entry.permissions.bits().to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+    value
+}
+
+#[cfg(feature = "impl-native")]
+pub(crate) fn decode_xattr(value: &[u8]) -> io::Result<Vec<LinuxAclEntry>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed ACL xattr");
+    if value.len() < 4 {
+        return Err(invalid());
+    }
+    let (header, entries) = value.split_at(4);
+    if header != XATTR_VERSION.to_le_bytes() || entries.len() % 8 != 0 {
+        return Err(invalid());
+    }
+
+    entries
+        .chunks(8)
+        .map(|entry| {
+            let tag = u16::from_le_bytes([entry[0], entry[1]]);
+            let permissions = u16::from_le_bytes([entry[2], entry[3]]);
+            let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            let tag = match tag {
+                0x01 => LinuxAclTag::UserOwner,
+                0x02 => LinuxAclTag::User(id),
+                0x04 => LinuxAclTag::GroupOwner,
+                0x08 => LinuxAclTag::Group(id),
+                0x10 => LinuxAclTag::Mask,
+                0x20 => LinuxAclTag::Other,
+                _ => return Err(invalid()),
+            };
+            Ok(LinuxAclEntry {
+                tag,
+                permissions: LinuxAclPermissions::from_bits_truncate(permissions),
+            })
+        })
+        .collect()
+}
+
+// the access ACL equivalent to the mode, which is what a file without an extended ACL has
+#[cfg(feature = "impl-native")]
+pub(crate) fn mode_entries(permissions: LinuxPermissions) -> Vec<LinuxAclEntry> {
+    let mode = permissions.bits();
+    [
+        (LinuxAclTag::UserOwner, mode >> 6),
+        (LinuxAclTag::GroupOwner, mode >> 3),
+        (LinuxAclTag::Other, mode),
+    ]
+    .into_iter()
+    .map(|(tag, bits)| LinuxAclEntry {
+        tag,
+        permissions: LinuxAclPermissions::from_bits_truncate((bits & 0o7) as u16),
+    })
+    .collect()
+}
+
+// the arguments of a getfacl invocation printing both ACLs with numeric ids and without comments
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn getfacl_args(path: &str) -> Vec<&str> {
+    vec![
+        "--absolute-names",
+        "--numeric",
+        "--omit-header",
+        "--no-effective",
+        "--",
+        path,
+    ]
+}
+
+// parses "[default:]tag:[id]:rwx" lines
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn parse_getfacl(output: &[u8]) -> io::Result<LinuxAcl> {
+    let output = std::str::from_utf8(output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("unexpected getfacl line: {}", line));
+    let mut acl = LinuxAcl::default();
+
+    for line in output.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (is_default, entry) = match line.strip_prefix("default:") {
+            Some(entry) => (true, entry),
+            None => (false, line),
+        };
+        let [tag, id, permissions] = entry.split(':').collect::<Vec<_>>()[..] else {
+            return Err(invalid(line));
+        };
+        let parse_id = || id.parse::<u32>().map_err(|_| invalid(line));
+        let tag = match (tag, id) {
+            ("user", "") => LinuxAclTag::UserOwner,
+            ("user", _) => LinuxAclTag::User(parse_id()?),
+            ("group", "") => LinuxAclTag::GroupOwner,
+            ("group", _) => LinuxAclTag::Group(parse_id()?),
+            ("mask", "") => LinuxAclTag::Mask,
+            ("other", "") => LinuxAclTag::Other,
+            _ => return Err(invalid(line)),
+        };
+        let entry = LinuxAclEntry {
+            tag,
+            permissions: parse_permissions(permissions).ok_or_else(|| invalid(line))?,
+        };
+        match is_default {
+            true => acl.default.push(entry),
+            false => acl.access.push(entry),
+        }
+    }
+
+    Ok(acl)
+}
+
+// the arguments of a setfacl invocation replacing the access ACL, along with the default ACL if it has entries
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn setfacl_args(path: &str, acl: &LinuxAcl) -> Vec<String> {
+    let spec = acl
+        .access
+        .iter()
+        .map(|entry| format_entry(entry, ""))
+        .chain(acl.default.iter().map(|entry| format_entry(entry, "d:")))
+        .collect::<Vec<_>>()
+        .join(",");
+    vec!["--set".to_string(), spec, "--".to_string(), path.to_string()]
+}
+
+// the arguments of a setfacl invocation removing the default ACL, which a "--set" without default entries keeps
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn remove_default_args(path: &str) -> Vec<&str> {
+    vec!["-k", "--", path]
+}
+
+#[cfg(feature = "impl-ssh-common")]
+fn format_entry(entry: &LinuxAclEntry, prefix: &str) -> String {
+    let (tag, id) = match entry.tag {
+        LinuxAclTag::UserOwner => ("u", None),
+        LinuxAclTag::User(user_id) => ("u", Some(user_id)),
+        LinuxAclTag::GroupOwner => ("g", None),
+        LinuxAclTag::Group(group_id) => ("g", Some(group_id)),
+        LinuxAclTag::Mask => ("m", None),
+        LinuxAclTag::Other => ("o", None),
+    };
+    format!(
+        "{}{}:{}:{}",
+        prefix,
+        tag,
+        id.map(|id| id.to_string()).unwrap_or_default(),
+        format_permissions(entry.permissions)
+    )
+}
+
+#[cfg(feature = "impl-ssh-common")]
+fn format_permissions(permissions: LinuxAclPermissions) -> String {
+    [
+        (LinuxAclPermissions::READ, 'r'),
+        (LinuxAclPermissions::WRITE, 'w'),
+        (LinuxAclPermissions::EXECUTE, 'x'),
+    ]
+    .into_iter()
+    .map(|(flag, symbol)| match permissions.contains(flag) {
+        true => symbol,
+        false => '-',
+    })
+    .collect()
+}
+
+#[cfg(feature = "impl-ssh-common")]
+fn parse_permissions(permissions: &str) -> Option<LinuxAclPermissions> {
+    let [read, write, execute] = permissions.as_bytes()[..] else {
+        return None;
+    };
+    let mut parsed = LinuxAclPermissions::empty();
+    for (symbol, expected, flag) in [
+        (read, b'r', LinuxAclPermissions::READ),
+        (write, b'w', LinuxAclPermissions::WRITE),
+        (execute, b'x', LinuxAclPermissions::EXECUTE),
+    ] {
+        match symbol {
+            b'-' => {}
+            _ if symbol == expected => parsed |= flag,
+            _ => return None,
+        }
+    }
+    Some(parsed)
+}
@@ -0,0 +1,684 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use flate2::{write::GzDecoder, write::GzEncoder, Compression};
+use futures_util::{future::BoxFuture, FutureExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    executor::{LinuxExecutor, LinuxProcess, LinuxProcessConfiguration, LinuxProcessError},
+    filesystem::{LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions},
+    tar_codec::{self, TarEntry, TarEntryKind, TarHeader, BLOCK_SIZE},
+    transfer::{self, LinuxCopyOptions, LinuxCopyReport},
+};
+
+// archive data is handed to the process or read from the archive file in pieces of this size
+const ARCHIVE_CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinuxArchiveOptions {
+    compression: LinuxArchiveCompression,
+    copy_options: LinuxCopyOptions,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinuxArchiveCompression {
+    /// A plain tar stream, for trees that are already compressed or fast links
+    None,
+    /// A gzip-compressed tar stream
+    #[default]
+    Gzip,
+}
+
+impl Default for LinuxArchiveOptions {
+    fn default() -> Self {
+        LinuxArchiveOptions::new()
+    }
+}
+
+impl LinuxArchiveOptions {
+    /// Gzip compression with the default copy options
+    pub fn new() -> LinuxArchiveOptions {
+        LinuxArchiveOptions {
+            compression: LinuxArchiveCompression::default(),
+            copy_options: LinuxCopyOptions::new(),
+        }
+    }
+
+    pub fn compression(&mut self, compression: LinuxArchiveCompression) -> &mut LinuxArchiveOptions {
+        self.compression = compression;
+        self
+    }
+
+    /// The attributes to carry over, with the same meaning as for copy_dir_recursively
+    pub fn copy_options(&mut self, copy_options: &LinuxCopyOptions) -> &mut LinuxArchiveOptions {
+        self.copy_options = *copy_options;
+        self
+    }
+}
+
+/// Copy a directory tree from any filesystem into a host with tar installed by streaming it as a single archive into
+/// a "tar -x" process, which avoids a round trip per file. The result matches copy_dir_recursively, and the copy
+/// falls back to going file by file when tar is unavailable on the destination
+pub fn upload_dir<'a, S, D>(
+    src_fs: &'a S,
+    src_path: &'a OsStr,
+    dst: &'a D,
+    dst_path: &'a OsStr,
+    archive_options: &'a LinuxArchiveOptions,
+) -> BoxFuture<'a, io::Result<LinuxCopyReport>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + LinuxExecutor + Sync + ?Sized,
+{
+    upload_dir_inner(src_fs, src_path, dst, dst_path, archive_options).boxed()
+}
+
+/// Copy a directory tree from a host with tar installed into any filesystem by having "tar -c" pack it into a
+/// temporary archive first, which is then transferred as a single file and unpacked on the fly. The result matches
+/// copy_dir_recursively, and the copy falls back to going file by file when tar is unavailable on the source
+pub fn download_dir<'a, S, D>(
+    src: &'a S,
+    src_path: &'a OsStr,
+    dst_fs: &'a D,
+    dst_path: &'a OsStr,
+    archive_options: &'a LinuxArchiveOptions,
+) -> BoxFuture<'a, io::Result<LinuxCopyReport>>
+where
+    S: LinuxFilesystem + LinuxExecutor + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    download_dir_inner(src, src_path, dst_fs, dst_path, archive_options).boxed()
+}
+
+async fn upload_dir_inner<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst: &D,
+    dst_path: &OsStr,
+    archive_options: &LinuxArchiveOptions,
+) -> io::Result<LinuxCopyReport>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + LinuxExecutor + Sync + ?Sized,
+{
+    let copy_options = &archive_options.copy_options;
+    let root_metadata = resolve_metadata(src_fs, src_path, copy_options).await?;
+    if root_metadata.file_type != Some(LinuxFileType::Dir) || !is_tar_available(dst).await {
+        return transfer::copy_tree(src_fs, src_path, dst, dst_path, copy_options, false).await;
+    }
+
+    // like copy_dir_recursively, the destination itself is created, but not its parents
+    match dst.get_metadata(dst_path).await {
+        Ok(dst_metadata) if dst_metadata.file_type == Some(LinuxFileType::Dir) => {}
+        _ => dst.create_dir(dst_path).await?,
+    }
+
+    let mut process_configuration = LinuxProcessConfiguration::new("tar");
+    process_configuration
+        .args(extract_args(dst_path, archive_options))
+        .redirect_stdin()
+        .redirect_stderr();
+    let mut process = dst.begin_execute(&process_configuration).await.map_err(process_error)?;
+
+    let mut sink = ArchiveSink::new(archive_options.compression);
+    let write_result = write_archive(src_fs, src_path, root_metadata, copy_options, &mut sink, &mut process).await;
+    let finish_result = match write_result {
+        Ok(_) => sink.finish(&mut process).await,
+        Err(_) => Ok(()),
+    };
+    // stdin is closed in any case, so that tar exits instead of waiting for more data
+    process.close_stdin().await.map_err(process_error)?;
+    let output = process.await_exit_with_output().await.map_err(process_error)?;
+
+    let report = write_result?;
+    finish_result?;
+    match output.status_code {
+        Some(0) => Ok(report),
+        _ => Err(io::Error::other(format!(
+            "tar exited with status code {:?}: {}",
+            output.status_code,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+async fn download_dir_inner<S, D>(
+    src: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    archive_options: &LinuxArchiveOptions,
+) -> io::Result<LinuxCopyReport>
+where
+    S: LinuxFilesystem + LinuxExecutor + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let copy_options = &archive_options.copy_options;
+    let root_metadata = resolve_metadata(src, src_path, copy_options).await?;
+    if root_metadata.file_type != Some(LinuxFileType::Dir) || !is_tar_available(src).await {
+        return transfer::copy_tree(src, src_path, dst_fs, dst_path, copy_options, false).await;
+    }
+
+    let archive_path = OsString::from(format!("/tmp/archive-{}", Uuid::new_v4()));
+    let mut process_configuration = LinuxProcessConfiguration::new("tar");
+    process_configuration
+        .args(create_args(src_path, &archive_path, archive_options))
+        .redirect_stderr();
+    let output = src.execute(&process_configuration).await.map_err(process_error)?;
+
+    // clean up regardless of whether packing or unpacking succeeded
+    let read_options = *LinuxOpenOptions::new().read();
+    let extract_result = match output.status_code {
+        Some(0) => match src.open_file(&archive_path, &read_options).await {
+            Ok(reader) => {
+                let source = ArchiveSource::new(reader, archive_options.compression);
+                extract_archive(source, dst_fs, dst_path, copy_options).await
+            }
+            Err(err) => Err(err),
+        },
+        _ => Err(io::Error::other(format!(
+            "tar exited with status code {:?}: {}",
+            output.status_code,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    };
+    let cleanup_result = match src.exists(&archive_path).await {
+        Ok(true) => src.remove_file(&archive_path).await,
+        _ => Ok(()),
+    };
+
+    let report = extract_result?;
+    cleanup_result?;
+    Ok(report)
+}
+
+async fn is_tar_available<F>(filesystem: &F) -> bool
+where
+    F: LinuxExecutor + Sync + ?Sized,
+{
+    let mut process_configuration = LinuxProcessConfiguration::new("tar");
+    process_configuration.arg("--version");
+    matches!(
+        filesystem.execute(&process_configuration).await,
+        Ok(output) if output.status_code == Some(0)
+    )
+}
+
+// the flags are understood by both GNU tar and busybox: -o skips restoring ownership when extracting, -m skips
+// restoring modification times and -p restores permissions without applying the umask
+fn extract_args(dst_path: &OsStr, archive_options: &LinuxArchiveOptions) -> Vec<String> {
+    let mut args = vec![
+        "-x".to_string(),
+        "-f".to_string(),
+        "-".to_string(),
+        "-C".to_string(),
+        dst_path.to_string_lossy().into_owned(),
+    ];
+    if archive_options.compression == LinuxArchiveCompression::Gzip {
+        args.push("-z".into());
+    }
+    if !archive_options.copy_options.is_preserve_ownership() {
+        args.push("-o".into());
+    }
+    if !archive_options.copy_options.is_preserve_times() {
+        args.push("-m".into());
+    }
+    if archive_options.copy_options.is_preserve_permissions() {
+        args.push("-p".into());
+    }
+    args
+}
+
+fn create_args(src_path: &OsStr, archive_path: &OsStr, archive_options: &LinuxArchiveOptions) -> Vec<String> {
+    let mut args = vec![
+        "-c".to_string(),
+        "-f".to_string(),
+        archive_path.to_string_lossy().into_owned(),
+        "-C".to_string(),
+        src_path.to_string_lossy().into_owned(),
+    ];
+    if archive_options.compression == LinuxArchiveCompression::Gzip {
+        args.push("-z".into());
+    }
+    // -h archives what symlinks point to instead of the symlinks themselves
+    if !archive_options.copy_options.is_preserve_symlinks() {
+        args.push("-h".into());
+    }
+    args.push(".".into());
+    args
+}
+
+fn process_error(err: LinuxProcessError) -> io::Error {
+    match err {
+        LinuxProcessError::IO(err) => err,
+        LinuxProcessError::Other(err) => io::Error::other(err),
+        err => io::Error::other(format!("{:?}", err)),
+    }
+}
+
+async fn resolve_metadata<F>(
+    filesystem: &F,
+    path: &OsStr,
+    copy_options: &LinuxCopyOptions,
+) -> io::Result<LinuxFileMetadata>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    let metadata = filesystem.get_symlink_metadata(path).await?;
+    match metadata.file_type == Some(LinuxFileType::Symlink) && !copy_options.is_preserve_symlinks() {
+        true => filesystem.get_metadata(path).await,
+        false => Ok(metadata),
+    }
+}
+
+// packs the tree in the same order as copy_dir_recursively copies it: depth-first with children sorted by name
+async fn write_archive<S>(
+    src_fs: &S,
+    src_path: &OsStr,
+    root_metadata: LinuxFileMetadata,
+    copy_options: &LinuxCopyOptions,
+    sink: &mut ArchiveSink,
+    process: &mut Box<dyn LinuxProcess>,
+) -> io::Result<LinuxCopyReport>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+{
+    let mut report = LinuxCopyReport::default();
+    let mut steps = vec![(src_path.to_os_string(), Vec::from(&b"."[..]), root_metadata)];
+
+    while let Some((entry_path, archive_path, metadata)) = steps.pop() {
+        let mut entry = TarEntry {
+            path: archive_path,
+            kind: TarEntryKind::File,
+            link_target: Vec::new(),
+            size: 0,
+            mode: metadata
+                .permissions
+                .map(|permissions| permissions.bits())
+                .unwrap_or(0o644),
+            user_id: metadata.user_id.unwrap_or(0),
+            group_id: metadata.group_id.unwrap_or(0),
+            modified_time: metadata.modified_time.unwrap_or(UNIX_EPOCH),
+            accessed_time: metadata.accessed_time,
+        };
+        let include_times = copy_options.is_preserve_times();
+
+        match metadata.file_type {
+            Some(LinuxFileType::Symlink) => {
+                entry.kind = TarEntryKind::Symlink;
+                entry.link_target = src_fs.read_link(&entry_path).await?.into_vec();
+                sink.write(&tar_codec::encode_header(&entry, include_times), process)
+                    .await?;
+                report.symlinks += 1;
+            }
+            Some(LinuxFileType::Dir) => {
+                entry.kind = TarEntryKind::Dir;
+                if !copy_options.is_preserve_permissions() {
+                    // tar applies the umask to this, just like creating the directory does
+                    entry.mode = 0o777;
+                }
+                sink.write(&tar_codec::encode_header(&entry, include_times), process)
+                    .await?;
+                report.dirs += 1;
+
+                let mut children = src_fs.list_dir_with_metadata(&entry_path).await?;
+                children.sort_by(|(first, _), (second, _)| first.name.cmp(&second.name));
+                for (dir_entry, child_metadata) in children.into_iter().rev() {
+                    let child_metadata = match child_metadata.file_type {
+                        Some(LinuxFileType::Symlink) if !copy_options.is_preserve_symlinks() => {
+                            src_fs.get_metadata(&dir_entry.path).await?
+                        }
+                        _ => child_metadata,
+                    };
+                    let child_archive_path = match entry.path.as_slice() {
+                        b"." => dir_entry.name.into_vec(),
+                        parent_path => [parent_path, b"/", dir_entry.name.as_bytes()].concat(),
+                    };
+                    steps.push((dir_entry.path, child_archive_path, child_metadata));
+                }
+            }
+            Some(LinuxFileType::File) => {
+                entry.size = metadata.size.unwrap_or_default();
+                if !copy_options.is_preserve_permissions() {
+                    entry.mode = 0o666;
+                }
+                sink.write(&tar_codec::encode_header(&entry, include_times), process)
+                    .await?;
+                write_file_content(src_fs, &entry_path, entry.size, sink, process).await?;
+                report.files += 1;
+                report.bytes += entry.size;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("cannot copy special file {}", entry_path.to_string_lossy()),
+                ))
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+async fn write_file_content<S>(
+    src_fs: &S,
+    path: &OsStr,
+    size: u64,
+    sink: &mut ArchiveSink,
+    process: &mut Box<dyn LinuxProcess>,
+) -> io::Result<()>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+{
+    let read_options = *LinuxOpenOptions::new().read();
+    let mut reader = src_fs.open_file(path, &read_options).await?;
+    let mut buf = vec![0; ARCHIVE_CHUNK_SIZE.min(size as usize)];
+    let mut remaining = size;
+
+    // the header already announced the size, so a file that changed in the meantime can't be packed
+    while remaining > 0 {
+        let chunk = &mut buf[..ARCHIVE_CHUNK_SIZE.min(remaining as usize)];
+        reader.read_exact(chunk).await.map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                io::Error::other(format!("{} shrank while being archived", path.to_string_lossy()))
+            }
+            _ => err,
+        })?;
+        sink.write(chunk, process).await?;
+        remaining -= chunk.len() as u64;
+    }
+
+    sink.write(&vec![0; tar_codec::padding(size)], process).await
+}
+
+// collects archive data, compressing it if needed, and hands it over to the process in large pieces
+struct ArchiveSink {
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    buffer: Vec<u8>,
+}
+
+impl ArchiveSink {
+    fn new(compression: LinuxArchiveCompression) -> ArchiveSink {
+        ArchiveSink {
+            encoder: match compression {
+                LinuxArchiveCompression::None => None,
+                LinuxArchiveCompression::Gzip => Some(GzEncoder::new(Vec::new(), Compression::default())),
+            },
+            buffer: Vec::new(),
+        }
+    }
+
+    async fn write(&mut self, data: &[u8], process: &mut Box<dyn LinuxProcess>) -> io::Result<()> {
+        let pending = match &mut self.encoder {
+            Some(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            None => {
+                self.buffer.extend_from_slice(data);
+                &mut self.buffer
+            }
+        };
+
+        if pending.len() >= ARCHIVE_CHUNK_SIZE {
+            let chunk = std::mem::take(pending);
+            process.write_all_to_stdin(&chunk).await.map_err(process_error)?;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self, process: &mut Box<dyn LinuxProcess>) -> io::Result<()> {
+        self.write(&tar_codec::end_of_archive(), process).await?;
+        let rest = match self.encoder {
+            Some(encoder) => encoder.finish()?,
+            None => self.buffer,
+        };
+        process.write_all_to_stdin(&rest).await.map_err(process_error)
+    }
+}
+
+// reads archive data from a file, decompressing it if needed
+struct ArchiveSource<R> {
+    reader: R,
+    decoder: Option<GzDecoder<Vec<u8>>>,
+    buffer: Vec<u8>,
+    position: usize,
+    is_eof: bool,
+}
+
+impl<R> ArchiveSource<R>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    fn new(reader: R, compression: LinuxArchiveCompression) -> ArchiveSource<R> {
+        ArchiveSource {
+            reader,
+            decoder: match compression {
+                LinuxArchiveCompression::None => None,
+                LinuxArchiveCompression::Gzip => Some(GzDecoder::new(Vec::new())),
+            },
+            buffer: Vec::new(),
+            position: 0,
+            is_eof: false,
+        }
+    }
+
+    async fn read_exact(&mut self, output: &mut [u8]) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < output.len() {
+            if self.position == self.buffer.len() && !self.fill().await? {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the archive ended in the middle of an entry",
+                ));
+            }
+            let length = (output.len() - filled).min(self.buffer.len() - self.position);
+            output[filled..filled + length].copy_from_slice(&self.buffer[self.position..self.position + length]);
+            self.position += length;
+            filled += length;
+        }
+        Ok(())
+    }
+
+    async fn skip(&mut self, mut length: u64) -> io::Result<()> {
+        let mut buf = vec![0; BLOCK_SIZE];
+        while length > 0 {
+            let chunk_length = length.min(BLOCK_SIZE as u64) as usize;
+            self.read_exact(&mut buf[..chunk_length]).await?;
+            length -= chunk_length as u64;
+        }
+        Ok(())
+    }
+
+    // replaces the consumed buffer with the next piece of data, returning false once everything was read
+    async fn fill(&mut self) -> io::Result<bool> {
+        self.buffer.clear();
+        self.position = 0;
+
+        while self.buffer.is_empty() && !self.is_eof {
+            let mut chunk = vec![0; ARCHIVE_CHUNK_SIZE];
+            let read_bytes = self.reader.read(&mut chunk).await?;
+            self.is_eof = read_bytes == 0;
+
+            match &mut self.decoder {
+                Some(decoder) => {
+                    match self.is_eof {
+                        true => decoder.try_finish()?,
+                        false => decoder.write_all(&chunk[..read_bytes])?,
+                    }
+                    std::mem::swap(&mut self.buffer, decoder.get_mut());
+                }
+                None => self.buffer.extend_from_slice(&chunk[..read_bytes]),
+            }
+        }
+
+        Ok(!self.buffer.is_empty())
+    }
+}
+
+async fn extract_archive<R, D>(
+    mut source: ArchiveSource<R>,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    copy_options: &LinuxCopyOptions,
+) -> io::Result<LinuxCopyReport>
+where
+    R: AsyncRead + Send + Unpin,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let mut report = LinuxCopyReport::default();
+    // directory attributes are applied once their contents have been extracted, so that they don't alter the mtime
+    let mut finished_dirs = Vec::new();
+    let mut block = [0; BLOCK_SIZE];
+    let mut pax_records: Option<Vec<u8>> = None;
+    let mut long_path: Option<Vec<u8>> = None;
+    let mut long_link_target: Option<Vec<u8>> = None;
+
+    loop {
+        source.read_exact(&mut block).await?;
+        let mut entry = match tar_codec::decode_header(&block)? {
+            None => break,
+            Some(TarHeader::Entry(entry)) => entry,
+            Some(TarHeader::PaxExtension { size }) => {
+                pax_records = Some(read_content(&mut source, size).await?);
+                continue;
+            }
+            Some(TarHeader::GnuLongPath { size }) => {
+                long_path = Some(read_content(&mut source, size).await?);
+                continue;
+            }
+            Some(TarHeader::GnuLongLinkTarget { size }) => {
+                long_link_target = Some(read_content(&mut source, size).await?);
+                continue;
+            }
+            Some(TarHeader::Skipped { size }) => {
+                source.skip(size + tar_codec::padding(size) as u64).await?;
+                continue;
+            }
+            Some(TarHeader::Unsupported { path, type_flag }) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "cannot copy special file {} of tar type {}",
+                        String::from_utf8_lossy(&path),
+                        type_flag as char
+                    ),
+                ))
+            }
+        };
+        if let Some(long_path) = long_path.take() {
+            entry.path = tar_codec::trim_path(long_path.into_iter().take_while(|byte| *byte != 0).collect());
+        }
+        if let Some(long_link_target) = long_link_target.take() {
+            entry.link_target = long_link_target.into_iter().take_while(|byte| *byte != 0).collect();
+        }
+        if let Some(pax_records) = pax_records.take() {
+            tar_codec::apply_pax_records(&mut entry, &pax_records)?;
+        }
+
+        let entry_path = resolve_entry_path(dst_path, &entry.path)?;
+        let metadata = LinuxFileMetadata {
+            size: Some(entry.size),
+            permissions: Some(LinuxPermissions::from_bits_truncate(entry.mode)),
+            modified_time: Some(entry.modified_time),
+            // tar only carries access times in pax records, so they fall back to the modification time
+            accessed_time: Some(entry.accessed_time.unwrap_or(entry.modified_time)),
+            user_id: Some(entry.user_id),
+            group_id: Some(entry.group_id),
+            ..Default::default()
+        };
+
+        match entry.kind {
+            TarEntryKind::Dir => {
+                match dst_fs.get_metadata(&entry_path).await {
+                    Ok(dst_metadata) if dst_metadata.file_type == Some(LinuxFileType::Dir) => {}
+                    _ => dst_fs.create_dir(&entry_path).await?,
+                }
+                finished_dirs.push((entry_path, metadata));
+                report.dirs += 1;
+            }
+            TarEntryKind::Symlink => {
+                if dst_fs.get_symlink_metadata(&entry_path).await.is_ok() {
+                    dst_fs.remove_file(&entry_path).await?;
+                }
+                dst_fs
+                    .create_symlink(OsStr::from_bytes(&entry.link_target), &entry_path)
+                    .await?;
+                report.symlinks += 1;
+            }
+            TarEntryKind::File => {
+                let write_options = *LinuxOpenOptions::new().write().create().truncate();
+                let mut writer = dst_fs.open_file(&entry_path, &write_options).await?;
+                let mut buf = vec![0; ARCHIVE_CHUNK_SIZE.min(entry.size as usize)];
+                let mut remaining = entry.size;
+                while remaining > 0 {
+                    let chunk = &mut buf[..ARCHIVE_CHUNK_SIZE.min(remaining as usize)];
+                    source.read_exact(chunk).await?;
+                    writer.write_all(chunk).await?;
+                    remaining -= chunk.len() as u64;
+                }
+                writer.shutdown().await?;
+                source.skip(tar_codec::padding(entry.size) as u64).await?;
+
+                transfer::apply_metadata(dst_fs, &entry_path, &metadata, copy_options).await?;
+                report.files += 1;
+                report.bytes += entry.size;
+            }
+            TarEntryKind::HardLink => {
+                // copy_dir_recursively doesn't preserve hard links, so the already extracted file is copied instead
+                let link_target_path = resolve_entry_path(dst_path, &tar_codec::trim_path(entry.link_target))?;
+                let bytes = match dst_fs.copy_file(&link_target_path, &entry_path).await? {
+                    Some(bytes) => bytes,
+                    None => dst_fs.get_metadata(&entry_path).await?.size.unwrap_or_default(),
+                };
+                transfer::apply_metadata(dst_fs, &entry_path, &metadata, copy_options).await?;
+                report.files += 1;
+                report.bytes += bytes;
+            }
+        }
+    }
+
+    for (entry_path, metadata) in finished_dirs.into_iter().rev() {
+        transfer::apply_metadata(dst_fs, &entry_path, &metadata, copy_options).await?;
+    }
+
+    Ok(report)
+}
+
+async fn read_content<R>(source: &mut ArchiveSource<R>, size: u64) -> io::Result<Vec<u8>>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut content = vec![0; size as usize];
+    source.read_exact(&mut content).await?;
+    source.skip(tar_codec::padding(size) as u64).await?;
+    Ok(content)
+}
+
+// joins a path from the archive onto the destination, refusing paths that would escape it
+fn resolve_entry_path(dst_path: &OsStr, archive_path: &[u8]) -> io::Result<OsString> {
+    let mut entry_path = PathBuf::from(dst_path);
+    for component in Path::new(OsStr::from_bytes(archive_path)).components() {
+        match component {
+            Component::Normal(name) => entry_path.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "archive entry {} points outside of the destination",
+                        String::from_utf8_lossy(archive_path)
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(entry_path.into_os_string())
+}
@@ -0,0 +1,147 @@
+#[cfg(feature = "impl-ssh-common")]
+use std::{collections::BTreeMap, path::Path};
+use std::{ffi::OsStr, io};
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+use std::{ffi::OsString, os::unix::ffi::OsStrExt};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::filesystem::{LinuxChecksumAlgorithm, LinuxFilesystem, LinuxOpenOptions};
+
+enum Hasher {
+    Md5(md5::Context),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: LinuxChecksumAlgorithm) -> io::Result<Hasher> {
+        match algorithm {
+            LinuxChecksumAlgorithm::Md5 => Ok(Hasher::Md5(md5::Context::new())),
+            LinuxChecksumAlgorithm::Sha1 => Ok(Hasher::Sha1(Sha1::new())),
+            LinuxChecksumAlgorithm::Sha256 => Ok(Hasher::Sha256(Sha256::new())),
+            LinuxChecksumAlgorithm::Sha512 => Ok(Hasher::Sha512(Sha512::new())),
+            LinuxChecksumAlgorithm::Blake3 => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "BLAKE3 checksums can only be computed on hosts with b3sum installed",
+            )),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(context) => context.consume(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Md5(context) => encode_hex(&context.compute().0),
+            Hasher::Sha1(hasher) => encode_hex(&hasher.finalize()),
+            Hasher::Sha256(hasher) => encode_hex(&hasher.finalize()),
+            Hasher::Sha512(hasher) => encode_hex(&hasher.finalize()),
+        }
+    }
+}
+
+// reads the whole file through the filesystem and hashes it in-process
+pub(crate) async fn hash_file<F>(filesystem: &F, path: &OsStr, algorithm: LinuxChecksumAlgorithm) -> io::Result<String>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    let read_options = *LinuxOpenOptions::new().read();
+    let reader = filesystem.open_file(path, &read_options).await?;
+    hash_reader(reader, algorithm).await
+}
+
+pub(crate) async fn hash_reader<R>(mut reader: R, algorithm: LinuxChecksumAlgorithm) -> io::Result<String>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut hasher = Hasher::new(algorithm)?;
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let read_bytes = reader.read(&mut buf).await?;
+        if read_bytes == 0 {
+            break;
+        }
+        hasher.update(&buf[..read_bytes]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+// turns the paths of checksums computed below the root into paths relative to it
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn relativize_checksums(root: &OsStr, checksums: Vec<(OsString, String)>) -> BTreeMap<OsString, String> {
+    checksums
+        .into_iter()
+        .map(|(path, checksum)| {
+            let relative_path = Path::new(&path)
+                .strip_prefix(root)
+                .map(|relative_path| relative_path.as_os_str().to_os_string())
+                .unwrap_or(path);
+            (relative_path, checksum)
+        })
+        .collect()
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// parses the output of the coreutils *sum commands and b3sum: "HASH  path" or "HASH *path" per line, where lines of
+// paths containing a newline or backslash start with a backslash and have these characters escaped
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+pub(crate) fn parse_sum_output(output: &[u8]) -> io::Result<Vec<(OsString, String)>> {
+    let mut checksums = Vec::new();
+
+    for line in output.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+        let (escaped, line) = match line.strip_prefix(b"\\") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let separator_index = line
+            .iter()
+            .position(|byte| *byte == b' ')
+            .filter(|index| *index + 1 < line.len())
+            .ok_or_else(|| io::Error::other(format!("malformed checksum line: {}", String::from_utf8_lossy(line))))?;
+
+        let hash = String::from_utf8_lossy(&line[..separator_index]).to_lowercase();
+        // the separator is followed by a space in text mode or an asterisk in binary mode
+        let path = &line[separator_index + 2..];
+        let path = match escaped {
+            true => unescape_sum_path(path),
+            false => path.to_vec(),
+        };
+        checksums.push((OsStr::from_bytes(&path).to_os_string(), hash));
+    }
+
+    Ok(checksums)
+}
+
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+fn unescape_sum_path(path: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte != b'\\' {
+            output.push(*byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => output.push(b'\n'),
+            Some(b'r') => output.push(b'\r'),
+            Some(escaped_byte) => output.push(*escaped_byte),
+            None => output.push(b'\\'),
+        }
+    }
+    output
+}
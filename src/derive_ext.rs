@@ -1,16 +1,23 @@
 use shell_escape::unix::escape;
 use uuid::Uuid;
 
-use crate::executor::LinuxProcessConfiguration;
+use crate::executor::{LinuxOutputTarget, LinuxProcessConfiguration};
+
+pub struct DerivedShellCommand {
+    pub command: String,
+    pub pid_file: String,
+    pub fd_files: Vec<(u32, String)>,
+}
 
 pub trait DeriveExt {
-    fn derive_shell_command(&self) -> (String, String);
+    fn derive_shell_command(&self) -> DerivedShellCommand;
 }
 
 impl DeriveExt for LinuxProcessConfiguration {
-    fn derive_shell_command(&self) -> (String, String) {
+    fn derive_shell_command(&self) -> DerivedShellCommand {
         // example of desugared command:
-        // (cd working_dir && echo $$ > /tmp/pid-UUID && env1=val1 env2=val2 ... exec actual_command arg1 arg2 ...)
+        // (cd working_dir && echo $$ > /tmp/pid-UUID && env1=val1 ... exec actual_command args >/dev/null 3>/tmp/fd-UUID)
+        // or, with a shell mode set, the same command passed to the shell: bash -lc '(cd working_dir && ...)'
 
         let pid_file = format!("/tmp/pid-{}", Uuid::new_v4());
         let mut sections: Vec<String> = Vec::new();
@@ -43,6 +50,21 @@ impl DeriveExt for LinuxProcessConfiguration {
             }
             exec_section = exec_section.trim_end().into();
         }
+        // 3.4. feed stdin from a file on the remote
+        if let Some(stdin_file) = &self.stdin_file {
+            exec_section.push_str(" < ");
+            exec_section.push_str(escape(stdin_file.into()).to_string().as_str());
+        }
+        // 3.5. redirect stdout and stderr into /dev/null or a file on the remote, so that they don't travel over SSH
+        push_output_redirection(&mut exec_section, "", &self.stdout_target);
+        push_output_redirection(&mut exec_section, "2", &self.stderr_target);
+        // 3.6. redirect captured extra fds into files to be read via SFTP later: 3>/tmp/fd-UUID
+        let mut fd_files = Vec::new();
+        for fd in &self.captured_fds {
+            let fd_file = format!("/tmp/fd-{}", Uuid::new_v4());
+            exec_section.push_str(format!(" {}>{}", fd, fd_file).as_str());
+            fd_files.push((*fd, fd_file));
+        }
         sections.push(exec_section);
 
         // join sections with && and wrap them in a subshell
@@ -50,6 +72,33 @@ impl DeriveExt for LinuxProcessConfiguration {
         output.push_str(sections.join(" && ").as_str());
         output.push(')');
 
-        (output, pid_file)
+        // 4. hand the whole subshell over to the configured shell, e.g.: bash -lc '(cd working_dir && ...)'
+        if let Some((shell, shell_args)) = self.shell_mode.invocation() {
+            let mut shell_section = escape(shell.into()).to_string();
+            for shell_arg in shell_args {
+                shell_section.push(' ');
+                shell_section.push_str(escape(shell_arg.into()).to_string().as_str());
+            }
+            shell_section.push(' ');
+            shell_section.push_str(escape(output.into()).to_string().as_str());
+            output = shell_section;
+        }
+
+        DerivedShellCommand {
+            command: output,
+            pid_file,
+            fd_files,
+        }
+    }
+}
+
+fn push_output_redirection(exec_section: &mut String, fd_prefix: &str, output_target: &LinuxOutputTarget) {
+    match output_target {
+        LinuxOutputTarget::Null => exec_section.push_str(format!(" {}>/dev/null", fd_prefix).as_str()),
+        LinuxOutputTarget::File { path, append } => {
+            let operator = if *append { ">>" } else { ">" };
+            exec_section.push_str(format!(" {}{} {}", fd_prefix, operator, escape(path.into())).as_str());
+        }
+        LinuxOutputTarget::Inherit | LinuxOutputTarget::Pipe => {}
     }
 }
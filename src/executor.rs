@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
 use async_trait::async_trait;
 use nix::{errno::Errno, sys::signal::Signal};
 use regex::Regex;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const STDIN_PIPE_BUFFER_SIZE: usize = 64 * 1024;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LinuxProcessConfiguration {
@@ -10,12 +13,42 @@ pub struct LinuxProcessConfiguration {
     pub(crate) args: Vec<String>,
     pub(crate) envs: HashMap<String, String>,
     pub(crate) working_dir: Option<String>,
-    pub(crate) redirect_stdout: bool,
+    pub(crate) stdout_target: LinuxOutputTarget,
     pub(crate) redirect_stdin: bool,
-    pub(crate) redirect_stderr: bool,
+    pub(crate) stdin_file: Option<String>,
+    pub(crate) stderr_target: LinuxOutputTarget,
+    pub(crate) captured_fds: Vec<u32>,
     pub(crate) user_id: Option<u32>,
     pub(crate) group_id: Option<u32>,
     pub(crate) process_group_id: Option<u32>,
+    pub(crate) shell_mode: LinuxShellMode,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LinuxShellMode {
+    /// Run the program directly (natively) or inside the default shell of the SSH server (remotely)
+    #[default]
+    Direct,
+    /// Run the program inside "sh -c"
+    Sh,
+    /// Run the program inside "bash -lc", so that profile scripts are sourced beforehand
+    BashLogin,
+    /// Run the program inside a user-specified shell, the args must end with the shell's equivalent of "-c"
+    Custom { shell: String, args: Vec<String> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LinuxOutputTarget {
+    #[default]
+    Null,
+    /// Remotely, openssh forwards the stream to the current process while russh only passes it to the handler
+    Inherit,
+    Pipe,
+    /// A file on the host the process runs on
+    File {
+        path: String,
+        append: bool,
+    },
 }
 
 pub enum LinuxProcessExpectation {
@@ -48,7 +81,7 @@ pub enum LinuxProcessError {
     KillUtilityFailed { status_code: Option<i64> },
     IO(std::io::Error),
     LowLevel(Errno),
-    Other(Box<dyn std::error::Error>),
+    Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -84,12 +117,15 @@ impl LinuxProcessConfiguration {
             args: Vec::new(),
             envs: HashMap::new(),
             working_dir: None,
-            redirect_stdout: false,
+            stdout_target: LinuxOutputTarget::Null,
             redirect_stdin: false,
-            redirect_stderr: false,
+            stdin_file: None,
+            stderr_target: LinuxOutputTarget::Null,
+            captured_fds: Vec::new(),
             user_id: None,
             group_id: None,
             process_group_id: None,
+            shell_mode: LinuxShellMode::Direct,
         }
     }
 
@@ -128,17 +164,43 @@ impl LinuxProcessConfiguration {
     }
 
     pub fn redirect_stdout(&mut self) -> &mut Self {
-        self.redirect_stdout = true;
+        self.stdout_target = LinuxOutputTarget::Pipe;
+        self
+    }
+
+    pub fn stdout_target(&mut self, stdout_target: LinuxOutputTarget) -> &mut Self {
+        self.stdout_target = stdout_target;
         self
     }
 
     pub fn redirect_stdin(&mut self) -> &mut Self {
         self.redirect_stdin = true;
+        self.stdin_file = None;
+        self
+    }
+
+    /// A file on the host the process runs on, replacing redirect_stdin
+    pub fn stdin_file(&mut self, path: impl Into<String>) -> &mut Self {
+        self.stdin_file = Some(path.into());
+        self.redirect_stdin = false;
         self
     }
 
     pub fn redirect_stderr(&mut self) -> &mut Self {
-        self.redirect_stderr = true;
+        self.stderr_target = LinuxOutputTarget::Pipe;
+        self
+    }
+
+    pub fn stderr_target(&mut self, stderr_target: LinuxOutputTarget) -> &mut Self {
+        self.stderr_target = stderr_target;
+        self
+    }
+
+    /// Captures into stdout_extended. Remote impls only read it once the process exits, so nothing is streamed
+    pub fn capture_fd(&mut self, fd: u32) -> &mut Self {
+        if fd > 2 && !self.captured_fds.contains(&fd) {
+            self.captured_fds.push(fd);
+        }
         self
     }
 
@@ -156,6 +218,31 @@ impl LinuxProcessConfiguration {
         self.process_group_id = Some(process_group_id);
         self
     }
+
+    pub fn shell_mode(&mut self, shell_mode: LinuxShellMode) -> &mut Self {
+        self.shell_mode = shell_mode;
+        self
+    }
+}
+
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+impl LinuxShellMode {
+    // the shell program and its args, to which the command string should be appended as the last argument
+    pub(crate) fn invocation(&self) -> Option<(String, Vec<String>)> {
+        match self {
+            LinuxShellMode::Direct => None,
+            LinuxShellMode::Sh => Some(("sh".into(), vec!["-c".into()])),
+            LinuxShellMode::BashLogin => Some(("bash".into(), vec!["-lc".into()])),
+            LinuxShellMode::Custom { shell, args } => Some((shell.clone(), args.clone())),
+        }
+    }
+}
+
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+impl LinuxOutputTarget {
+    pub(crate) fn is_piped(&self) -> bool {
+        matches!(self, LinuxOutputTarget::Pipe)
+    }
 }
 
 #[async_trait]
@@ -164,6 +251,34 @@ pub trait LinuxProcess: Send {
 
     async fn write_to_stdin(&mut self, data: &[u8]) -> Result<usize, LinuxProcessError>;
 
+    async fn write_all_to_stdin(&mut self, mut data: &[u8]) -> Result<(), LinuxProcessError> {
+        while !data.is_empty() {
+            let written = self.write_to_stdin(data).await?;
+            if written == 0 {
+                return Err(LinuxProcessError::IO(io::ErrorKind::WriteZero.into()));
+            }
+            data = &data[written..];
+        }
+
+        Ok(())
+    }
+
+    async fn pipe_stdin_from(&mut self, reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<u64, LinuxProcessError> {
+        let mut buf = vec![0; STDIN_PIPE_BUFFER_SIZE];
+        let mut total_written: u64 = 0;
+
+        loop {
+            let read = reader.read(&mut buf).await.map_err(LinuxProcessError::IO)?;
+            if read == 0 {
+                break;
+            }
+            self.write_all_to_stdin(&buf[..read]).await?;
+            total_written += read as u64;
+        }
+
+        Ok(total_written)
+    }
+
     async fn close_stdin(&mut self) -> Result<(), LinuxProcessError>;
 
     fn get_current_output(&self) -> Result<LinuxProcessOutput, LinuxProcessError>;
@@ -185,5 +300,12 @@ pub trait LinuxExecutor {
         process_configuration: &LinuxProcessConfiguration,
     ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError>;
 
+    async fn execute_script(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError>;
+
     async fn send_signal(&self, signal: Signal, process_id: u32) -> Result<(), LinuxProcessError>;
 }
@@ -1,13 +1,27 @@
 use std::{
+    collections::{BTreeMap, HashSet},
     ffi::{OsStr, OsString},
     fs::Permissions,
-    io,
+    io::{self, SeekFrom},
     os::unix::fs::PermissionsExt,
-    time::SystemTime,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use futures_util::{
+    future::try_join_all,
+    stream::{self, BoxStream, StreamExt, TryStreamExt},
+    Stream,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    checksum, glob_expander,
+    transfer::{self, LinuxCopyOptions, LinuxCopyReport},
+    walker,
+};
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct LinuxOpenOptions {
@@ -16,6 +30,11 @@ pub struct LinuxOpenOptions {
     append: bool,
     truncate: bool,
     create: bool,
+    create_new: bool,
+    mode: Option<LinuxPermissions>,
+    no_follow: bool,
+    directory: bool,
+    sync: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -25,7 +44,33 @@ pub struct LinuxDirEntry {
     pub path: OsString,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinuxWalkOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) include_patterns: Vec<String>,
+    pub(crate) exclude_patterns: Vec<String>,
+    pub(crate) file_types: Vec<LinuxFileType>,
+    pub(crate) order: LinuxWalkOrder,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LinuxWalkOrder {
+    /// Yield directories before their contents
+    #[default]
+    PreOrder,
+    /// Yield directories after their contents
+    PostOrder,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinuxWalkEntry {
+    pub dir_entry: LinuxDirEntry,
+    pub metadata: LinuxFileMetadata,
+    pub depth: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct LinuxFileMetadata {
     pub file_type: Option<LinuxFileType>,
     pub size: Option<u64>,
@@ -33,10 +78,22 @@ pub struct LinuxFileMetadata {
     pub modified_time: Option<SystemTime>,
     pub accessed_time: Option<SystemTime>,
     pub created_time: Option<SystemTime>,
+    /// The time of the last status change, i.e. ctime
+    pub changed_time: Option<SystemTime>,
     pub user_id: Option<u32>,
     pub user_name: Option<String>,
     pub group_id: Option<u32>,
     pub group_name: Option<String>,
+    pub inode: Option<u64>,
+    /// The device containing the file, which together with the inode identifies it
+    pub device_id: Option<u64>,
+    /// The device a block or character device file represents
+    pub special_device_id: Option<u64>,
+    pub hard_link_count: Option<u64>,
+    /// The preferred block size for I/O
+    pub block_size: Option<u64>,
+    /// The number of allocated 512-byte blocks
+    pub block_count: Option<u64>,
 }
 
 bitflags::bitflags! {
@@ -60,6 +117,42 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct LinuxAclPermissions: u16 {
+        const READ = 0o4;
+        const WRITE = 0o2;
+        const EXECUTE = 0o1;
+    }
+}
+
+/// A POSIX ACL. The access entries of a file always include the owning user, owning group and other entries, and a
+/// mask entry as soon as there are named users or groups
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LinuxAcl {
+    pub access: Vec<LinuxAclEntry>,
+    /// The ACL inherited by entries created inside a directory, empty when there's none
+    pub default: Vec<LinuxAclEntry>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LinuxAclEntry {
+    pub tag: LinuxAclTag,
+    pub permissions: LinuxAclPermissions,
+}
+
+/// Who an ACL entry applies to, ordered like the entries of a valid ACL
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LinuxAclTag {
+    UserOwner,
+    User(u32),
+    GroupOwner,
+    Group(u32),
+    /// The upper bound of the permissions granted by named user and group entries and the owning group entry
+    Mask,
+    Other,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LinuxPermissionsUnknownBitSetError {
     pub mode: u32,
@@ -70,9 +163,138 @@ pub enum LinuxFileType {
     File,
     Dir,
     Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
     Other,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinuxChecksumAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    /// Only available on hosts with b3sum installed, never computed in-process
+    Blake3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinuxLockMode {
+    /// Held by any number of holders at once, as long as nobody holds an exclusive lock
+    Shared,
+    Exclusive,
+}
+
+/// An advisory lock on a file, which is released when dropped. Use unlock to find out whether releasing it failed
+pub struct LinuxFileLock {
+    holder: Box<dyn LinuxLockHolder>,
+}
+
+// what keeps a lock held for an impl, which releases it when dropped
+#[async_trait]
+pub(crate) trait LinuxLockHolder: Send {
+    async fn release(self: Box<Self>) -> io::Result<()>;
+}
+
+/// The statistics of the filesystem containing a path. Block counts are in units of the fragment size
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LinuxFsStats {
+    /// The preferred block size for I/O
+    pub block_size: u64,
+    pub fragment_size: u64,
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    /// The free blocks that unprivileged users may use
+    pub available_blocks: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    /// The free inodes that unprivileged users may use
+    pub available_inodes: u64,
+    /// None when the host doesn't report them. SFTP only reports the read-only and nosuid flags
+    pub mount_flags: Option<LinuxMountFlags>,
+}
+
+bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct LinuxMountFlags: u64 {
+        const READ_ONLY = 0x1;
+        const NO_SUID = 0x2;
+        const NO_DEV = 0x4;
+        const NO_EXEC = 0x8;
+        const SYNCHRONOUS = 0x10;
+        const MANDATORY_LOCK = 0x40;
+        const NO_ATIME = 0x400;
+        const NO_DIR_ATIME = 0x800;
+        const RELATIME = 0x1000;
+    }
+}
+
+impl LinuxFileLock {
+    #[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+    pub(crate) fn new(holder: impl LinuxLockHolder + 'static) -> LinuxFileLock {
+        LinuxFileLock {
+            holder: Box::new(holder),
+        }
+    }
+
+    /// Release the lock, reporting the errors that dropping it would ignore
+    pub async fn unlock(self) -> io::Result<()> {
+        self.holder.release().await
+    }
+}
+
+impl LinuxFsStats {
+    /// The bytes that unprivileged users may still write
+    pub fn available_bytes(&self) -> u64 {
+        self.available_blocks.saturating_mul(self.fragment_size)
+    }
+}
+
+impl LinuxFileType {
+    // the file type encoded in the S_IFMT bits of a mode
+    #[cfg(feature = "impl-russh")]
+    pub(crate) fn from_mode(mode: u32) -> LinuxFileType {
+        match mode & 0o170000 {
+            0o100000 => LinuxFileType::File,
+            0o040000 => LinuxFileType::Dir,
+            0o120000 => LinuxFileType::Symlink,
+            0o010000 => LinuxFileType::Fifo,
+            0o140000 => LinuxFileType::Socket,
+            0o060000 => LinuxFileType::BlockDevice,
+            0o020000 => LinuxFileType::CharDevice,
+            _ => LinuxFileType::Other,
+        }
+    }
+}
+
+impl LinuxChecksumAlgorithm {
+    // the coreutils-style command computing the checksum
+    #[cfg(feature = "impl-ssh-common")]
+    pub(crate) fn sum_command(&self) -> &'static str {
+        match self {
+            LinuxChecksumAlgorithm::Md5 => "md5sum",
+            LinuxChecksumAlgorithm::Sha1 => "sha1sum",
+            LinuxChecksumAlgorithm::Sha256 => "sha256sum",
+            LinuxChecksumAlgorithm::Sha512 => "sha512sum",
+            LinuxChecksumAlgorithm::Blake3 => "b3sum",
+        }
+    }
+
+    // the name of the algorithm in the SFTP check-file extension, which doesn't define BLAKE3
+    #[cfg(feature = "impl-russh")]
+    pub(crate) fn check_file_name(&self) -> Option<&'static str> {
+        match self {
+            LinuxChecksumAlgorithm::Md5 => Some("md5"),
+            LinuxChecksumAlgorithm::Sha1 => Some("sha1"),
+            LinuxChecksumAlgorithm::Sha256 => Some("sha256"),
+            LinuxChecksumAlgorithm::Sha512 => Some("sha512"),
+            LinuxChecksumAlgorithm::Blake3 => None,
+        }
+    }
+}
+
 impl LinuxOpenOptions {
     pub fn new() -> LinuxOpenOptions {
         LinuxOpenOptions {
@@ -81,6 +303,11 @@ impl LinuxOpenOptions {
             append: false,
             truncate: false,
             create: false,
+            create_new: false,
+            mode: None,
+            no_follow: false,
+            directory: false,
+            sync: false,
         }
     }
 
@@ -104,6 +331,26 @@ impl LinuxOpenOptions {
         self.create
     }
 
+    pub fn is_create_new(&self) -> bool {
+        self.create_new
+    }
+
+    pub fn get_mode(&self) -> Option<LinuxPermissions> {
+        self.mode
+    }
+
+    pub fn is_no_follow(&self) -> bool {
+        self.no_follow
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.directory
+    }
+
+    pub fn is_sync(&self) -> bool {
+        self.sync
+    }
+
     pub fn read(&mut self) -> &mut LinuxOpenOptions {
         self.read = true;
         self
@@ -128,6 +375,120 @@ impl LinuxOpenOptions {
         self.create = true;
         self
     }
+
+    /// Create the file, failing if anything exists at the path, even a dangling symlink
+    pub fn create_new(&mut self) -> &mut LinuxOpenOptions {
+        self.create_new = true;
+        self
+    }
+
+    /// The permissions of a file this creates, which are reduced by the umask natively. SFTP clients don't pass them
+    /// along, so remote backends set them right after creating the file
+    pub fn mode(&mut self, mode: LinuxPermissions) -> &mut LinuxOpenOptions {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Fail if the path is a symlink. Remote backends check this before opening, missing a symlink placed in between
+    pub fn no_follow(&mut self) -> &mut LinuxOpenOptions {
+        self.no_follow = true;
+        self
+    }
+
+    /// Fail unless the path is a directory, which can then be synced through LinuxFile::sync_all
+    pub fn directory(&mut self) -> &mut LinuxOpenOptions {
+        self.directory = true;
+        self
+    }
+
+    /// Make writes return only once they reached the disk, like O_SYNC. Remote backends don't support this
+    pub fn sync(&mut self) -> &mut LinuxOpenOptions {
+        self.sync = true;
+        self
+    }
+
+    // whether opening over SFTP needs the type of what's at the path beforehand, which is looked up without following
+    // symlinks if no_follow is set
+    #[cfg(feature = "impl-ssh-common")]
+    pub(crate) fn needs_sftp_file_type(&self) -> bool {
+        self.no_follow || self.directory || (self.mode.is_some() && self.create && !self.create_new)
+    }
+
+    // SFTP v3 has no flags for no_follow, directory and sync, so the first two are checked against the file type
+    // looked up beforehand. A lookup that failed, leaving no file type, is left for the open itself to report
+    #[cfg(feature = "impl-ssh-common")]
+    pub(crate) fn check_sftp_file_type(&self, file_type: Option<LinuxFileType>) -> io::Result<()> {
+        if self.sync {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "SFTP can't open files for synchronous writes, use LinuxFile::sync_all instead",
+            ));
+        }
+        match file_type {
+            Some(LinuxFileType::Symlink) if self.no_follow => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the path is a symlink, which no_follow forbids",
+            )),
+            Some(file_type) if self.directory && file_type != LinuxFileType::Dir => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the path isn't a directory",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // the mode to set after opening over SFTP, if the open created the file
+    #[cfg(feature = "impl-ssh-common")]
+    pub(crate) fn sftp_mode_to_apply(&self, file_type: Option<LinuxFileType>) -> Option<LinuxPermissions> {
+        match self.create_new || (self.create && file_type.is_none()) {
+            true => self.mode,
+            false => None,
+        }
+    }
+}
+
+impl LinuxWalkOptions {
+    pub fn new() -> LinuxWalkOptions {
+        LinuxWalkOptions::default()
+    }
+
+    /// Only descend this many levels, the entries directly inside the walked directory being at depth 1. A depth of
+    /// 0 yields nothing
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut LinuxWalkOptions {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Descend into symlinked directories and report the metadata of symlink targets instead of the symlinks
+    pub fn follow_symlinks(&mut self) -> &mut LinuxWalkOptions {
+        self.follow_symlinks = true;
+        self
+    }
+
+    /// Only yield entries whose path relative to the walked directory matches one of the included glob patterns
+    pub fn include(&mut self, pattern: impl Into<String>) -> &mut LinuxWalkOptions {
+        self.include_patterns.push(pattern.into());
+        self
+    }
+
+    /// Skip entries whose path relative to the walked directory matches the glob pattern, along with their contents
+    pub fn exclude(&mut self, pattern: impl Into<String>) -> &mut LinuxWalkOptions {
+        self.exclude_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only yield entries of the given file types, without affecting which directories get descended into
+    pub fn file_type(&mut self, file_type: LinuxFileType) -> &mut LinuxWalkOptions {
+        if !self.file_types.contains(&file_type) {
+            self.file_types.push(file_type);
+        }
+        self
+    }
+
+    pub fn order(&mut self, order: LinuxWalkOrder) -> &mut LinuxWalkOptions {
+        self.order = order;
+        self
+    }
 }
 
 impl TryFrom<Permissions> for LinuxPermissions {
@@ -145,32 +506,230 @@ impl From<LinuxPermissions> for Permissions {
     }
 }
 
+/// A file opened through a LinuxFilesystem, which can be boxed as a trait object
+#[async_trait]
+pub trait LinuxFile: AsyncRead + AsyncWrite + AsyncSeek + Send + Unpin {
+    /// Owner names aren't resolved
+    async fn metadata(&mut self) -> io::Result<LinuxFileMetadata> {
+        Err(unsupported_error("metadata"))
+    }
+
+    /// Leaves the cursor where it is
+    async fn set_len(&mut self, _size: u64) -> io::Result<()> {
+        Err(unsupported_error("set_len"))
+    }
+
+    /// Read at an offset, restoring the cursor afterwards
+    async fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let position = self.stream_position().await?;
+        self.seek(SeekFrom::Start(offset)).await?;
+        let read_result = self.read(buf).await;
+        self.seek(SeekFrom::Start(position)).await?;
+        read_result
+    }
+
+    /// Write at an offset, restoring the cursor afterwards. Files opened for appending still write at their end
+    async fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let position = self.stream_position().await?;
+        self.seek(SeekFrom::Start(offset)).await?;
+        let write_result = self.write(buf).await;
+        self.seek(SeekFrom::Start(position)).await?;
+        write_result
+    }
+
+    async fn sync_all(&mut self) -> io::Result<()> {
+        Err(unsupported_error("sync_all"))
+    }
+
+    /// Reports the errors that dropping the file would swallow
+    async fn close(mut self: Box<Self>) -> io::Result<()> {
+        self.flush().await
+    }
+}
+
 #[async_trait]
 pub trait LinuxFilesystem {
     async fn exists(&self, path: &OsStr) -> io::Result<bool>;
 
     async fn create_file(&self, path: &OsStr) -> io::Result<()>;
 
-    async fn open_file(
-        &self,
-        path: &OsStr,
-        open_options: &LinuxOpenOptions,
-    ) -> io::Result<impl AsyncReadExt + AsyncWriteExt + AsyncSeekExt>;
+    async fn open_file(&self, path: &OsStr, open_options: &LinuxOpenOptions) -> io::Result<impl LinuxFile>;
+
+    async fn read_file(&self, path: &OsStr) -> io::Result<Vec<u8>> {
+        let read_options = *LinuxOpenOptions::new().read();
+        let mut file = self.open_file(path, &read_options).await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        Ok(contents)
+    }
+
+    async fn read_to_string(&self, path: &OsStr) -> io::Result<String> {
+        String::from_utf8(self.read_file(path).await?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    async fn write_file(&self, path: &OsStr, contents: &[u8]) -> io::Result<()> {
+        let write_options = *LinuxOpenOptions::new().write().create().truncate();
+        let mut file = self.open_file(path, &write_options).await?;
+        file.write_all(contents).await?;
+        Box::new(file).close().await
+    }
+
+    /// Writes a hidden sibling and renames it over the file, keeping its mode and owner
+    async fn write_file_atomic(&self, path: &OsStr, contents: &[u8]) -> io::Result<()> {
+        let old_metadata = match self.get_metadata(path).await {
+            Ok(metadata) => Some(metadata),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        let tmp_path = atomic_tmp_path(path)?;
+        self.write_file(&tmp_path, contents).await?;
+        let result = async {
+            if let Some(old_metadata) = old_metadata {
+                if let Some(permissions) = old_metadata.permissions {
+                    self.set_permissions(&tmp_path, permissions).await?;
+                }
+                let tmp_metadata = self.get_metadata(&tmp_path).await?;
+                let user_id = old_metadata
+                    .user_id
+                    .filter(|user_id| Some(*user_id) != tmp_metadata.user_id);
+                let group_id = old_metadata
+                    .group_id
+                    .filter(|group_id| Some(*group_id) != tmp_metadata.group_id);
+                if user_id.is_some() || group_id.is_some() {
+                    self.set_owner(&tmp_path, user_id, group_id).await?;
+                }
+            }
+            // the rename is atomic either way, it just may not survive a crash without the sync
+            match self.sync_file(&tmp_path).await {
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => {}
+                result => result?,
+            }
+            self.rename_file(&tmp_path, path).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = self.remove_file(&tmp_path).await;
+        }
+        result
+    }
 
     async fn rename_file(&self, old_path: &OsStr, new_path: &OsStr) -> io::Result<()>;
 
     async fn copy_file(&self, old_path: &OsStr, new_path: &OsStr) -> io::Result<Option<u64>>;
 
+    /// See transfer for copying between filesystems
+    async fn copy_dir_recursively(
+        &self,
+        old_path: &OsStr,
+        new_path: &OsStr,
+        copy_options: &LinuxCopyOptions,
+    ) -> io::Result<LinuxCopyReport> {
+        transfer::copy_tree(self, old_path, self, new_path, copy_options, true).await
+    }
+
     async fn canonicalize(&self, path: &OsStr) -> io::Result<OsString>;
 
     async fn create_symlink(&self, source_path: &OsStr, destination_path: &OsStr) -> io::Result<()>;
 
     async fn create_hard_link(&self, source_path: &OsStr, destination_path: &OsStr) -> io::Result<()>;
 
+    /// The permissions aren't affected by the umask
+    async fn create_fifo(&self, _path: &OsStr, _permissions: LinuxPermissions) -> io::Result<()> {
+        Err(unsupported_error("create_fifo"))
+    }
+
+    /// The permissions aren't affected by the umask
+    async fn create_device_node(
+        &self,
+        _path: &OsStr,
+        _file_type: LinuxFileType,
+        _major: u32,
+        _minor: u32,
+        _permissions: LinuxPermissions,
+    ) -> io::Result<()> {
+        Err(unsupported_error("create_device_node"))
+    }
+
     async fn read_link(&self, path: &OsStr) -> io::Result<OsString>;
 
     async fn set_permissions(&self, path: &OsStr, permissions: LinuxPermissions) -> io::Result<()>;
 
+    async fn set_times(&self, _path: &OsStr, _accessed_time: SystemTime, _modified_time: SystemTime) -> io::Result<()> {
+        Err(unsupported_error("set_times"))
+    }
+
+    /// An id passed as None is left unchanged
+    async fn set_owner(&self, _path: &OsStr, _user_id: Option<u32>, _group_id: Option<u32>) -> io::Result<()> {
+        Err(unsupported_error("set_owner"))
+    }
+
+    /// Like lchown, changes the symlink itself
+    async fn set_symlink_owner(&self, _path: &OsStr, _user_id: Option<u32>, _group_id: Option<u32>) -> io::Result<()> {
+        Err(unsupported_error("set_symlink_owner"))
+    }
+
+    async fn set_len(&self, _path: &OsStr, _size: u64) -> io::Result<()> {
+        Err(unsupported_error("set_len"))
+    }
+
+    /// Remote impls need fallocate from util-linux on the host
+    async fn allocate(&self, _path: &OsStr, _offset: u64, _len: u64) -> io::Result<()> {
+        Err(unsupported_error("allocate"))
+    }
+
+    async fn sync_file(&self, _path: &OsStr) -> io::Result<()> {
+        Err(unsupported_error("sync_file"))
+    }
+
+    /// Remote impls do a full sync_file
+    async fn sync_file_data(&self, path: &OsStr) -> io::Result<()> {
+        self.sync_file(path).await
+    }
+
+    /// Unrelated to sync::sync_dir, which synchronises two directory trees
+    async fn sync_dir(&self, path: &OsStr) -> io::Result<()> {
+        self.sync_file(path).await
+    }
+
+    async fn get_xattr(&self, _path: &OsStr, _name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        Err(unsupported_error("get_xattr"))
+    }
+
+    async fn set_xattr(&self, _path: &OsStr, _name: &OsStr, _value: &[u8]) -> io::Result<()> {
+        Err(unsupported_error("set_xattr"))
+    }
+
+    async fn list_xattrs(&self, _path: &OsStr) -> io::Result<Vec<OsString>> {
+        Err(unsupported_error("list_xattrs"))
+    }
+
+    async fn remove_xattr(&self, _path: &OsStr, _name: &OsStr) -> io::Result<()> {
+        Err(unsupported_error("remove_xattr"))
+    }
+
+    /// Files without an extended ACL report the one equivalent to their mode
+    async fn get_acl(&self, _path: &OsStr) -> io::Result<LinuxAcl> {
+        Err(unsupported_error("get_acl"))
+    }
+
+    /// An empty default ACL removes it
+    async fn set_acl(&self, _path: &OsStr, _acl: &LinuxAcl) -> io::Result<()> {
+        Err(unsupported_error("set_acl"))
+    }
+
+    /// Creates the file if needed and fails with TimedOut once the timeout passes. Remote locks are held by a flock
+    /// process on the host, so they're also released when the connection drops
+    async fn lock_file(
+        &self,
+        _path: &OsStr,
+        _mode: LinuxLockMode,
+        _timeout: Option<Duration>,
+    ) -> io::Result<LinuxFileLock> {
+        Err(unsupported_error("lock_file"))
+    }
+
     async fn remove_file(&self, path: &OsStr) -> io::Result<()>;
 
     async fn create_dir(&self, path: &OsStr) -> io::Result<()>;
@@ -179,6 +738,81 @@ pub trait LinuxFilesystem {
 
     async fn list_dir(&self, path: &OsStr) -> io::Result<Vec<LinuxDirEntry>>;
 
+    /// The stream ends after the first error it yields
+    async fn read_dir_stream(
+        &self,
+        path: &OsStr,
+    ) -> io::Result<impl Stream<Item = io::Result<LinuxDirEntry>> + Send + Unpin + 'static>;
+
+    /// Doesn't follow symlinks. Remote impls lack what SFTP doesn't carry, such as inodes
+    async fn list_dir_with_metadata(&self, path: &OsStr) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        let entries = self.list_dir(path).await?;
+        let metadata = try_join_all(
+            entries
+                .iter()
+                .map(|dir_entry| self.get_symlink_metadata(dir_entry.path.as_os_str())),
+        )
+        .await?;
+        Ok(entries.into_iter().zip(metadata).collect())
+    }
+
+    async fn list_dir_with_extended_metadata(
+        &self,
+        path: &OsStr,
+    ) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        self.list_dir_with_metadata(path).await
+    }
+
+    /// Doesn't yield the directory itself, and yields children in the order of their names
+    fn walk_dir<'a>(
+        &'a self,
+        path: &OsStr,
+        walk_options: &LinuxWalkOptions,
+    ) -> BoxStream<'a, io::Result<LinuxWalkEntry>>
+    where
+        Self: Sync,
+    {
+        walker::walk_dir(self, path, walk_options)
+    }
+
+    /// Hidden entries only match patterns starting with a dot
+    fn glob<'a>(&'a self, pattern: &str) -> BoxStream<'a, io::Result<LinuxDirEntry>>
+    where
+        Self: Sync,
+    {
+        glob_expander::glob(self, pattern)
+    }
+
+    /// Returns the lowercase hex digest
+    async fn checksum(&self, path: &OsStr, algorithm: LinuxChecksumAlgorithm) -> io::Result<String> {
+        checksum::hash_file(self, path, algorithm).await
+    }
+
+    /// Keyed by the paths relative to the directory. Symlinks aren't followed
+    async fn checksum_tree(
+        &self,
+        path: &OsStr,
+        algorithm: LinuxChecksumAlgorithm,
+    ) -> io::Result<BTreeMap<OsString, String>> {
+        let walk_entries = self
+            .walk_dir(path, LinuxWalkOptions::new().file_type(LinuxFileType::File))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        stream::iter(walk_entries)
+            .map(|walk_entry| async move {
+                let checksum = self.checksum(&walk_entry.dir_entry.path, algorithm).await?;
+                let relative_path = Path::new(&walk_entry.dir_entry.path)
+                    .strip_prefix(path)
+                    .map(|relative_path| relative_path.as_os_str().to_os_string())
+                    .unwrap_or(walk_entry.dir_entry.name);
+                Ok::<_, io::Error>((relative_path, checksum))
+            })
+            .buffered(8)
+            .try_collect()
+            .await
+    }
+
     async fn remove_dir(&self, path: &OsStr) -> io::Result<()>;
 
     async fn remove_dir_recursively(&self, path: &OsStr) -> io::Result<()>;
@@ -186,4 +820,132 @@ pub trait LinuxFilesystem {
     async fn get_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata>;
 
     async fn get_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata>;
+
+    /// Includes the fields that SFTP lacks, such as inodes. Remote impls need GNU stat on the host
+    async fn get_extended_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        self.get_metadata(path).await
+    }
+
+    async fn get_extended_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        self.get_symlink_metadata(path).await
+    }
+
+    async fn fs_stats(&self, _path: &OsStr) -> io::Result<LinuxFsStats> {
+        Err(unsupported_error("fs_stats"))
+    }
+
+    /// Like du, counts hard-linked files once and doesn't follow symlinks
+    async fn disk_usage(&self, path: &OsStr) -> io::Result<u64> {
+        let metadata = self.get_symlink_metadata(path).await?;
+        if metadata.file_type != Some(LinuxFileType::Dir) {
+            return Ok(allocated_size(&metadata));
+        }
+
+        let mut usage = allocated_size(&metadata);
+        let mut seen_inodes = HashSet::new();
+        let mut walk_stream = self.walk_dir(path, &LinuxWalkOptions::new());
+        while let Some(walk_entry) = walk_stream.try_next().await? {
+            let metadata = &walk_entry.metadata;
+            if let (Some(2..), Some(device_id), Some(inode)) =
+                (metadata.hard_link_count, metadata.device_id, metadata.inode)
+            {
+                if !seen_inodes.insert((device_id, inode)) {
+                    continue;
+                }
+            }
+            usage += allocated_size(metadata);
+        }
+        Ok(usage)
+    }
+
+    /// Only reads the host's /etc/passwd, so users of other NSS sources such as LDAP aren't found
+    async fn lookup_user(&self, _user_id: u32) -> io::Result<Option<String>> {
+        Err(unsupported_error("lookup_user"))
+    }
+
+    /// Only reads the host's /etc/group
+    async fn lookup_group(&self, _group_id: u32) -> io::Result<Option<String>> {
+        Err(unsupported_error("lookup_group"))
+    }
+}
+
+// a sibling of the path, so that renaming it over the path doesn't cross filesystems
+fn atomic_tmp_path(path: &OsStr) -> io::Result<OsString> {
+    static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let path = Path::new(path);
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} doesn't name a file", path.to_string_lossy()),
+        )
+    })?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    // the time tells apart processes with the same id on different hosts writing to a shared remote file
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or_default();
+    tmp_name.push(format!(
+        ".{}-{}-{}.tmp",
+        std::process::id(),
+        nanos,
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(path.with_file_name(tmp_name).into_os_string())
+}
+
+// the allocated blocks of a file, or its size when the impl doesn't report them
+fn allocated_size(metadata: &LinuxFileMetadata) -> u64 {
+    match metadata.block_count {
+        Some(block_count) => block_count * 512,
+        None => metadata.size.unwrap_or_default(),
+    }
+}
+
+// the error of the trait methods that an impl doesn't provide
+fn unsupported_error(operation: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} isn't supported by this filesystem", operation),
+    )
+}
+
+// the error of syncing through a server without the fsync@openssh.com extension
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn fsync_unsupported_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the server doesn't support the fsync@openssh.com extension",
+    )
+}
+
+// the owner argument of chown, which leaves out the user or group to keep them unchanged
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn chown_owner_arg(user_id: Option<u32>, group_id: Option<u32>) -> Option<String> {
+    match (user_id, group_id) {
+        (Some(user_id), Some(group_id)) => Some(format!("{}:{}", user_id, group_id)),
+        (Some(user_id), None) => Some(user_id.to_string()),
+        (None, Some(group_id)) => Some(format!(":{}", group_id)),
+        (None, None) => None,
+    }
+}
+
+// the mknod type argument of a device node
+#[cfg(feature = "impl-ssh-common")]
+pub(crate) fn mknod_type_arg(file_type: LinuxFileType) -> io::Result<&'static str> {
+    match file_type {
+        LinuxFileType::BlockDevice => Ok("b"),
+        LinuxFileType::CharDevice => Ok("c"),
+        _ => Err(invalid_device_type(file_type)),
+    }
+}
+
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+pub(crate) fn invalid_device_type(file_type: LinuxFileType) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{:?} isn't a device node type", file_type),
+    )
 }
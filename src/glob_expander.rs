@@ -0,0 +1,330 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use futures_util::stream::{self, BoxStream, StreamExt};
+
+use crate::{
+    filesystem::{LinuxDirEntry, LinuxFileType, LinuxFilesystem},
+    glob_pattern::GlobPattern,
+};
+
+struct GlobState<'a, F: ?Sized> {
+    filesystem: &'a F,
+    // the path segments of the pattern, starting from either "/" or ".". Braces are expanded within their segment,
+    // only groups spanning a slash split the pattern into several alternatives
+    alternatives: Vec<GlobAlternative>,
+    // a stack of pending steps, popped from the end
+    steps: Vec<GlobStep>,
+    // paths that were already yielded, since multiple alternatives can match the same path
+    yielded_paths: HashSet<OsString>,
+}
+
+struct GlobAlternative {
+    base: PathBuf,
+    segments: Vec<GlobSegment>,
+}
+
+enum GlobSegment {
+    // names that are joined without listing the directory, more than one for a segment like "{a,b}"
+    Literals(Vec<String>),
+    Patterns(Vec<GlobSegmentPattern>),
+    GlobStar,
+}
+
+struct GlobSegmentPattern {
+    pattern: GlobPattern,
+    matches_hidden: bool,
+}
+
+enum GlobStep {
+    // match the segment at the given index against the entries of the directory
+    Expand {
+        dir: PathBuf,
+        alternative_index: usize,
+        segment_index: usize,
+    },
+    // yield every entry below the directory, for a trailing globstar
+    Descendants {
+        dir: PathBuf,
+    },
+    Yield(LinuxDirEntry),
+}
+
+pub(crate) fn glob<'a, F>(filesystem: &'a F, pattern: &str) -> BoxStream<'a, io::Result<LinuxDirEntry>>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    let alternatives = match parse_alternatives(pattern) {
+        Ok(alternatives) => alternatives,
+        Err(err) => return stream::once(async { Err(err) }).boxed(),
+    };
+    // the first alternative ends up on top of the stack
+    let steps = alternatives
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(alternative_index, alternative)| GlobStep::Expand {
+            dir: alternative.base.clone(),
+            alternative_index,
+            segment_index: 0,
+        })
+        .collect();
+
+    let state = GlobState {
+        filesystem,
+        alternatives,
+        steps,
+        yielded_paths: HashSet::new(),
+    };
+
+    stream::try_unfold(state, |mut state| async move {
+        Ok(state.next_entry().await?.map(|dir_entry| (dir_entry, state)))
+    })
+    .boxed()
+}
+
+fn parse_alternatives(pattern: &str) -> io::Result<Vec<GlobAlternative>> {
+    let mut alternatives = Vec::new();
+    let split_alternatives = match has_brace_spanning_slash(pattern) {
+        true => GlobPattern::expand_alternatives(pattern)?,
+        false => vec![pattern.to_string()],
+    };
+
+    for alternative in split_alternatives {
+        let base = match alternative.starts_with('/') {
+            true => PathBuf::from("/"),
+            false => PathBuf::from("."),
+        };
+        let mut segments = Vec::new();
+
+        for segment in alternative.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "**" {
+                // consecutive globstars are equivalent to one
+                if !matches!(segments.last(), Some(GlobSegment::GlobStar)) {
+                    segments.push(GlobSegment::GlobStar);
+                }
+            } else {
+                segments.push(parse_segment(segment)?);
+            }
+        }
+
+        alternatives.push(GlobAlternative { base, segments });
+    }
+
+    Ok(alternatives)
+}
+
+fn parse_segment(segment: &str) -> io::Result<GlobSegment> {
+    let segment_alternatives = GlobPattern::expand_alternatives(segment)?;
+    if !segment_alternatives
+        .iter()
+        .any(|segment_alternative| GlobPattern::has_wildcards(segment_alternative))
+    {
+        return Ok(GlobSegment::Literals(
+            segment_alternatives
+                .iter()
+                .map(|segment_alternative| GlobPattern::unescape(segment_alternative))
+                .collect(),
+        ));
+    }
+
+    let mut patterns = Vec::with_capacity(segment_alternatives.len());
+    for segment_alternative in segment_alternatives {
+        patterns.push(GlobSegmentPattern {
+            pattern: GlobPattern::new(&segment_alternative)?,
+            matches_hidden: segment_alternative.starts_with('.'),
+        });
+    }
+    Ok(GlobSegment::Patterns(patterns))
+}
+
+// whether a brace group contains a slash, as in "{a/b,c}", which can't be expanded within a single segment
+fn has_brace_spanning_slash(pattern: &str) -> bool {
+    let mut depth = 0;
+    let mut escaped = false;
+    let mut in_class = false;
+
+    for char in pattern.chars() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '[' => in_class = true,
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '/' if depth > 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+impl<F> GlobState<'_, F>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    async fn next_entry(&mut self) -> io::Result<Option<LinuxDirEntry>> {
+        while let Some(step) = self.steps.pop() {
+            match step {
+                GlobStep::Yield(dir_entry) => {
+                    if self.yielded_paths.insert(dir_entry.path.clone()) {
+                        return Ok(Some(dir_entry));
+                    }
+                }
+                GlobStep::Expand {
+                    dir,
+                    alternative_index,
+                    segment_index,
+                } => self.expand(dir, alternative_index, segment_index).await?,
+                GlobStep::Descendants { dir } => self.push_descendants(dir).await?,
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn expand(&mut self, dir: PathBuf, alternative_index: usize, segment_index: usize) -> io::Result<()> {
+        let alternative = &self.alternatives[alternative_index];
+        let is_last = segment_index + 1 == alternative.segments.len();
+
+        let Some(segment) = alternative.segments.get(segment_index) else {
+            // a pattern without any segments, such as "/", matches its base only
+            if let Some(dir_entry) = self.literal_entry(dir).await? {
+                self.steps.push(GlobStep::Yield(dir_entry));
+            }
+            return Ok(());
+        };
+
+        match segment {
+            GlobSegment::Literals(literals) => {
+                let paths = literals.iter().map(|literal| dir.join(literal)).collect::<Vec<_>>();
+                if is_last {
+                    let mut dir_entries = Vec::with_capacity(paths.len());
+                    for path in paths {
+                        dir_entries.extend(self.literal_entry(path).await?);
+                    }
+                    self.steps.extend(dir_entries.into_iter().rev().map(GlobStep::Yield));
+                } else {
+                    // literal segments are never listed, a missing directory only shows up once it gets listed
+                    for path in paths.into_iter().rev() {
+                        self.steps.push(GlobStep::Expand {
+                            dir: path,
+                            alternative_index,
+                            segment_index: segment_index + 1,
+                        });
+                    }
+                }
+            }
+            GlobSegment::Patterns(patterns) => {
+                let matching_entries = self
+                    .list_dir_sorted(&dir)
+                    .await?
+                    .into_iter()
+                    .filter(|dir_entry| {
+                        let name = dir_entry.name.to_string_lossy();
+                        patterns.iter().any(|segment_pattern| {
+                            is_visible(dir_entry, segment_pattern.matches_hidden)
+                                && segment_pattern.pattern.is_match(&name)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for dir_entry in matching_entries.into_iter().rev() {
+                    if is_last {
+                        self.steps.push(GlobStep::Yield(dir_entry));
+                    } else if dir_entry.file_type != LinuxFileType::File {
+                        // symlinks are followed in non-globstar segments, like shells do
+                        self.steps.push(GlobStep::Expand {
+                            dir: PathBuf::from(dir_entry.path),
+                            alternative_index,
+                            segment_index: segment_index + 1,
+                        });
+                    }
+                }
+            }
+            GlobSegment::GlobStar if is_last => self.steps.push(GlobStep::Descendants { dir }),
+            GlobSegment::GlobStar => {
+                // the globstar matches any number of directories, symlinks to directories aren't descended into
+                let subdirs = self
+                    .list_dir_sorted(&dir)
+                    .await?
+                    .into_iter()
+                    .filter(|dir_entry| dir_entry.file_type == LinuxFileType::Dir && is_visible(dir_entry, false))
+                    .collect::<Vec<_>>();
+
+                for subdir in subdirs.into_iter().rev() {
+                    self.steps.push(GlobStep::Expand {
+                        dir: PathBuf::from(subdir.path),
+                        alternative_index,
+                        segment_index,
+                    });
+                }
+                // matching zero directories comes first
+                self.steps.push(GlobStep::Expand {
+                    dir,
+                    alternative_index,
+                    segment_index: segment_index + 1,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn push_descendants(&mut self, dir: PathBuf) -> io::Result<()> {
+        let children = self
+            .list_dir_sorted(&dir)
+            .await?
+            .into_iter()
+            .filter(|dir_entry| is_visible(dir_entry, false))
+            .collect::<Vec<_>>();
+
+        for dir_entry in children.into_iter().rev() {
+            if dir_entry.file_type == LinuxFileType::Dir {
+                self.steps.push(GlobStep::Descendants {
+                    dir: PathBuf::from(&dir_entry.path),
+                });
+            }
+            self.steps.push(GlobStep::Yield(dir_entry));
+        }
+
+        Ok(())
+    }
+
+    // lists a directory sorted by name, treating paths that are missing or aren't directories as empty
+    async fn list_dir_sorted(&self, dir: &Path) -> io::Result<Vec<LinuxDirEntry>> {
+        let dir = dir.as_os_str();
+        match self.filesystem.list_dir(dir).await {
+            Ok(mut entries) => {
+                entries.sort_by(|first, second| first.name.cmp(&second.name));
+                Ok(entries)
+            }
+            // remote impls can't tell apart why listing failed, so check whether there was a directory at all
+            Err(err) => match self.filesystem.get_metadata(dir).await {
+                Ok(metadata) if metadata.file_type == Some(LinuxFileType::Dir) => Err(err),
+                _ => Ok(Vec::new()),
+            },
+        }
+    }
+
+    async fn literal_entry(&self, path: PathBuf) -> io::Result<Option<LinuxDirEntry>> {
+        let Ok(metadata) = self.filesystem.get_symlink_metadata(path.as_os_str()).await else {
+            return Ok(None);
+        };
+
+        Ok(Some(LinuxDirEntry {
+            name: path.file_name().unwrap_or(path.as_os_str()).to_os_string(),
+            file_type: metadata.file_type.unwrap_or(LinuxFileType::Other),
+            path: path.into_os_string(),
+        }))
+    }
+}
+
+fn is_visible(dir_entry: &LinuxDirEntry, matches_hidden: bool) -> bool {
+    matches_hidden || !dir_entry.name.to_string_lossy().starts_with('.')
+}
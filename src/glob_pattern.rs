@@ -0,0 +1,361 @@
+use std::io;
+
+// a small shell-style glob matcher: * and ? (never matching /), ** as a whole path segment (matching any number of
+// segments), [abc], [a-z], [!abc] or [^abc] and [[:alpha:]]-style classes, {a,b} alternatives and \ escapes
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct GlobPattern {
+    // every brace alternative is expanded upfront into its own token sequence
+    alternatives: Vec<Vec<GlobToken>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum GlobToken {
+    Literal(char),
+    AnyChar,
+    AnyRun,
+    // "**/": an empty string or any number of whole segments
+    GlobStarSegments,
+    // a trailing "**": anything, including slashes
+    GlobStarRest,
+    Class { negated: bool, items: Vec<ClassItem> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Named(NamedClass),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NamedClass {
+    Alpha,
+    Digit,
+    Alnum,
+    Upper,
+    Lower,
+    Space,
+    Punct,
+    XDigit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct GlobPatternError {
+    pub pattern: String,
+    pub reason: &'static str,
+}
+
+impl GlobPattern {
+    pub(crate) fn new(pattern: &str) -> Result<GlobPattern, GlobPatternError> {
+        let to_error = |reason| GlobPatternError {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        let alternatives = expand_braces(pattern).map_err(to_error)?;
+        let mut tokenized_alternatives = Vec::with_capacity(alternatives.len());
+        for alternative in alternatives {
+            tokenized_alternatives.push(tokenize(&alternative).map_err(to_error)?);
+        }
+
+        Ok(GlobPattern {
+            alternatives: tokenized_alternatives,
+        })
+    }
+
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text = text.chars().collect::<Vec<_>>();
+        self.alternatives
+            .iter()
+            .any(|tokens| match_tokens(tokens.as_slice(), text.as_slice()))
+    }
+
+    // splits the pattern into its brace-expanded alternatives, which are still patterns themselves
+    pub(crate) fn expand_alternatives(pattern: &str) -> Result<Vec<String>, GlobPatternError> {
+        expand_braces(pattern).map_err(|reason| GlobPatternError {
+            pattern: pattern.to_string(),
+            reason,
+        })
+    }
+
+    // whether the text contains unescaped wildcards, making it a pattern instead of a literal
+    pub(crate) fn has_wildcards(text: &str) -> bool {
+        let mut escaped = false;
+        for char in text.chars() {
+            match char {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '*' | '?' | '[' | '{' => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // strips the escapes of a literal, e.g. "a\\*b" becomes "a*b"
+    pub(crate) fn unescape(text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut escaped = false;
+        for char in text.chars() {
+            if char == '\\' && !escaped {
+                escaped = true;
+                continue;
+            }
+            escaped = false;
+            output.push(char);
+        }
+        output
+    }
+}
+
+impl From<GlobPatternError> for io::Error {
+    fn from(value: GlobPatternError) -> Self {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid glob pattern {}: {}", value.pattern, value.reason),
+        )
+    }
+}
+
+// turns "a{b,c{d,e}}f" into ["abf", "acdf", "acef"], leaving escaped braces and braces inside classes alone
+fn expand_braces(pattern: &str) -> Result<Vec<String>, &'static str> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut index = 0;
+    let mut escaped = false;
+    let mut in_class = false;
+
+    while index < chars.len() {
+        let char = chars[index];
+        if escaped {
+            escaped = false;
+        } else if char == '\\' {
+            escaped = true;
+        } else if in_class {
+            if char == ']' {
+                in_class = false;
+            }
+        } else if char == '[' {
+            in_class = true;
+        } else if char == '{' {
+            let (close_index, options) = split_brace_group(&chars, index)?;
+            let prefix = chars[..index].iter().collect::<String>();
+            let suffix = chars[close_index + 1..].iter().collect::<String>();
+            let mut expanded = Vec::new();
+            for option in options {
+                expanded.extend(expand_braces(&format!("{}{}{}", prefix, option, suffix))?);
+            }
+            return Ok(expanded);
+        } else if char == '}' {
+            return Err("unmatched closing brace");
+        }
+        index += 1;
+    }
+
+    Ok(vec![pattern.to_string()])
+}
+
+// returns the index of the closing brace and the top-level, comma-separated options of the group
+fn split_brace_group(chars: &[char], open_index: usize) -> Result<(usize, Vec<String>), &'static str> {
+    let mut options = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for (index, char) in chars.iter().enumerate().skip(open_index + 1) {
+        if escaped {
+            escaped = false;
+            current.push(*char);
+            continue;
+        }
+        match char {
+            '\\' => {
+                escaped = true;
+                current.push(*char);
+            }
+            '{' => {
+                depth += 1;
+                current.push(*char);
+            }
+            '}' if depth == 0 => {
+                options.push(current);
+                return Ok((index, options));
+            }
+            '}' => {
+                depth -= 1;
+                current.push(*char);
+            }
+            ',' if depth == 0 => options.push(std::mem::take(&mut current)),
+            _ => current.push(*char),
+        }
+    }
+
+    Err("unmatched opening brace")
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<GlobToken>, &'static str> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => {
+                let escaped = chars.get(index + 1).ok_or("trailing escape")?;
+                tokens.push(GlobToken::Literal(*escaped));
+                index += 2;
+            }
+            '?' => {
+                tokens.push(GlobToken::AnyChar);
+                index += 1;
+            }
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                let starts_segment = index == 0 || chars[index - 1] == '/';
+                match chars.get(index + 2) {
+                    Some('/') if starts_segment => {
+                        tokens.push(GlobToken::GlobStarSegments);
+                        index += 3;
+                    }
+                    None if starts_segment => {
+                        tokens.push(GlobToken::GlobStarRest);
+                        index += 2;
+                    }
+                    // "**" that isn't a whole segment behaves like "*"
+                    _ => {
+                        tokens.push(GlobToken::AnyRun);
+                        index += 2;
+                    }
+                }
+            }
+            '*' => {
+                tokens.push(GlobToken::AnyRun);
+                index += 1;
+            }
+            '[' => {
+                let (token, next_index) = tokenize_class(&chars, index)?;
+                tokens.push(token);
+                index = next_index;
+            }
+            char => {
+                tokens.push(GlobToken::Literal(char));
+                index += 1;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn tokenize_class(chars: &[char], open_index: usize) -> Result<(GlobToken, usize), &'static str> {
+    let mut index = open_index + 1;
+    let negated = matches!(chars.get(index), Some('!') | Some('^'));
+    if negated {
+        index += 1;
+    }
+
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+        let char = *chars.get(index).ok_or("unmatched opening bracket")?;
+        // a "]" right after the opening bracket is a literal, like in shells
+        if char == ']' && !first {
+            return Ok((GlobToken::Class { negated, items }, index + 1));
+        }
+        first = false;
+
+        if char == '[' && chars.get(index + 1) == Some(&':') {
+            let rest = chars[index + 2..].iter().collect::<String>();
+            let name_end = rest.find(":]").ok_or("unterminated named class")?;
+            items.push(ClassItem::Named(parse_named_class(&rest[..name_end])?));
+            index += 2 + rest[..name_end].chars().count() + 2;
+            continue;
+        }
+
+        let char = if char == '\\' {
+            index += 1;
+            *chars.get(index).ok_or("trailing escape")?
+        } else {
+            char
+        };
+
+        if chars.get(index + 1) == Some(&'-') && chars.get(index + 2).is_some_and(|end| *end != ']') {
+            let end = chars[index + 2];
+            if end < char {
+                return Err("reversed range in class");
+            }
+            items.push(ClassItem::Range(char, end));
+            index += 3;
+        } else {
+            items.push(ClassItem::Char(char));
+            index += 1;
+        }
+    }
+}
+
+fn parse_named_class(name: &str) -> Result<NamedClass, &'static str> {
+    match name {
+        "alpha" => Ok(NamedClass::Alpha),
+        "digit" => Ok(NamedClass::Digit),
+        "alnum" => Ok(NamedClass::Alnum),
+        "upper" => Ok(NamedClass::Upper),
+        "lower" => Ok(NamedClass::Lower),
+        "space" => Ok(NamedClass::Space),
+        "punct" => Ok(NamedClass::Punct),
+        "xdigit" => Ok(NamedClass::XDigit),
+        _ => Err("unknown named class"),
+    }
+}
+
+// fills in whether the tokens from each one onwards match the text from each position onwards, going backwards
+// through the tokens, so that runs of wildcards take time proportional to the text instead of backtracking
+fn match_tokens(tokens: &[GlobToken], text: &[char]) -> bool {
+    // rest_matches[index] tells whether the tokens after the current one match text[index..]
+    let mut rest_matches = vec![false; text.len() + 1];
+    rest_matches[text.len()] = true;
+
+    for token in tokens.iter().rev() {
+        let mut matches = vec![false; text.len() + 1];
+        // whether the rest matches right after some slash at or after the current position
+        let mut rest_matches_after_slash = false;
+        for index in (0..=text.len()).rev() {
+            let char = text.get(index);
+            matches[index] = match token {
+                GlobToken::Literal(expected) => char == Some(expected) && rest_matches[index + 1],
+                GlobToken::AnyChar => char.is_some_and(|char| *char != '/') && rest_matches[index + 1],
+                GlobToken::Class { negated, items } => {
+                    char.is_some_and(|char| *char != '/' && items.iter().any(|item| item.matches(*char)) != *negated)
+                        && rest_matches[index + 1]
+                }
+                GlobToken::AnyRun => {
+                    rest_matches[index] || (char.is_some_and(|char| *char != '/') && matches[index + 1])
+                }
+                GlobToken::GlobStarSegments => {
+                    rest_matches_after_slash |= char == Some(&'/') && rest_matches[index + 1];
+                    rest_matches[index] || rest_matches_after_slash
+                }
+                GlobToken::GlobStarRest => true,
+            };
+        }
+        rest_matches = matches;
+    }
+
+    rest_matches[0]
+}
+
+impl ClassItem {
+    fn matches(&self, char: char) -> bool {
+        match self {
+            ClassItem::Char(expected) => char == *expected,
+            ClassItem::Range(start, end) => (*start..=*end).contains(&char),
+            ClassItem::Named(named_class) => match named_class {
+                NamedClass::Alpha => char.is_alphabetic(),
+                NamedClass::Digit => char.is_ascii_digit(),
+                NamedClass::Alnum => char.is_alphanumeric(),
+                NamedClass::Upper => char.is_uppercase(),
+                NamedClass::Lower => char.is_lowercase(),
+                NamedClass::Space => char.is_whitespace(),
+                NamedClass::Punct => char.is_ascii_punctuation(),
+                NamedClass::XDigit => char.is_ascii_hexdigit(),
+            },
+        }
+    }
+}
@@ -1,5 +1,9 @@
 use std::{
     collections::HashMap,
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
     process::{Output, Stdio},
     sync::Arc,
 };
@@ -8,42 +12,51 @@ use async_trait::async_trait;
 use bytes::BytesMut;
 use dashmap::DashMap;
 use nix::{
+    fcntl::{fcntl, FcntlArg, OFlag},
     sys::signal::{kill, Signal},
-    unistd::Pid,
+    unistd::{dup2, pipe2, Pid},
 };
 use once_cell::sync::Lazy;
+use shell_escape::unix::escape;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
     process::{Child, ChildStderr, ChildStdin, ChildStdout, Command},
+    task::JoinHandle,
 };
 
-use crate::executor::{
-    FinishedLinuxProcessOutput, LinuxExecutor, LinuxProcess, LinuxProcessConfiguration, LinuxProcessError,
-    LinuxProcessOutput,
+use crate::{
+    executor::{
+        FinishedLinuxProcessOutput, LinuxExecutor, LinuxOutputTarget, LinuxProcess, LinuxProcessConfiguration,
+        LinuxProcessError, LinuxProcessOutput,
+    },
+    script_ext::ScriptExt,
 };
 
 use super::NativeLinux;
 
 static STDOUT_BUFFERS: Lazy<Arc<DashMap<u32, BytesMut>>> = Lazy::new(|| Arc::new(DashMap::new()));
 static STDERR_BUFFERS: Lazy<Arc<DashMap<u32, BytesMut>>> = Lazy::new(|| Arc::new(DashMap::new()));
+static STDEXT_BUFFERS: Lazy<Arc<DashMap<(u32, u32), BytesMut>>> = Lazy::new(|| Arc::new(DashMap::new()));
 
 struct NativeLinuxProcess {
     child: Child,
     stdin: Option<ChildStdin>,
-    redirect_stdout: bool,
-    redirect_stderr: bool,
+    stdout_piped: bool,
+    stderr_piped: bool,
+    captured_fds: Vec<u32>,
+    fd_capturers: Vec<JoinHandle<()>>,
     pid: Option<u32>,
 }
 
 #[async_trait]
-impl<'a> LinuxProcess for NativeLinuxProcess {
+impl LinuxProcess for NativeLinuxProcess {
     fn id(&self) -> Option<u32> {
         self.pid
     }
 
     async fn write_to_stdin(&mut self, data: &[u8]) -> Result<usize, LinuxProcessError> {
         let stdin_ref = self.stdin.as_mut().ok_or(LinuxProcessError::StdinNotPiped)?;
-        stdin_ref.write(data).await.map_err(|err| LinuxProcessError::IO(err))
+        stdin_ref.write(data).await.map_err(LinuxProcessError::IO)
     }
 
     async fn close_stdin(&mut self) -> Result<(), LinuxProcessError> {
@@ -61,13 +74,13 @@ impl<'a> LinuxProcess for NativeLinuxProcess {
         let mut stdout: Vec<u8> = Vec::new();
         let mut stderr: Vec<u8> = Vec::new();
 
-        if self.redirect_stdout {
+        if self.stdout_piped {
             if let Some(buf) = STDOUT_BUFFERS.get(&pid) {
                 stdout = buf.to_vec();
             }
         }
 
-        if self.redirect_stderr {
+        if self.stderr_piped {
             if let Some(buf) = STDERR_BUFFERS.get(&pid) {
                 stderr = buf.to_vec();
             }
@@ -76,7 +89,7 @@ impl<'a> LinuxProcess for NativeLinuxProcess {
         Ok(LinuxProcessOutput {
             stdout,
             stderr,
-            stdout_extended: HashMap::new(),
+            stdout_extended: fetch_fd_outputs(pid, &self.captured_fds),
         })
     }
 
@@ -97,6 +110,10 @@ impl<'a> LinuxProcess for NativeLinuxProcess {
             .await
             .map(|status| status.code().map(|i| i.into()))
             .map_err(LinuxProcessError::IO)?;
+        // the captured fds are drained once the process and its descendants have closed them
+        for fd_capturer in self.fd_capturers.drain(..) {
+            let _ = fd_capturer.await;
+        }
         let output = self.get_current_output()?;
         Ok(FinishedLinuxProcessOutput::join(output, status_code))
     }
@@ -107,6 +124,9 @@ impl Drop for NativeLinuxProcess {
         if let Some(pid) = self.pid {
             STDOUT_BUFFERS.remove(&pid);
             STDERR_BUFFERS.remove(&pid);
+            for fd in &self.captured_fds {
+                STDEXT_BUFFERS.remove(&(pid, *fd));
+            }
         }
     }
 }
@@ -126,18 +146,21 @@ impl LinuxExecutor for NativeLinux {
         &self,
         process_configuration: &LinuxProcessConfiguration,
     ) -> Result<Box<dyn LinuxProcess>, LinuxProcessError> {
-        let mut command = create_command_from_config(process_configuration);
+        let mut command = create_command_from_config(process_configuration).map_err(LinuxProcessError::IO)?;
+        let fd_pipes = prepare_fd_pipes(&mut command, &process_configuration.captured_fds)?;
         let mut child = command.spawn().map_err(LinuxProcessError::IO)?;
+        drop(command);
         let stdin = child.stdin.take();
         let pid = child.id();
+        let fd_capturers = queue_fd_capturers(pid, fd_pipes);
 
         if let Some(pid) = pid {
-            if process_configuration.redirect_stdout {
+            if process_configuration.stdout_target.is_piped() {
                 STDOUT_BUFFERS.insert(pid, BytesMut::new());
                 queue_capturer(&mut child, false);
             }
 
-            if process_configuration.redirect_stderr {
+            if process_configuration.stderr_target.is_piped() {
                 STDERR_BUFFERS.insert(pid, BytesMut::new());
                 queue_capturer(&mut child, true)
             }
@@ -146,8 +169,10 @@ impl LinuxExecutor for NativeLinux {
         Ok(Box::new(NativeLinuxProcess {
             child,
             stdin,
-            redirect_stdout: process_configuration.redirect_stdout,
-            redirect_stderr: process_configuration.redirect_stderr,
+            stdout_piped: process_configuration.stdout_target.is_piped(),
+            stderr_piped: process_configuration.stderr_target.is_piped(),
+            captured_fds: process_configuration.captured_fds.clone(),
+            fd_capturers,
             pid,
         }))
     }
@@ -156,9 +181,35 @@ impl LinuxExecutor for NativeLinux {
         &self,
         process_configuration: &LinuxProcessConfiguration,
     ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
-        let mut command = create_command_from_config(process_configuration);
-        let os_output = command.output().await.map_err(LinuxProcessError::IO)?;
-        Ok(conv_finished_output(os_output))
+        let mut command = create_command_from_config(process_configuration).map_err(LinuxProcessError::IO)?;
+        let fd_pipes = prepare_fd_pipes(&mut command, &process_configuration.captured_fds)?;
+        let child = command.spawn().map_err(LinuxProcessError::IO)?;
+        drop(command);
+        let pid = child.id();
+        let fd_capturers = queue_fd_capturers(pid, fd_pipes);
+
+        let os_output = child.wait_with_output().await.map_err(LinuxProcessError::IO)?;
+        let mut finished_output = conv_finished_output(os_output);
+        if let Some(pid) = pid {
+            for fd_capturer in fd_capturers {
+                let _ = fd_capturer.await;
+            }
+            finished_output.stdout_extended = fetch_fd_outputs(pid, &process_configuration.captured_fds);
+            for fd in &process_configuration.captured_fds {
+                STDEXT_BUFFERS.remove(&(pid, *fd));
+            }
+        }
+        Ok(finished_output)
+    }
+
+    async fn execute_script(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
+        self.execute_script_via_filesystem(interpreter, script_body, process_configuration)
+            .await
     }
 
     async fn send_signal(&self, signal: Signal, process_id: u32) -> Result<(), LinuxProcessError> {
@@ -166,6 +217,41 @@ impl LinuxExecutor for NativeLinux {
     }
 }
 
+fn queue_fd_capturers(pid: Option<u32>, fd_pipes: Vec<(u32, OwnedFd)>) -> Vec<JoinHandle<()>> {
+    let Some(pid) = pid else {
+        return Vec::new();
+    };
+
+    fd_pipes
+        .into_iter()
+        .map(|(fd, read_end)| {
+            STDEXT_BUFFERS.insert((pid, fd), BytesMut::new());
+            let mut reader = tokio::fs::File::from_std(File::from(read_end));
+
+            tokio::spawn(async move {
+                let mut buf = [0; 8192];
+                loop {
+                    let read = match reader.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => read,
+                    };
+                    match STDEXT_BUFFERS.get_mut(&(pid, fd)) {
+                        Some(mut fd_buf) => fd_buf.extend_from_slice(&buf[..read]),
+                        None => break,
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+fn fetch_fd_outputs(pid: u32, captured_fds: &[u32]) -> HashMap<u32, Vec<u8>> {
+    captured_fds
+        .iter()
+        .filter_map(|fd| STDEXT_BUFFERS.get(&(pid, *fd)).map(|buf| (*fd, buf.to_vec())))
+        .collect()
+}
+
 fn queue_capturer(child: &mut Child, is_stderr: bool) {
     let pid = child.id().expect("Child has no PID!");
     let mut stdout: Option<ChildStdout> = None;
@@ -213,28 +299,104 @@ fn queue_capturer(child: &mut Child, is_stderr: bool) {
     });
 }
 
-fn create_command_from_config(process_configuration: &LinuxProcessConfiguration) -> Command {
-    let mut command = Command::new(&process_configuration.program);
-    command.args(&process_configuration.args);
-    command.envs(&process_configuration.envs);
+// creates a pipe for every captured fd, the write ends of which get moved onto the respective fds in the child
+fn prepare_fd_pipes(command: &mut Command, captured_fds: &[u32]) -> Result<Vec<(u32, OwnedFd)>, LinuxProcessError> {
+    if captured_fds.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    if let Some(working_dir) = &process_configuration.working_dir {
-        command.current_dir(working_dir);
+    let mut read_ends = Vec::new();
+    let mut write_ends = Vec::new();
+    for fd in captured_fds {
+        // both ends are close-on-exec, so that only the dup2-ed fds remain open in the executed program
+        let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC).map_err(LinuxProcessError::LowLevel)?;
+        read_ends.push((*fd, read_end));
+        write_ends.push(write_end);
     }
 
-    if process_configuration.redirect_stdout {
-        command.stdout(Stdio::piped());
-    } else {
-        command.stdout(Stdio::null());
+    // std reports exec failures through a close-on-exec pipe it creates on the lowest free fds while spawning. Free
+    // targets are taken up until the command is dropped, so that the pipe can't be created on a target and then be
+    // overwritten by dup2, which would make a failed exec look like a successful spawn
+    let mut reserved_fds = Vec::new();
+    for fd in captured_fds {
+        let reserved_fd = fcntl(write_ends[0].as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(*fd as RawFd))
+            .map_err(LinuxProcessError::LowLevel)?;
+        // SAFETY: fcntl just created the fd, so nothing else owns it
+        let reserved_fd = unsafe { OwnedFd::from_raw_fd(reserved_fd) };
+        // an fd above the target means that the target is already taken, so the reservation isn't needed
+        if reserved_fd.as_raw_fd() == *fd as RawFd {
+            reserved_fds.push(reserved_fd);
+        }
     }
 
-    if process_configuration.redirect_stderr {
-        command.stderr(Stdio::piped());
-    } else {
-        command.stderr(Stdio::null());
+    let mut mappings: Vec<(RawFd, RawFd)> = write_ends
+        .iter()
+        .zip(captured_fds)
+        .map(|(write_end, fd)| (write_end.as_raw_fd(), *fd as RawFd))
+        .collect();
+    let lowest_free_fd = captured_fds.iter().max().map(|fd| *fd as RawFd + 1).unwrap_or(3);
+
+    // SAFETY: only async-signal-safe fcntl and dup2 are called, and the closure doesn't allocate
+    unsafe {
+        command.pre_exec(move || {
+            // the write ends and reservations are owned by the hook, so they're closed in the parent once the command
+            // is dropped
+            let _ = (&write_ends, &reserved_fds);
+            // first move all write ends above the targets, so that no dup2 can overwrite a yet unmoved write end
+            for (write_fd, _) in mappings.iter_mut() {
+                *write_fd = fcntl(*write_fd, FcntlArg::F_DUPFD_CLOEXEC(lowest_free_fd))?;
+            }
+            for (write_fd, fd) in mappings.iter() {
+                dup2(*write_fd, *fd)?;
+            }
+            Ok(())
+        });
     }
 
-    if process_configuration.redirect_stdin {
+    Ok(read_ends)
+}
+
+fn create_command_from_config(process_configuration: &LinuxProcessConfiguration) -> io::Result<Command> {
+    let mut command = match process_configuration.shell_mode.invocation() {
+        Some((shell, shell_args)) => {
+            // exec makes the program take over the shell's PID, just like with direct execution
+            let mut shell_command = format!("exec {}", escape((&process_configuration.program).into()));
+            for arg in &process_configuration.args {
+                shell_command.push(' ');
+                shell_command.push_str(escape(arg.into()).to_string().as_str());
+            }
+
+            let mut command = Command::new(shell);
+            command.args(shell_args).arg(shell_command);
+            command
+        }
+        None => {
+            let mut command = Command::new(&process_configuration.program);
+            command.args(&process_configuration.args);
+            command
+        }
+    };
+    command.envs(&process_configuration.envs);
+
+    if let Some(working_dir) = &process_configuration.working_dir {
+        command.current_dir(working_dir);
+    }
+
+    command.stdout(create_output_stdio(
+        process_configuration,
+        &process_configuration.stdout_target,
+    )?);
+    command.stderr(create_output_stdio(
+        process_configuration,
+        &process_configuration.stderr_target,
+    )?);
+
+    if let Some(stdin_file) = &process_configuration.stdin_file {
+        command.stdin(Stdio::from(File::open(resolve_path(
+            process_configuration,
+            stdin_file,
+        ))?));
+    } else if process_configuration.redirect_stdin {
         command.stdin(Stdio::piped());
     } else {
         command.stdin(Stdio::null());
@@ -248,5 +410,33 @@ fn create_command_from_config(process_configuration: &LinuxProcessConfiguration)
         command.gid(gid);
     }
 
-    command
+    Ok(command)
+}
+
+fn create_output_stdio(
+    process_configuration: &LinuxProcessConfiguration,
+    output_target: &LinuxOutputTarget,
+) -> io::Result<Stdio> {
+    Ok(match output_target {
+        LinuxOutputTarget::Null => Stdio::null(),
+        LinuxOutputTarget::Inherit => Stdio::inherit(),
+        LinuxOutputTarget::Pipe => Stdio::piped(),
+        LinuxOutputTarget::File { path, append } => {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(*append)
+                .truncate(!*append)
+                .open(resolve_path(process_configuration, path))?;
+            Stdio::from(file)
+        }
+    })
+}
+
+// resolve relative paths against the working dir, same as the shell redirections in remote impls do
+fn resolve_path(process_configuration: &LinuxProcessConfiguration, path: &str) -> PathBuf {
+    match &process_configuration.working_dir {
+        Some(working_dir) => Path::new(working_dir).join(path),
+        None => PathBuf::from(path),
+    }
 }
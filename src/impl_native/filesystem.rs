@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream, StreamExt};
 use tokio::fs::{
     canonicalize, copy, create_dir, create_dir_all, hard_link, metadata, read_dir, read_link, remove_dir,
     remove_dir_all, remove_file, rename, set_permissions, symlink, symlink_metadata, try_exists, File, OpenOptions,
@@ -6,14 +7,35 @@ use tokio::fs::{
 
 use super::NativeLinux;
 use crate::filesystem::{
-    LinuxDirEntry, LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions,
+    self, LinuxAcl, LinuxChecksumAlgorithm, LinuxDirEntry, LinuxFile, LinuxFileLock, LinuxFileMetadata, LinuxFileType,
+    LinuxFilesystem, LinuxFsStats, LinuxLockHolder, LinuxLockMode, LinuxMountFlags, LinuxOpenOptions, LinuxPermissions,
+};
+use crate::{acl_ext, checksum};
+use nix::{
+    errno::Errno,
+    fcntl::{fallocate, FallocateFlags, Flock, FlockArg},
+    sys::{
+        stat::{makedev, mknod, utimensat, Mode, SFlag, UtimensatFlags},
+        statvfs::statvfs,
+        time::TimeSpec,
+    },
+    unistd::{close, mkfifo},
 };
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::{CString, OsStr, OsString},
     fs::{FileType, Metadata, Permissions},
     io,
-    os::unix::fs::{MetadataExt, PermissionsExt},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{chown, lchown, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+        io::{AsRawFd, IntoRawFd},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tokio::{io::AsyncWriteExt, process::Command, task::spawn_blocking};
+
+// how often a lock with a timeout is retried, since flock can't wait for a limited time
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 #[async_trait]
 impl LinuxFilesystem for NativeLinux {
@@ -38,6 +60,24 @@ impl LinuxFilesystem for NativeLinux {
         if open_options.is_create() {
             final_options.create(true);
         }
+        if open_options.is_create_new() {
+            final_options.create_new(true);
+        }
+        if let Some(mode) = open_options.get_mode() {
+            final_options.mode(mode.bits());
+        }
+
+        let mut custom_flags = 0;
+        if open_options.is_no_follow() {
+            custom_flags |= libc::O_NOFOLLOW;
+        }
+        if open_options.is_directory() {
+            custom_flags |= libc::O_DIRECTORY;
+        }
+        if open_options.is_sync() {
+            custom_flags |= libc::O_SYNC;
+        }
+        final_options.custom_flags(custom_flags);
 
         final_options.open(path).await
     }
@@ -70,6 +110,44 @@ impl LinuxFilesystem for NativeLinux {
         hard_link(source_path, destination_path).await
     }
 
+    async fn create_fifo(&self, path: &OsStr, permissions: LinuxPermissions) -> io::Result<()> {
+        let path = path.to_os_string();
+        spawn_blocking(move || {
+            mkfifo(path.as_os_str(), Mode::from_bits_truncate(permissions.bits()))?;
+            // the mode passed to mkfifo is subject to the umask
+            std::fs::set_permissions(&path, Permissions::from_mode(permissions.bits()))
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    async fn create_device_node(
+        &self,
+        path: &OsStr,
+        file_type: LinuxFileType,
+        major: u32,
+        minor: u32,
+        permissions: LinuxPermissions,
+    ) -> io::Result<()> {
+        let kind = match file_type {
+            LinuxFileType::BlockDevice => SFlag::S_IFBLK,
+            LinuxFileType::CharDevice => SFlag::S_IFCHR,
+            _ => return Err(filesystem::invalid_device_type(file_type)),
+        };
+        let path = path.to_os_string();
+        spawn_blocking(move || {
+            mknod(
+                path.as_os_str(),
+                kind,
+                Mode::from_bits_truncate(permissions.bits()),
+                makedev(major.into(), minor.into()),
+            )?;
+            std::fs::set_permissions(&path, Permissions::from_mode(permissions.bits()))
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
     async fn read_link(&self, link_path: &OsStr) -> io::Result<OsString> {
         read_link(link_path).await.map(|path| path.into_os_string())
     }
@@ -78,6 +156,181 @@ impl LinuxFilesystem for NativeLinux {
         set_permissions(path, Permissions::from_mode(permissions.bits())).await
     }
 
+    async fn set_times(&self, path: &OsStr, accessed_time: SystemTime, modified_time: SystemTime) -> io::Result<()> {
+        // unlike opening the file to set its times, utimensat doesn't need read permission
+        let path = path.to_os_string();
+        spawn_blocking(move || {
+            utimensat(
+                None,
+                path.as_os_str(),
+                &time_spec(accessed_time),
+                &time_spec(modified_time),
+                UtimensatFlags::FollowSymlink,
+            )
+        })
+        .await
+        .map_err(io::Error::other)?
+        .map_err(io::Error::from)
+    }
+
+    async fn set_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        let path = path.to_os_string();
+        spawn_blocking(move || chown(path, user_id, group_id))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    async fn set_symlink_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        let path = path.to_os_string();
+        spawn_blocking(move || lchown(path, user_id, group_id))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    async fn set_len(&self, path: &OsStr, size: u64) -> io::Result<()> {
+        OpenOptions::new().write(true).open(path).await?.set_len(size).await
+    }
+
+    async fn allocate(&self, path: &OsStr, offset: u64, len: u64) -> io::Result<()> {
+        let offset = i64::try_from(offset).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let len = i64::try_from(len).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let file = OpenOptions::new().write(true).open(path).await?.into_std().await;
+        spawn_blocking(move || fallocate(file.as_raw_fd(), FallocateFlags::empty(), offset, len))
+            .await
+            .map_err(io::Error::other)?
+            .map_err(io::Error::from)
+    }
+
+    async fn sync_file(&self, path: &OsStr) -> io::Result<()> {
+        File::open(path).await?.sync_all().await
+    }
+
+    async fn sync_file_data(&self, path: &OsStr) -> io::Result<()> {
+        File::open(path).await?.sync_data().await
+    }
+
+    async fn get_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        let path = to_cstring(path)?;
+        let name = to_cstring(name)?;
+        spawn_blocking(move || {
+            // SAFETY: path and name are NUL-terminated and outlive the call, and read_xattr_buffer only passes a null
+            // buffer with a size of 0 or a buffer of at least size bytes
+            let result = read_xattr_buffer(|buf, size| unsafe {
+                libc::getxattr(path.as_ptr(), name.as_ptr(), buf.cast(), size)
+            });
+            match result {
+                Ok(value) => Ok(Some(value)),
+                Err(err) if err.raw_os_error() == Some(libc::ENODATA) => Ok(None),
+                Err(err) => Err(err),
+            }
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    async fn set_xattr(&self, path: &OsStr, name: &OsStr, value: &[u8]) -> io::Result<()> {
+        let path = to_cstring(path)?;
+        let name = to_cstring(name)?;
+        let value = value.to_vec();
+        spawn_blocking(move || {
+            // SAFETY: path and name are NUL-terminated, and value is valid for reads of its length
+            let result = unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+            match result {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    async fn list_xattrs(&self, path: &OsStr) -> io::Result<Vec<OsString>> {
+        let path = to_cstring(path)?;
+        spawn_blocking(move || {
+            // SAFETY: path is NUL-terminated, and read_xattr_buffer only passes a null buffer with a size of 0 or a
+            // buffer of at least size bytes
+            let names = read_xattr_buffer(|buf, size| unsafe { libc::listxattr(path.as_ptr(), buf.cast(), size) })?;
+            // the names are NUL-terminated and concatenated
+            Ok(names
+                .split(|byte| *byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| OsStr::from_bytes(name).to_os_string())
+                .collect())
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    async fn remove_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<()> {
+        let path = to_cstring(path)?;
+        let name = to_cstring(name)?;
+        spawn_blocking(move || {
+            // SAFETY: path and name are NUL-terminated and outlive the call
+            match unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    async fn get_acl(&self, path: &OsStr) -> io::Result<LinuxAcl> {
+        let access = match self.get_xattr(path, OsStr::new(acl_ext::ACCESS_XATTR)).await? {
+            Some(value) => acl_ext::decode_xattr(&value)?,
+            None => acl_ext::mode_entries(LinuxPermissions::from_bits_truncate(metadata(path).await?.mode())),
+        };
+        let default = match self.get_xattr(path, OsStr::new(acl_ext::DEFAULT_XATTR)).await? {
+            Some(value) => acl_ext::decode_xattr(&value)?,
+            None => Vec::new(),
+        };
+        Ok(LinuxAcl { access, default })
+    }
+
+    async fn set_acl(&self, path: &OsStr, acl: &LinuxAcl) -> io::Result<()> {
+        self.set_xattr(
+            path,
+            OsStr::new(acl_ext::ACCESS_XATTR),
+            &acl_ext::encode_xattr(&acl.access),
+        )
+        .await?;
+        match acl.default.is_empty() {
+            true => match self.remove_xattr(path, OsStr::new(acl_ext::DEFAULT_XATTR)).await {
+                Err(err) if err.raw_os_error() != Some(libc::ENODATA) => Err(err),
+                _ => Ok(()),
+            },
+            false => {
+                self.set_xattr(
+                    path,
+                    OsStr::new(acl_ext::DEFAULT_XATTR),
+                    &acl_ext::encode_xattr(&acl.default),
+                )
+                .await
+            }
+        }
+    }
+
+    async fn lock_file(
+        &self,
+        path: &OsStr,
+        mode: LinuxLockMode,
+        timeout: Option<Duration>,
+    ) -> io::Result<LinuxFileLock> {
+        let path = path.to_os_string();
+        spawn_blocking(move || {
+            // like flock(1), open read-only so that locking works without write access. std only allows creating
+            // files that are opened for writing, hence the raw flag
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_CREAT)
+                .open(&path)?;
+            let lock = lock_blocking(file, mode, timeout)?;
+            Ok(LinuxFileLock::new(NativeLockHolder(lock)))
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
     async fn remove_file(&self, path: &OsStr) -> io::Result<()> {
         remove_file(path).await
     }
@@ -118,6 +371,25 @@ impl LinuxFilesystem for NativeLinux {
         Ok(entries)
     }
 
+    async fn read_dir_stream(&self, path: &OsStr) -> io::Result<BoxStream<'static, io::Result<LinuxDirEntry>>> {
+        let read_dir = read_dir(path).await?;
+
+        Ok(stream::try_unfold(read_dir, |mut read_dir| async move {
+            match read_dir.next_entry().await? {
+                Some(entry_value) => {
+                    let dir_entry = LinuxDirEntry {
+                        name: entry_value.file_name(),
+                        file_type: entry_value.file_type().await?.into(),
+                        path: entry_value.path().into_os_string(),
+                    };
+                    Ok(Some((dir_entry, read_dir)))
+                }
+                None => Ok(None),
+            }
+        })
+        .boxed())
+    }
+
     async fn remove_dir(&self, path: &OsStr) -> io::Result<()> {
         remove_dir(path).await
     }
@@ -127,45 +399,220 @@ impl LinuxFilesystem for NativeLinux {
     }
 
     async fn get_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
-        let metadata = metadata(path).await?;
-        Ok(metadata.into())
+        let mut metadata = metadata(path).await?.into();
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
     }
 
     async fn get_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
-        let metadata = symlink_metadata(path).await?;
-        Ok(metadata.into())
+        let mut metadata = symlink_metadata(path).await?.into();
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
+    }
+
+    async fn fs_stats(&self, path: &OsStr) -> io::Result<LinuxFsStats> {
+        let path = path.to_owned();
+        let stats = spawn_blocking(move || statvfs(path.as_os_str()))
+            .await
+            .map_err(io::Error::other)??;
+        Ok(LinuxFsStats {
+            block_size: stats.block_size() as u64,
+            fragment_size: stats.fragment_size() as u64,
+            total_blocks: stats.blocks() as u64,
+            free_blocks: stats.blocks_free() as u64,
+            available_blocks: stats.blocks_available() as u64,
+            total_inodes: stats.files() as u64,
+            free_inodes: stats.files_free() as u64,
+            available_inodes: stats.files_available() as u64,
+            mount_flags: Some(LinuxMountFlags::from_bits_truncate(stats.flags().bits() as u64)),
+        })
+    }
+
+    async fn lookup_user(&self, user_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_user(self, user_id).await
+    }
+
+    async fn lookup_group(&self, group_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_group(self, group_id).await
+    }
+
+    async fn checksum(&self, path: &OsStr, algorithm: LinuxChecksumAlgorithm) -> io::Result<String> {
+        if algorithm != LinuxChecksumAlgorithm::Blake3 {
+            return checksum::hash_reader(File::open(path).await?, algorithm).await;
+        }
+
+        // BLAKE3 isn't hashed in-process, so it's left to a local b3sum like on remote hosts
+        let output = Command::new("b3sum").arg("--").arg(path).output().await?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "b3sum failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        checksum::parse_sum_output(&output.stdout)?
+            .into_iter()
+            .next()
+            .map(|(_, checksum)| checksum)
+            .ok_or_else(|| io::Error::other("b3sum didn't output anything"))
     }
 }
 
-impl Into<LinuxFileType> for FileType {
-    fn into(self) -> LinuxFileType {
-        if self.is_file() {
+impl From<FileType> for LinuxFileType {
+    fn from(value: FileType) -> Self {
+        if value.is_file() {
             return LinuxFileType::File;
         }
-        if self.is_dir() {
+        if value.is_dir() {
             return LinuxFileType::Dir;
         }
-        if self.is_symlink() {
+        if value.is_symlink() {
             return LinuxFileType::Symlink;
         }
+        if value.is_fifo() {
+            return LinuxFileType::Fifo;
+        }
+        if value.is_socket() {
+            return LinuxFileType::Socket;
+        }
+        if value.is_block_device() {
+            return LinuxFileType::BlockDevice;
+        }
+        if value.is_char_device() {
+            return LinuxFileType::CharDevice;
+        }
 
         LinuxFileType::Other
     }
 }
 
-impl Into<LinuxFileMetadata> for Metadata {
-    fn into(self) -> LinuxFileMetadata {
+#[async_trait]
+impl LinuxFile for File {
+    async fn metadata(&mut self) -> io::Result<LinuxFileMetadata> {
+        // tokio writes in the background, so a pending write could be missing from the size
+        self.flush().await?;
+        File::metadata(self).await.map(|metadata| metadata.into())
+    }
+
+    async fn set_len(&mut self, size: u64) -> io::Result<()> {
+        File::set_len(self, size).await
+    }
+
+    async fn sync_all(&mut self) -> io::Result<()> {
+        File::sync_all(self).await
+    }
+
+    async fn close(mut self: Box<Self>) -> io::Result<()> {
+        self.flush().await?;
+        // dropping the std file would ignore what close reports, e.g. write-back errors of network filesystems
+        close(self.into_std().await.into_raw_fd()).map_err(io::Error::from)
+    }
+}
+
+// the lock is tied to the open file description, so closing the file releases it
+struct NativeLockHolder(Flock<std::fs::File>);
+
+#[async_trait]
+impl LinuxLockHolder for NativeLockHolder {
+    async fn release(self: Box<Self>) -> io::Result<()> {
+        self.0.unlock().map(|_| ()).map_err(|(_, errno)| errno.into())
+    }
+}
+
+impl From<Metadata> for LinuxFileMetadata {
+    fn from(value: Metadata) -> Self {
         LinuxFileMetadata {
-            file_type: Some(self.file_type().into()),
-            size: Some(self.size()),
-            permissions: Some(LinuxPermissions::from_bits_retain(self.permissions().mode())),
-            modified_time: self.modified().ok(),
-            accessed_time: self.accessed().ok(),
-            created_time: self.created().ok(),
-            user_id: Some(self.uid()),
+            file_type: Some(value.file_type().into()),
+            size: Some(value.size()),
+            permissions: Some(LinuxPermissions::from_bits_retain(value.permissions().mode())),
+            modified_time: value.modified().ok(),
+            accessed_time: value.accessed().ok(),
+            created_time: value.created().ok(),
+            changed_time: unix_time(value.ctime(), value.ctime_nsec()),
+            user_id: Some(value.uid()),
             user_name: None,
-            group_id: Some(self.gid()),
+            group_id: Some(value.gid()),
             group_name: None,
+            inode: Some(value.ino()),
+            device_id: Some(value.dev()),
+            special_device_id: Some(value.rdev()),
+            hard_link_count: Some(value.nlink()),
+            block_size: Some(value.blksize()),
+            block_count: Some(value.blocks()),
+        }
+    }
+}
+
+fn unix_time(secs: i64, nanos: i64) -> Option<SystemTime> {
+    let nanos = Duration::from_nanos(nanos.try_into().ok()?);
+    match secs >= 0 {
+        true => UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64) + nanos),
+        false => UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+            .checked_add(nanos),
+    }
+}
+
+fn time_spec(time: SystemTime) -> TimeSpec {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => TimeSpec::from(duration),
+        Err(err) => -TimeSpec::from(err.duration()),
+    }
+}
+
+fn lock_blocking(
+    mut file: std::fs::File,
+    mode: LinuxLockMode,
+    timeout: Option<Duration>,
+) -> io::Result<Flock<std::fs::File>> {
+    let (blocking_arg, non_blocking_arg) = match mode {
+        LinuxLockMode::Shared => (FlockArg::LockShared, FlockArg::LockSharedNonblock),
+        LinuxLockMode::Exclusive => (FlockArg::LockExclusive, FlockArg::LockExclusiveNonblock),
+    };
+    let Some(timeout) = timeout else {
+        return Flock::lock(file, blocking_arg).map_err(|(_, errno)| errno.into());
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match Flock::lock(file, non_blocking_arg) {
+            Ok(lock) => return Ok(lock),
+            Err((unlocked_file, Errno::EWOULDBLOCK)) => {
+                file = unlocked_file;
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for the lock",
+                    ));
+                }
+                std::thread::sleep(LOCK_POLL_INTERVAL.min(deadline - now));
+            }
+            Err((_, errno)) => return Err(errno.into()),
+        }
+    }
+}
+
+fn to_cstring(value: &OsStr) -> io::Result<CString> {
+    CString::new(value.as_bytes()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+// calls an xattr function that fills a buffer, first to learn the size and then to read into a buffer of that size.
+// The size is learned again if the value grew in between. The buffer passed to read is either null with a size of 0,
+// or valid for writes of the given size
+fn read_xattr_buffer(read: impl Fn(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let size = match read(std::ptr::null_mut(), 0) {
+            -1 => return Err(io::Error::last_os_error()),
+            size => size as usize,
+        };
+        let mut buf = vec![0u8; size];
+        match read(buf.as_mut_ptr(), size) {
+            -1 if io::Error::last_os_error().raw_os_error() == Some(libc::ERANGE) => continue,
+            -1 => return Err(io::Error::last_os_error()),
+            read_size => {
+                buf.truncate(read_size as usize);
+                return Ok(buf);
+            }
         }
     }
 }
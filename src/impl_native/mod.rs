@@ -5,4 +5,25 @@ mod filesystem;
 #[cfg(feature = "network")]
 mod network;
 
-pub struct NativeLinux {}
+#[cfg(feature = "filesystem")]
+use crate::name_cache::NameCache;
+
+pub struct NativeLinux {
+    #[cfg(feature = "filesystem")]
+    name_cache: NameCache,
+}
+
+impl NativeLinux {
+    pub const fn new() -> NativeLinux {
+        NativeLinux {
+            #[cfg(feature = "filesystem")]
+            name_cache: NameCache::new(),
+        }
+    }
+}
+
+impl Default for NativeLinux {
+    fn default() -> Self {
+        NativeLinux::new()
+    }
+}
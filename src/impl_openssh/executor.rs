@@ -14,15 +14,20 @@ use dashmap::DashMap;
 use nix::sys::signal::Signal;
 use once_cell::sync::Lazy;
 use openssh::{Child, ChildStdin, OwningCommand, Session, Stdio};
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use openssh_sftp_client::Sftp;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    sync::Mutex,
+};
 
 use crate::{
-    derive_ext::DeriveExt,
+    derive_ext::{DeriveExt, DerivedShellCommand},
     executor::{
-        FinishedLinuxProcessOutput, LinuxExecutor, LinuxProcess, LinuxProcessConfiguration, LinuxProcessError,
-        LinuxProcessOutput, LinuxStreamType,
+        FinishedLinuxProcessOutput, LinuxExecutor, LinuxOutputTarget, LinuxProcess, LinuxProcessConfiguration,
+        LinuxProcessError, LinuxProcessOutput, LinuxStreamType,
     },
     filesystem::{LinuxFilesystem, LinuxOpenOptions},
+    script_ext::ScriptExt,
 };
 
 use super::OpensshLinux;
@@ -36,6 +41,8 @@ struct OpensshLinuxProcess {
     stdin: Option<ChildStdin>,
     synthetic_id: u32,
     pid_option: Option<u32>,
+    sftp_mutex: Arc<Mutex<Sftp>>,
+    fd_files: Vec<(u32, String)>,
 }
 
 #[async_trait]
@@ -67,11 +74,14 @@ impl LinuxProcess for OpensshLinuxProcess {
     }
 
     async fn await_exit(self: Box<Self>) -> Result<Option<i64>, LinuxProcessError> {
-        self.child
+        let status_code = self
+            .child
             .wait()
             .await
             .map(|status| status.code().map(|i| i.into()))
-            .map_err(|err| LinuxProcessError::Other(Box::new(err)))
+            .map_err(|err| LinuxProcessError::Other(Box::new(err)))?;
+        collect_fd_files(&self.sftp_mutex, &self.fd_files).await;
+        Ok(status_code)
     }
 
     async fn await_exit_with_output(self: Box<Self>) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
@@ -82,7 +92,10 @@ impl LinuxProcess for OpensshLinuxProcess {
             .map_err(|err| LinuxProcessError::Other(Box::new(err)))?
             .code()
             .map(|i| i.into());
-        let output = get_current_output_internal(self.synthetic_id);
+        let mut output = get_current_output_internal(self.synthetic_id);
+        output
+            .stdout_extended
+            .extend(collect_fd_files(&self.sftp_mutex, &self.fd_files).await);
         Ok(FinishedLinuxProcessOutput::join(output, status_code))
     }
 }
@@ -93,7 +106,7 @@ impl LinuxExecutor for OpensshLinux {
         &self,
         process_configuration: &LinuxProcessConfiguration,
     ) -> Result<Box<dyn LinuxProcess>, LinuxProcessError> {
-        let (mut owning_command, pid_file) = create_owning_command(&self, &process_configuration);
+        let (mut owning_command, shell_command) = create_owning_command(self, process_configuration);
         let mut child = owning_command
             .spawn()
             .await
@@ -101,20 +114,20 @@ impl LinuxExecutor for OpensshLinux {
         let synthetic_id = SYNTHETIC_ID_GENERATOR.fetch_add(1, Ordering::Relaxed);
         let stdin = child.stdin().take();
 
-        if process_configuration.redirect_stdout {
+        if process_configuration.stdout_target.is_piped() {
             spawn_capture_task(synthetic_id, LinuxStreamType::Stdout, &mut child);
         }
 
-        if process_configuration.redirect_stderr {
+        if process_configuration.stderr_target.is_piped() {
             spawn_capture_task(synthetic_id, LinuxStreamType::Stderr, &mut child);
         }
 
         #[allow(unused)]
         let mut pid_option: Option<u32> = None;
-        let pid_file_os_str = OsString::from(pid_file);
+        let pid_file_os_str = OsString::from(shell_command.pid_file);
         let pid_file_os_str = pid_file_os_str.as_os_str();
         loop {
-            if let Ok(mut reader) = self.open_file(pid_file_os_str, &LinuxOpenOptions::new().read()).await {
+            if let Ok(mut reader) = self.open_file(pid_file_os_str, LinuxOpenOptions::new().read()).await {
                 let mut content = String::new();
                 if reader.read_to_string(&mut content).await.is_ok() {
                     pid_option = content.trim_end().parse().ok();
@@ -130,6 +143,8 @@ impl LinuxExecutor for OpensshLinux {
             synthetic_id,
             stdin,
             pid_option,
+            sftp_mutex: self.sftp_mutex.clone(),
+            fd_files: shell_command.fd_files,
         }))
     }
 
@@ -137,12 +152,26 @@ impl LinuxExecutor for OpensshLinux {
         &self,
         process_configuration: &LinuxProcessConfiguration,
     ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
-        let (mut owning_command, _) = create_owning_command(&self, &process_configuration);
+        let (mut owning_command, shell_command) = create_owning_command(self, process_configuration);
         let output = owning_command
             .output()
             .await
             .map_err(|err| LinuxProcessError::Other(Box::new(err)))?;
-        Ok(conv_finished_output(output))
+        let mut finished_output = conv_finished_output(output);
+        finished_output
+            .stdout_extended
+            .extend(collect_fd_files(&self.sftp_mutex, &shell_command.fd_files).await);
+        Ok(finished_output)
+    }
+
+    async fn execute_script(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
+        self.execute_script_via_filesystem(interpreter, script_body, process_configuration)
+            .await
     }
 
     async fn send_signal(&self, signal: Signal, process_id: u32) -> Result<(), LinuxProcessError> {
@@ -203,19 +232,10 @@ fn get_current_output_internal(synthetic_id: u32) -> LinuxProcessOutput {
 fn create_owning_command(
     instance: &OpensshLinux,
     process_configuration: &LinuxProcessConfiguration,
-) -> (OwningCommand<Arc<Session>>, String) {
+) -> (OwningCommand<Arc<Session>>, DerivedShellCommand) {
     let apply_pipes = |owning_command: &mut OwningCommand<Arc<Session>>| {
-        if process_configuration.redirect_stdout {
-            owning_command.stdout(Stdio::piped());
-        } else {
-            owning_command.stdout(Stdio::null());
-        }
-
-        if process_configuration.redirect_stderr {
-            owning_command.stderr(Stdio::piped());
-        } else {
-            owning_command.stderr(Stdio::null());
-        }
+        owning_command.stdout(create_output_stdio(&process_configuration.stdout_target));
+        owning_command.stderr(create_output_stdio(&process_configuration.stderr_target));
 
         if process_configuration.redirect_stdin {
             owning_command.stdin(Stdio::piped());
@@ -224,10 +244,34 @@ fn create_owning_command(
         }
     };
 
-    let (command, pid_file) = process_configuration.derive_shell_command();
-    let mut owning_command = instance.session.clone().arc_shell(command);
+    let shell_command = process_configuration.derive_shell_command();
+    let mut owning_command = instance.session.clone().arc_shell(&shell_command.command);
     apply_pipes(&mut owning_command);
-    (owning_command, pid_file)
+    (owning_command, shell_command)
+}
+
+fn create_output_stdio(output_target: &LinuxOutputTarget) -> Stdio {
+    match output_target {
+        LinuxOutputTarget::Inherit => Stdio::inherit(),
+        LinuxOutputTarget::Pipe => Stdio::piped(),
+        // the remote shell already redirects the stream, so nothing arrives locally
+        LinuxOutputTarget::Null | LinuxOutputTarget::File { .. } => Stdio::null(),
+    }
+}
+
+// reads the files captured extra fds were redirected into and removes them, skipping ones that were never created
+async fn collect_fd_files(sftp_mutex: &Arc<Mutex<Sftp>>, fd_files: &[(u32, String)]) -> HashMap<u32, Vec<u8>> {
+    let sftp = sftp_mutex.lock().await;
+    let mut fd_outputs = HashMap::new();
+
+    for (fd, fd_file) in fd_files {
+        if let Ok(content) = sftp.fs().read(fd_file).await {
+            fd_outputs.insert(*fd, content.to_vec());
+            let _ = sftp.fs().remove_file(fd_file).await;
+        }
+    }
+
+    fd_outputs
 }
 
 fn spawn_capture_task(synthetic_id: u32, capturer_type: LinuxStreamType, child: &mut Child<Arc<Session>>) {
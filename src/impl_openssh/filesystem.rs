@@ -1,22 +1,34 @@
 use std::{
+    collections::BTreeMap,
     ffi::{OsStr, OsString},
-    io,
-    path::PathBuf,
+    io, iter,
+    path::{Path, PathBuf},
     pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use async_trait::async_trait;
-use futures_util::StreamExt;
+use futures_util::{stream::BoxStream, StreamExt};
+use openssh::{Child, ChildStdin, Session, Stdio};
 use openssh_sftp_client::{
     file::TokioCompatFile,
-    metadata::{FileType, MetaData, Permissions},
+    metadata::{FileType, MetaData, MetaDataBuilder, Permissions},
+    UnixTimeStamp,
 };
-
-use crate::filesystem::{
-    LinuxDirEntry, LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions,
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    acl_ext, checksum,
+    filesystem::{
+        self, LinuxAcl, LinuxChecksumAlgorithm, LinuxDirEntry, LinuxFile, LinuxFileLock, LinuxFileMetadata,
+        LinuxFileType, LinuxFilesystem, LinuxFsStats, LinuxLockHolder, LinuxLockMode, LinuxOpenOptions,
+        LinuxPermissions,
+    },
+    lock_ext, stat_ext, xattr_ext,
 };
 
-use super::OpensshLinux;
+use super::{statvfs, OpensshLinux};
 
 #[async_trait]
 impl LinuxFilesystem for OpensshLinux {
@@ -55,9 +67,28 @@ impl LinuxFilesystem for OpensshLinux {
             actual_options.create(true); // specificity of this impl
             actual_options.truncate(true);
         }
+        if open_options.is_create_new() {
+            actual_options.create_new(true);
+        }
+
+        let mut file_type = None;
+        if open_options.needs_sftp_file_type() {
+            let metadata = match open_options.is_no_follow() {
+                true => sftp.fs().symlink_metadata(path).await,
+                false => sftp.fs().metadata(path).await,
+            };
+            file_type = metadata
+                .ok()
+                .and_then(|metadata| metadata.file_type())
+                .map(|file_type| file_type.into());
+        }
+        open_options.check_sftp_file_type(file_type)?;
 
-        let tokio_compat_file = TokioCompatFile::new(actual_options.open(path).await.map_err(io::Error::other)?);
-        Ok(Box::pin(tokio_compat_file))
+        let mut file = actual_options.open(path).await.map_err(io::Error::other)?;
+        if let Some(mode) = open_options.sftp_mode_to_apply(file_type) {
+            file.set_permissions(mode.into()).await.map_err(io::Error::other)?;
+        }
+        Ok(Box::pin(TokioCompatFile::new(file)))
     }
 
     async fn rename_file(&self, old_path: &OsStr, new_path: &OsStr) -> io::Result<()> {
@@ -114,6 +145,46 @@ impl LinuxFilesystem for OpensshLinux {
             .map_err(io::Error::other)
     }
 
+    async fn create_fifo(&self, path: &OsStr, permissions: LinuxPermissions) -> io::Result<()> {
+        run_fs_command(
+            self,
+            "mkfifo",
+            vec![
+                "-m",
+                &format!("{:o}", permissions.bits() & 0o7777),
+                "--",
+                path.to_str()
+                    .ok_or(io::Error::other("path couldn't be converted to str"))?,
+            ],
+        )
+        .await
+    }
+
+    async fn create_device_node(
+        &self,
+        path: &OsStr,
+        file_type: LinuxFileType,
+        major: u32,
+        minor: u32,
+        permissions: LinuxPermissions,
+    ) -> io::Result<()> {
+        run_fs_command(
+            self,
+            "mknod",
+            vec![
+                "-m",
+                &format!("{:o}", permissions.bits() & 0o7777),
+                "--",
+                path.to_str()
+                    .ok_or(io::Error::other("path couldn't be converted to str"))?,
+                filesystem::mknod_type_arg(file_type)?,
+                &major.to_string(),
+                &minor.to_string(),
+            ],
+        )
+        .await
+    }
+
     async fn read_link(&self, link_path: &OsStr) -> io::Result<OsString> {
         let sftp = self.sftp_mutex.lock().await;
         sftp.fs()
@@ -131,6 +202,216 @@ impl LinuxFilesystem for OpensshLinux {
             .map_err(io::Error::other)
     }
 
+    async fn set_times(&self, path: &OsStr, accessed_time: SystemTime, modified_time: SystemTime) -> io::Result<()> {
+        let sftp = self.sftp_mutex.lock().await;
+        let accessed_time = UnixTimeStamp::new(accessed_time).map_err(io::Error::other)?;
+        let modified_time = UnixTimeStamp::new(modified_time).map_err(io::Error::other)?;
+        sftp.fs()
+            .set_metadata(path, MetaDataBuilder::new().time(accessed_time, modified_time).create())
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn set_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        let sftp = self.sftp_mutex.lock().await;
+        let mut fs = sftp.fs();
+        // SFTP sets the user and group together, so a missing one is filled in with its current value
+        let (user_id, group_id) = match (user_id, group_id) {
+            (Some(user_id), Some(group_id)) => (user_id, group_id),
+            (None, None) => return Ok(()),
+            _ => {
+                let current = fs.metadata(path).await.map_err(io::Error::other)?;
+                (
+                    user_id
+                        .or(current.uid())
+                        .ok_or(io::Error::other("file has no user id"))?,
+                    group_id
+                        .or(current.gid())
+                        .ok_or(io::Error::other("file has no group id"))?,
+                )
+            }
+        };
+
+        fs.set_metadata(path, MetaDataBuilder::new().id((user_id, group_id)).create())
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn set_symlink_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        // SFTP v3 setstat always follows symlinks
+        let Some(owner) = filesystem::chown_owner_arg(user_id, group_id) else {
+            return Ok(());
+        };
+        run_fs_command(
+            self,
+            "chown",
+            vec![
+                "-h",
+                &owner,
+                "--",
+                path.to_str()
+                    .ok_or(io::Error::other("path couldn't be converted to str"))?,
+            ],
+        )
+        .await
+    }
+
+    async fn set_len(&self, path: &OsStr, size: u64) -> io::Result<()> {
+        let sftp = self.sftp_mutex.lock().await;
+        sftp.fs()
+            .set_metadata(path, MetaDataBuilder::new().len(size).create())
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn allocate(&self, path: &OsStr, offset: u64, len: u64) -> io::Result<()> {
+        run_fs_command(
+            self,
+            "fallocate",
+            vec![
+                "-o",
+                &offset.to_string(),
+                "-l",
+                &len.to_string(),
+                "--",
+                path.to_str()
+                    .ok_or(io::Error::other("path couldn't be converted to str"))?,
+            ],
+        )
+        .await
+    }
+
+    async fn sync_file(&self, path: &OsStr) -> io::Result<()> {
+        let sftp = self.sftp_mutex.lock().await;
+        let mut file = sftp.options().read(true).open(path).await.map_err(io::Error::other)?;
+        let sync_result = match file.sync_all().await {
+            Ok(()) => Ok(()),
+            Err(openssh_sftp_client::Error::UnsupportedExtension(_)) => Err(filesystem::fsync_unsupported_error()),
+            Err(err) => Err(io::Error::other(err)),
+        };
+        file.close().await.map_err(io::Error::other)?;
+        sync_result
+    }
+
+    async fn get_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        let output = run_attr_command(self, "extended attributes", "getfattr", xattr_ext::dump_args(str_path)).await?;
+        Ok(xattr_ext::parse_dump(&output)?
+            .into_iter()
+            .find(|(xattr_name, _)| xattr_name == name)
+            .map(|(_, value)| value))
+    }
+
+    async fn set_xattr(&self, path: &OsStr, name: &OsStr, value: &[u8]) -> io::Result<()> {
+        let args = xattr_ext::set_args(
+            path.to_str()
+                .ok_or(io::Error::other("path couldn't be converted to str"))?,
+            name.to_str()
+                .ok_or(io::Error::other("name couldn't be converted to str"))?,
+            value,
+        );
+        run_attr_command(
+            self,
+            "extended attributes",
+            "setfattr",
+            args.iter().map(|arg| arg.as_str()).collect(),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn list_xattrs(&self, path: &OsStr) -> io::Result<Vec<OsString>> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        let output = run_attr_command(self, "extended attributes", "getfattr", xattr_ext::dump_args(str_path)).await?;
+        Ok(xattr_ext::parse_dump(&output)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    async fn remove_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<()> {
+        let args = xattr_ext::remove_args(
+            path.to_str()
+                .ok_or(io::Error::other("path couldn't be converted to str"))?,
+            name.to_str()
+                .ok_or(io::Error::other("name couldn't be converted to str"))?,
+        );
+        run_attr_command(self, "extended attributes", "setfattr", args)
+            .await
+            .map(|_| ())
+    }
+
+    async fn get_acl(&self, path: &OsStr) -> io::Result<LinuxAcl> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        let output = run_attr_command(self, "ACLs", "getfacl", acl_ext::getfacl_args(str_path)).await?;
+        acl_ext::parse_getfacl(&output)
+    }
+
+    async fn set_acl(&self, path: &OsStr, acl: &LinuxAcl) -> io::Result<()> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        if acl.default.is_empty() {
+            run_attr_command(self, "ACLs", "setfacl", acl_ext::remove_default_args(str_path)).await?;
+        }
+        let args = acl_ext::setfacl_args(str_path, acl);
+        run_attr_command(self, "ACLs", "setfacl", args.iter().map(|arg| arg.as_str()).collect())
+            .await
+            .map(|_| ())
+    }
+
+    async fn lock_file(
+        &self,
+        path: &OsStr,
+        mode: LinuxLockMode,
+        timeout: Option<Duration>,
+    ) -> io::Result<LinuxFileLock> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        // the command owns the session, so that the lock can outlive this borrow of the instance
+        let mut child = self
+            .session
+            .clone()
+            .arc_command("flock")
+            .args(lock_ext::flock_args(str_path, mode, timeout))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .await
+            .map_err(io::Error::other)?;
+
+        let mut stdout_pipe = child
+            .stdout()
+            .take()
+            .ok_or(io::Error::other("flock stdout wasn't piped"))?;
+        let mut stdout = Vec::new();
+        let mut buf = [0u8; 64];
+        loop {
+            let read_size = stdout_pipe.read(&mut buf).await?;
+            if read_size == 0 {
+                break;
+            }
+            stdout.extend_from_slice(&buf[..read_size]);
+            if lock_ext::is_locked(&stdout) {
+                return Ok(LinuxFileLock::new(OpensshLockHolder {
+                    stdin: child.stdin().take(),
+                    child,
+                }));
+            }
+        }
+
+        let status = child.wait().await.map_err(io::Error::other)?;
+        Err(lock_ext::status_error(str_path, status.code().map(|code| code as u32)))
+    }
+
     async fn remove_file(&self, path: &OsStr) -> io::Result<()> {
         let sftp = self.sftp_mutex.lock().await;
         sftp.fs().remove_file(path).await.map_err(io::Error::other)
@@ -162,6 +443,9 @@ impl LinuxFilesystem for OpensshLinux {
 
         while let Some(dir_entry_result) = read_dir.next().await {
             let dir_entry = dir_entry_result.map_err(io::Error::other)?;
+            if dir_entry.filename() == Path::new(".") || dir_entry.filename() == Path::new("..") {
+                continue;
+            }
 
             entries.push(LinuxDirEntry {
                 name: dir_entry.filename().as_os_str().to_os_string(),
@@ -176,6 +460,138 @@ impl LinuxFilesystem for OpensshLinux {
         Ok(entries)
     }
 
+    async fn list_dir_with_metadata(&self, path: &OsStr) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        let sftp = self.sftp_mutex.lock().await;
+        let read_dir = sftp.fs().open_dir(path).await.map_err(io::Error::other)?.read_dir();
+        tokio::pin!(read_dir);
+        let mut entries: Vec<(LinuxDirEntry, LinuxFileMetadata)> = Vec::new();
+
+        // readdir replies already carry the metadata of every entry, so no further stat requests are needed
+        while let Some(dir_entry_result) = read_dir.next().await {
+            let dir_entry = dir_entry_result.map_err(io::Error::other)?;
+            if dir_entry.filename() == Path::new(".") || dir_entry.filename() == Path::new("..") {
+                continue;
+            }
+
+            entries.push((
+                LinuxDirEntry {
+                    name: dir_entry.filename().as_os_str().to_os_string(),
+                    file_type: dir_entry
+                        .file_type()
+                        .ok_or(io::Error::other("file has no type"))?
+                        .into(),
+                    path: PathBuf::from(path).join(dir_entry.filename()).into_os_string(),
+                },
+                dir_entry.metadata().into(),
+            ));
+        }
+        // resolving names reads files through SFTP, so the session has to be released first
+        drop(sftp);
+
+        for (_, metadata) in entries.iter_mut() {
+            self.name_cache.apply_names(self, metadata).await;
+        }
+        Ok(entries)
+    }
+
+    async fn list_dir_with_extended_metadata(
+        &self,
+        path: &OsStr,
+    ) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        let mut entries = self.list_dir_with_metadata(path).await?;
+        for batch in entries.chunks_mut(stat_ext::STAT_BATCH_SIZE) {
+            let paths = batch
+                .iter()
+                .map(|(dir_entry, _)| {
+                    dir_entry
+                        .path
+                        .to_str()
+                        .ok_or(io::Error::other("path couldn't be converted to str"))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let stdout = run_stat_command(self, &paths, false).await?;
+            stat_ext::apply_stat_output(batch.iter_mut().map(|(_, metadata)| metadata), &stdout)?;
+        }
+        Ok(entries)
+    }
+
+    async fn read_dir_stream(&self, path: &OsStr) -> io::Result<BoxStream<'static, io::Result<LinuxDirEntry>>> {
+        let sftp = self.sftp_mutex.lock().await;
+        // the directory handle is owned by the stream, so the SFTP lock is released once it has been opened
+        let read_dir = sftp.fs().open_dir(path).await.map_err(io::Error::other)?.read_dir();
+        drop(sftp);
+        let path = PathBuf::from(path);
+
+        Ok(read_dir
+            .filter(|dir_entry_result| {
+                let is_special = dir_entry_result.as_ref().is_ok_and(|dir_entry| {
+                    dir_entry.filename() == Path::new(".") || dir_entry.filename() == Path::new("..")
+                });
+                std::future::ready(!is_special)
+            })
+            .map(move |dir_entry_result| {
+                let dir_entry = dir_entry_result.map_err(io::Error::other)?;
+                Ok(LinuxDirEntry {
+                    name: dir_entry.filename().as_os_str().to_os_string(),
+                    file_type: dir_entry
+                        .file_type()
+                        .ok_or(io::Error::other("file has no type"))?
+                        .into(),
+                    path: path.join(dir_entry.filename()).into_os_string(),
+                })
+            })
+            .scan(false, |errored, dir_entry_result| {
+                // end the stream after the first error, same as the other impls
+                if *errored {
+                    return std::future::ready(None);
+                }
+                *errored = dir_entry_result.is_err();
+                std::future::ready(Some(dir_entry_result))
+            })
+            .boxed())
+    }
+
+    async fn checksum(&self, path: &OsStr, algorithm: LinuxChecksumAlgorithm) -> io::Result<String> {
+        // the openssh SFTP client has no way to issue the requests of the check-file extension
+        run_sum_command(
+            &self,
+            algorithm.sum_command(),
+            vec![
+                "--",
+                path.to_str().ok_or(io::Error::other("could not convert path to str"))?,
+            ],
+        )
+        .await?
+        .into_iter()
+        .next()
+        .map(|(_, checksum)| checksum)
+        .ok_or_else(|| io::Error::other("checksum command didn't output anything"))
+    }
+
+    async fn checksum_tree(
+        &self,
+        path: &OsStr,
+        algorithm: LinuxChecksumAlgorithm,
+    ) -> io::Result<BTreeMap<OsString, String>> {
+        // a single find invocation instead of a round trip per file
+        let checksums = run_sum_command(
+            &self,
+            "find",
+            vec![
+                path.to_str().ok_or(io::Error::other("could not convert path to str"))?,
+                "-type",
+                "f",
+                "-exec",
+                algorithm.sum_command(),
+                "--",
+                "{}",
+                "+",
+            ],
+        )
+        .await?;
+        Ok(checksum::relativize_checksums(path, checksums))
+    }
+
     async fn remove_dir(&self, path: &OsStr) -> io::Result<()> {
         let sftp = self.sftp_mutex.lock().await;
         sftp.fs().remove_dir(path).await.map_err(io::Error::other)
@@ -187,6 +603,7 @@ impl LinuxFilesystem for OpensshLinux {
             "rm",
             vec![
                 "-r",
+                "--",
                 path.to_str().ok_or(io::Error::other("could not convert path to str"))?,
             ],
         )
@@ -195,20 +612,134 @@ impl LinuxFilesystem for OpensshLinux {
 
     async fn get_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
         let sftp = self.sftp_mutex.lock().await;
-        sftp.fs()
+        let mut metadata = sftp
+            .fs()
             .metadata(path)
             .await
             .map_err(io::Error::other)
-            .map(|metadata| metadata.into())
+            .map(|metadata| metadata.into())?;
+        drop(sftp);
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
     }
 
     async fn get_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
         let sftp = self.sftp_mutex.lock().await;
-        sftp.fs()
+        let mut metadata = sftp
+            .fs()
             .symlink_metadata(path)
             .await
             .map_err(io::Error::other)
-            .map(|metadata| metadata.into())
+            .map(|metadata| metadata.into())?;
+        drop(sftp);
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
+    }
+
+    async fn get_extended_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        let mut metadata = self.get_metadata(path).await?;
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        let stdout = run_stat_command(self, &[str_path], true).await?;
+        stat_ext::apply_stat_output(iter::once(&mut metadata), &stdout)?;
+        Ok(metadata)
+    }
+
+    async fn get_extended_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        let mut metadata = self.get_symlink_metadata(path).await?;
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        let stdout = run_stat_command(self, &[str_path], false).await?;
+        stat_ext::apply_stat_output(iter::once(&mut metadata), &stdout)?;
+        Ok(metadata)
+    }
+
+    async fn fs_stats(&self, path: &OsStr) -> io::Result<LinuxFsStats> {
+        let str_path = path
+            .to_str()
+            .ok_or(io::Error::other("path couldn't be converted to str"))?;
+        if let Some(fs_stats) = statvfs::statvfs(self, str_path).await? {
+            return Ok(fs_stats);
+        }
+
+        // the server lacks the statvfs extension
+        let mut child = self.session.command("stat");
+        child.args(stat_ext::fs_stat_args(str_path));
+        let output = child.output().await.map_err(io::Error::other)?;
+        match output.status.success() {
+            true => stat_ext::parse_fs_stat_output(&output.stdout),
+            false => Err(io::Error::other("stat exited with a non-zero status code")),
+        }
+    }
+
+    async fn disk_usage(&self, path: &OsStr) -> io::Result<u64> {
+        let mut child = self.session.command("du");
+        child.args(stat_ext::du_args(
+            path.to_str()
+                .ok_or(io::Error::other("path couldn't be converted to str"))?,
+        ));
+        let output = child.output().await.map_err(io::Error::other)?;
+        match output.status.success() {
+            true => stat_ext::parse_du_output(&output.stdout),
+            false => Err(io::Error::other("du exited with a non-zero status code")),
+        }
+    }
+
+    async fn lookup_user(&self, user_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_user(self, user_id).await
+    }
+
+    async fn lookup_group(&self, group_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_group(self, group_id).await
+    }
+}
+
+// runs a coreutils-style *sum command, possibly through find, and parses its output
+async fn run_sum_command(
+    instance: &OpensshLinux,
+    program: &str,
+    args: Vec<&str>,
+) -> io::Result<Vec<(OsString, String)>> {
+    let mut child = instance.session.command(program);
+    child.args(args);
+    let output = child.output().await.map_err(io::Error::other)?;
+    match output.status.success() {
+        true => checksum::parse_sum_output(&output.stdout),
+        false => Err(io::Error::other("checksum command exited with a non-zero status code")),
+    }
+}
+
+// SFTP v3 attributes lack inodes, link counts and the like, so extended metadata is completed by running stat for
+// the paths, returning its stdout
+async fn run_stat_command(instance: &OpensshLinux, paths: &[&str], follow_symlinks: bool) -> io::Result<Vec<u8>> {
+    let mut child = instance.session.command("stat");
+    child.args(stat_ext::stat_args(paths.iter().copied(), follow_symlinks));
+    let output = child.output().await.map_err(io::Error::other)?;
+    match output.status.success() {
+        true => Ok(output.stdout),
+        false => Err(io::Error::other("stat exited with a non-zero status code")),
+    }
+}
+
+// runs a tool that hosts may lack, such as getfattr or setfacl, returning the stdout
+async fn run_attr_command(
+    instance: &OpensshLinux,
+    feature: &str,
+    program: &str,
+    args: Vec<&str>,
+) -> io::Result<Vec<u8>> {
+    let mut child = instance.session.command(program);
+    child.args(args);
+    let output = child.output().await.map_err(io::Error::other)?;
+    match output.status.success() {
+        true => Ok(output.stdout),
+        false => Err(xattr_ext::status_error(
+            feature,
+            program,
+            output.status.code().map(|code| code as u32),
+        )),
     }
 }
 
@@ -224,42 +755,109 @@ async fn run_fs_command(instance: &OpensshLinux, program: &str, args: Vec<&str>)
     }
 }
 
-impl Into<LinuxFileMetadata> for MetaData {
-    fn into(self) -> LinuxFileMetadata {
+// the flock command, which exits and releases the lock once its stdin is closed. The stdin is declared first so that
+// it's closed before the child is dropped
+struct OpensshLockHolder {
+    stdin: Option<ChildStdin>,
+    child: Child<Arc<Session>>,
+}
+
+#[async_trait]
+impl LinuxLockHolder for OpensshLockHolder {
+    async fn release(self: Box<Self>) -> io::Result<()> {
+        let OpensshLockHolder { stdin, child } = *self;
+        drop(stdin);
+        let status = child.wait().await.map_err(io::Error::other)?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(io::Error::other(format!(
+                "flock exited with status code {:?}",
+                status.code()
+            ))),
+        }
+    }
+}
+
+// writes are pipelined, so they're flushed before the file is inspected or changed through its handle
+#[async_trait]
+impl LinuxFile for Pin<Box<TokioCompatFile>> {
+    async fn metadata(&mut self) -> io::Result<LinuxFileMetadata> {
+        self.flush().await?;
+        self.as_mut()
+            .as_mut_file()
+            .metadata()
+            .await
+            .map(|metadata| metadata.into())
+            .map_err(io::Error::other)
+    }
+
+    async fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.flush().await?;
+        self.as_mut()
+            .as_mut_file()
+            .set_len(size)
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn sync_all(&mut self) -> io::Result<()> {
+        self.flush().await?;
+        match self.as_mut().as_mut_file().sync_all().await {
+            Ok(()) => Ok(()),
+            Err(openssh_sftp_client::Error::UnsupportedExtension(_)) => Err(filesystem::fsync_unsupported_error()),
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+
+    async fn close(mut self: Box<Self>) -> io::Result<()> {
+        // the pinned file can't hand its handle back to be closed, so after the flush that's left to dropping it
+        self.flush().await
+    }
+}
+
+impl From<MetaData> for LinuxFileMetadata {
+    fn from(value: MetaData) -> Self {
         LinuxFileMetadata {
-            file_type: self.file_type().map(|file_type| file_type.into()),
-            size: self.len(),
-            permissions: self.permissions().map(|perms| perms.into()),
-            modified_time: self.modified().map(|timestamp| timestamp.as_system_time()),
-            accessed_time: self.accessed().map(|timestamp| timestamp.as_system_time()),
+            file_type: value.file_type().map(|file_type| file_type.into()),
+            size: value.len(),
+            permissions: value.permissions().map(|perms| perms.into()),
+            modified_time: value.modified().map(|timestamp| timestamp.as_system_time()),
+            accessed_time: value.accessed().map(|timestamp| timestamp.as_system_time()),
             created_time: None,
-            user_id: self.uid(),
+            changed_time: None,
+            user_id: value.uid(),
             user_name: None,
-            group_id: self.gid(),
+            group_id: value.gid(),
             group_name: None,
+            inode: None,
+            device_id: None,
+            special_device_id: None,
+            hard_link_count: None,
+            block_size: None,
+            block_count: None,
         }
     }
 }
 
-impl Into<Permissions> for LinuxPermissions {
-    fn into(self) -> Permissions {
+impl From<LinuxPermissions> for Permissions {
+    fn from(value: LinuxPermissions) -> Self {
         let mut result = Permissions::new();
 
-        result.set_execute_by_owner(self.contains(LinuxPermissions::OWNER_EXECUTE));
-        result.set_execute_by_group(self.contains(LinuxPermissions::GROUP_EXECUTE));
-        result.set_execute_by_other(self.contains(LinuxPermissions::OTHER_EXECUTE));
+        result.set_execute_by_owner(value.contains(LinuxPermissions::OWNER_EXECUTE));
+        result.set_execute_by_group(value.contains(LinuxPermissions::GROUP_EXECUTE));
+        result.set_execute_by_other(value.contains(LinuxPermissions::OTHER_EXECUTE));
 
-        result.set_write_by_owner(self.contains(LinuxPermissions::OWNER_WRITE));
-        result.set_write_by_group(self.contains(LinuxPermissions::GROUP_WRITE));
-        result.set_write_by_other(self.contains(LinuxPermissions::OTHER_WRITE));
+        result.set_write_by_owner(value.contains(LinuxPermissions::OWNER_WRITE));
+        result.set_write_by_group(value.contains(LinuxPermissions::GROUP_WRITE));
+        result.set_write_by_other(value.contains(LinuxPermissions::OTHER_WRITE));
 
-        result.set_read_by_owner(self.contains(LinuxPermissions::OWNER_READ));
-        result.set_read_by_group(self.contains(LinuxPermissions::GROUP_READ));
-        result.set_read_by_other(self.contains(LinuxPermissions::OWNER_READ));
+        result.set_read_by_owner(value.contains(LinuxPermissions::OWNER_READ));
+        result.set_read_by_group(value.contains(LinuxPermissions::GROUP_READ));
+        result.set_read_by_other(value.contains(LinuxPermissions::OWNER_READ));
 
-        result.set_suid(self.contains(LinuxPermissions::SET_UID));
-        result.set_sgid(self.contains(LinuxPermissions::SET_GID));
-        result.set_vtx(self.contains(LinuxPermissions::STICKY_BIT));
+        result.set_suid(value.contains(LinuxPermissions::SET_UID));
+        result.set_sgid(value.contains(LinuxPermissions::SET_GID));
+        result.set_vtx(value.contains(LinuxPermissions::STICKY_BIT));
 
         result
     }
@@ -300,6 +898,18 @@ impl From<FileType> for LinuxFileType {
         if value.is_symlink() {
             return LinuxFileType::Symlink;
         }
-        return LinuxFileType::Other;
+        if value.is_fifo() {
+            return LinuxFileType::Fifo;
+        }
+        if value.is_socket() {
+            return LinuxFileType::Socket;
+        }
+        if value.is_block_device() {
+            return LinuxFileType::BlockDevice;
+        }
+        if value.is_char_device() {
+            return LinuxFileType::CharDevice;
+        }
+        LinuxFileType::Other
     }
 }
@@ -4,6 +4,8 @@ mod executor;
 mod filesystem;
 #[cfg(feature = "network")]
 mod network;
+#[cfg(feature = "filesystem")]
+mod statvfs;
 
 use std::sync::Arc;
 
@@ -11,10 +13,15 @@ use openssh::Session;
 use openssh_sftp_client::{Sftp, SftpOptions};
 use tokio::sync::Mutex;
 
+#[cfg(feature = "filesystem")]
+use crate::name_cache::NameCache;
+
 #[allow(unused)]
 pub struct OpensshLinux {
     session: Arc<Session>,
     sftp_mutex: Arc<Mutex<Sftp>>,
+    #[cfg(feature = "filesystem")]
+    name_cache: Arc<NameCache>,
 }
 
 #[derive(Debug)]
@@ -29,6 +36,8 @@ impl OpensshLinux {
         Ok(OpensshLinux {
             session: Arc::new(session),
             sftp_mutex: Arc::new(Mutex::new(sftp)),
+            #[cfg(feature = "filesystem")]
+            name_cache: Arc::new(NameCache::new()),
         })
     }
 
@@ -45,6 +54,8 @@ impl OpensshLinux {
         Ok(OpensshLinux {
             session: session_arc,
             sftp_mutex: Arc::new(Mutex::new(sftp)),
+            #[cfg(feature = "filesystem")]
+            name_cache: Arc::new(NameCache::new()),
         })
     }
 }
@@ -0,0 +1,182 @@
+use std::io;
+
+use openssh::Stdio;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::filesystem::{LinuxFsStats, LinuxMountFlags};
+
+use super::OpensshLinux;
+
+const SSH_FXP_INIT: u8 = 1;
+const SSH_FXP_VERSION: u8 = 2;
+const SSH_FXP_STATUS: u8 = 101;
+const SSH_FXP_EXTENDED: u8 = 200;
+const SSH_FXP_EXTENDED_REPLY: u8 = 201;
+
+const STATVFS_EXTENSION: &str = "statvfs@openssh.com";
+const STATVFS_REQUEST_ID: u32 = 1;
+
+// larger replies than this can't be statvfs replies, and are rejected instead of being buffered
+const MAX_PACKET_SIZE: u32 = 256 * 1024;
+
+// openssh-sftp-client knows whether the server supports the statvfs@openssh.com extension, but has no request for it,
+// so it's sent over a short-lived SFTP session of its own. Returns None when the server lacks the extension
+pub(super) async fn statvfs(instance: &OpensshLinux, path: &str) -> io::Result<Option<LinuxFsStats>> {
+    let mut child = instance
+        .session
+        .subsystem("sftp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .await
+        .map_err(io::Error::other)?;
+    let mut stdin = child
+        .stdin()
+        .take()
+        .ok_or(io::Error::other("sftp stdin wasn't piped"))?;
+    let mut stdout = child
+        .stdout()
+        .take()
+        .ok_or(io::Error::other("sftp stdout wasn't piped"))?;
+
+    let result = request_statvfs(&mut stdin, &mut stdout, path).await;
+    // closing stdin ends the SFTP server, which leaves the channel to close as well
+    drop(stdin);
+    drop(stdout);
+    let _ = child.wait().await;
+    result
+}
+
+async fn request_statvfs<W, R>(stdin: &mut W, stdout: &mut R, path: &str) -> io::Result<Option<LinuxFsStats>>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    write_packet(stdin, SSH_FXP_INIT, &3u32.to_be_bytes()).await?;
+    let (packet_type, payload) = read_packet(stdout).await?;
+    if packet_type != SSH_FXP_VERSION {
+        return Err(unexpected_packet(packet_type));
+    }
+    // the version is followed by pairs of extension names and data
+    let mut reader = PayloadReader(&payload);
+    reader.read_u32()?;
+    let mut has_extension = false;
+    while !reader.0.is_empty() {
+        has_extension |= reader.read_string()? == STATVFS_EXTENSION.as_bytes();
+        reader.read_string()?;
+    }
+    if !has_extension {
+        return Ok(None);
+    }
+
+    let mut request = STATVFS_REQUEST_ID.to_be_bytes().to_vec();
+    push_string(&mut request, STATVFS_EXTENSION.as_bytes());
+    push_string(&mut request, path.as_bytes());
+    write_packet(stdin, SSH_FXP_EXTENDED, &request).await?;
+
+    let (packet_type, payload) = read_packet(stdout).await?;
+    let mut reader = PayloadReader(&payload);
+    if reader.read_u32()? != STATVFS_REQUEST_ID {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected SFTP request id"));
+    }
+    match packet_type {
+        SSH_FXP_EXTENDED_REPLY => {
+            let mut fields = [0u64; 11];
+            for field in fields.iter_mut() {
+                *field = reader.read_u64()?;
+            }
+            let [block_size, fragment_size, total_blocks, free_blocks, available_blocks, total_inodes, free_inodes, available_inodes, _fs_id, flags, _max_name_length] =
+                fields;
+            Ok(Some(LinuxFsStats {
+                block_size,
+                fragment_size,
+                total_blocks,
+                free_blocks,
+                available_blocks,
+                total_inodes,
+                free_inodes,
+                available_inodes,
+                mount_flags: Some(LinuxMountFlags::from_bits_truncate(flags)),
+            }))
+        }
+        SSH_FXP_STATUS => {
+            reader.read_u32()?;
+            let message = reader.read_string()?;
+            Err(io::Error::other(format!(
+                "statvfs failed: {}",
+                String::from_utf8_lossy(message)
+            )))
+        }
+        packet_type => Err(unexpected_packet(packet_type)),
+    }
+}
+
+async fn write_packet<W>(stdin: &mut W, packet_type: u8, payload: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut packet = Vec::with_capacity(5 + payload.len());
+    packet.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+    packet.push(packet_type);
+    packet.extend_from_slice(payload);
+    stdin.write_all(&packet).await?;
+    stdin.flush().await
+}
+
+async fn read_packet<R>(stdout: &mut R) -> io::Result<(u8, Vec<u8>)>
+where
+    R: AsyncRead + Unpin,
+{
+    let length = stdout.read_u32().await?;
+    if length == 0 || length > MAX_PACKET_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid SFTP packet length {}", length),
+        ));
+    }
+    let packet_type = stdout.read_u8().await?;
+    let mut payload = vec![0u8; length as usize - 1];
+    stdout.read_exact(&mut payload).await?;
+    Ok((packet_type, payload))
+}
+
+fn push_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+fn unexpected_packet(packet_type: u8) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected SFTP packet type {}", packet_type),
+    )
+}
+
+struct PayloadReader<'a>(&'a [u8]);
+
+impl<'a> PayloadReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated SFTP packet"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_string(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.read_bytes(len)
+    }
+}
@@ -5,10 +5,12 @@ use std::sync::{
 
 use russh::client;
 use russh_keys::key::KeyPair;
-use russh_sftp::client::SftpSession;
+use russh_sftp::client::{RawSftpSession, SftpSession};
 use tokio::sync::Mutex;
 
 use crate::impl_russh::RusshLinux;
+#[cfg(feature = "filesystem")]
+use crate::name_cache::NameCache;
 
 use super::RusshPtyOptions;
 
@@ -96,17 +98,36 @@ where
             .await
             .map_err(|err| RusshConnectionError::SftpOpenError(err))?;
 
-        let fs_ssh_channel = handle
+        // a raw session gives access to lower-level requests, such as paginated directory reads and extensions.
+        // SftpSession keeps its own raw session private and can't be built on top of an existing one, so the raw
+        // session needs a channel of its own
+        let raw_sftp_channel = handle
             .channel_open_session()
             .await
-            .map_err(|err| RusshConnectionError::SftpChannelOpenError(err))?;
+            .map_err(|err| RusshConnectionError::SshChannelOpenError(err))?;
+
+        raw_sftp_channel
+            .request_subsystem(true, "sftp")
+            .await
+            .map_err(|err| RusshConnectionError::SftpRequestError(err))?;
+
+        let raw_sftp_session = RawSftpSession::new(raw_sftp_channel.into_stream());
+        let raw_sftp_version = raw_sftp_session
+            .init()
+            .await
+            .map_err(|err| RusshConnectionError::SftpOpenError(err))?;
 
         Ok(RusshLinux {
             id: instance_id,
             pty_options,
             handle_mutex: Arc::new(Mutex::new(handle)),
-            fs_channel_mutex: Arc::new(Mutex::new(fs_ssh_channel)),
             sftp_session: Arc::new(sftp_session),
+            raw_sftp_session: Arc::new(raw_sftp_session),
+            check_file_supported: raw_sftp_version.extensions.contains_key("check-file"),
+            posix_rename_supported: raw_sftp_version.extensions.contains_key("posix-rename@openssh.com"),
+            fsync_supported: raw_sftp_version.extensions.contains_key("fsync@openssh.com"),
+            #[cfg(feature = "filesystem")]
+            name_cache: Arc::new(NameCache::new()),
         })
     }
 }
@@ -1,4 +1,4 @@
-use std::{ffi::OsString, pin::Pin, sync::Arc};
+use std::{collections::HashMap, ffi::OsString, pin::Pin, sync::Arc};
 
 use async_trait::async_trait;
 use bytes::BytesMut;
@@ -9,18 +9,20 @@ use russh::{
     client::{self, Msg},
     Channel, ChannelId, ChannelMsg,
 };
+use russh_sftp::client::SftpSession;
 use tokio::{
     io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
     sync::Mutex,
 };
 
 use crate::{
-    derive_ext::DeriveExt,
+    derive_ext::{DeriveExt, DerivedShellCommand},
     executor::{
         FinishedLinuxProcessOutput, LinuxExecutor, LinuxProcess, LinuxProcessConfiguration, LinuxProcessError,
         LinuxProcessOutput,
     },
     filesystem::{LinuxFilesystem, LinuxOpenOptions},
+    script_ext::ScriptExt,
 };
 
 use super::RusshLinux;
@@ -47,6 +49,8 @@ struct RusshLinuxProcess {
     pub(super) channel_mutex: Arc<Mutex<Channel<Msg>>>,
     pub(super) stdin: Option<Pin<Box<dyn AsyncWrite + Send>>>,
     pub(super) pid_option: Option<u32>,
+    pub(super) sftp_session: Arc<SftpSession>,
+    pub(super) fd_files: Vec<(u32, String)>,
 }
 
 #[async_trait]
@@ -79,6 +83,7 @@ impl LinuxProcess for RusshLinuxProcess {
     async fn await_exit(self: Box<Self>) -> Result<Option<i64>, LinuxProcessError> {
         let mut channel = self.channel_mutex.lock().await;
         let status = await_process_exit(&mut channel).await;
+        collect_fd_files(&self.sftp_session, &self.fd_files).await;
         Ok(status)
     }
 
@@ -86,7 +91,10 @@ impl LinuxProcess for RusshLinuxProcess {
         let mut channel = self.channel_mutex.lock().await;
         let status_code = await_process_exit(&mut channel).await;
         drop(channel);
-        let output = self.get_current_output()?;
+        let mut output = self.get_current_output()?;
+        output
+            .stdout_extended
+            .extend(collect_fd_files(&self.sftp_session, &self.fd_files).await);
         Ok(FinishedLinuxProcessOutput::join(output, status_code))
     }
 }
@@ -118,13 +126,26 @@ where
         let process = begin_execute_internal(&self, process_configuration).await?;
         let mut channel = process.channel_mutex.lock().await;
         let status_code = await_process_exit(&mut channel).await;
-        let output = fetch_process_output(&InternalId {
+        let mut output = fetch_process_output(&InternalId {
             channel_id: channel.id(),
             instance_id: self.id,
         });
+        output
+            .stdout_extended
+            .extend(collect_fd_files(&process.sftp_session, &process.fd_files).await);
         Ok(FinishedLinuxProcessOutput::join(output, status_code))
     }
 
+    async fn execute_script(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
+        self.execute_script_via_filesystem(interpreter, script_body, process_configuration)
+            .await
+    }
+
     async fn send_signal(&self, signal: Signal, process_id: u32) -> Result<(), LinuxProcessError> {
         let mut process_configuration = LinuxProcessConfiguration::new("kill");
         process_configuration
@@ -149,11 +170,11 @@ async fn begin_execute_internal<H: client::Handler>(
         .map_err(|err| LinuxProcessError::Other(Box::new(err)))?;
     drop(handle); // do not hold handle for longer than necessary
 
-    let pid_file = apply_process_configuration(&mut channel, process_configuration)
+    let shell_command = apply_process_configuration(&mut channel, process_configuration)
         .await
         .map_err(|err| LinuxProcessError::Other(Box::new(err)))?;
 
-    if process_configuration.redirect_stdout {
+    if process_configuration.stdout_target.is_piped() {
         STDOUT_BUFFERS.insert(
             InternalId {
                 channel_id: channel.id(),
@@ -162,7 +183,7 @@ async fn begin_execute_internal<H: client::Handler>(
             BytesMut::new(),
         );
     }
-    if process_configuration.redirect_stderr {
+    if process_configuration.stderr_target.is_piped() {
         STDERR_BUFFERS.insert(
             InternalId {
                 channel_id: channel.id(),
@@ -174,11 +195,11 @@ async fn begin_execute_internal<H: client::Handler>(
 
     #[allow(unused)]
     let mut pid_option: Option<u32> = None;
-    let pid_file_os_str = OsString::from(pid_file);
+    let pid_file_os_str = OsString::from(shell_command.pid_file);
     let pid_file_os_str = pid_file_os_str.as_os_str();
     loop {
         if let Ok(mut reader) = instance
-            .open_file(pid_file_os_str, &LinuxOpenOptions::new().read())
+            .open_file(pid_file_os_str, LinuxOpenOptions::new().read())
             .await
         {
             let mut content = String::new();
@@ -220,6 +241,8 @@ async fn begin_execute_internal<H: client::Handler>(
         channel_mutex: Arc::new(Mutex::new(channel)),
         stdin: stdin_option,
         pid_option,
+        sftp_session: instance.sftp_session.clone(),
+        fd_files: shell_command.fd_files,
     })
 }
 
@@ -268,9 +291,23 @@ fn fetch_process_output(internal_id: &InternalId) -> LinuxProcessOutput {
 async fn apply_process_configuration(
     channel: &mut Channel<Msg>,
     process_configuration: &LinuxProcessConfiguration,
-) -> Result<String, russh::Error> {
-    let (command, pid_file) = process_configuration.derive_shell_command();
-    channel.exec(true, command).await?;
+) -> Result<DerivedShellCommand, russh::Error> {
+    let shell_command = process_configuration.derive_shell_command();
+    channel.exec(true, shell_command.command.as_str()).await?;
+
+    Ok(shell_command)
+}
+
+// reads the files captured extra fds were redirected into and removes them, skipping ones that were never created
+async fn collect_fd_files(sftp_session: &SftpSession, fd_files: &[(u32, String)]) -> HashMap<u32, Vec<u8>> {
+    let mut fd_outputs = HashMap::new();
+
+    for (fd, fd_file) in fd_files {
+        if let Ok(content) = sftp_session.read(fd_file).await {
+            fd_outputs.insert(*fd, content);
+            let _ = sftp_session.remove_file(fd_file).await;
+        }
+    }
 
-    Ok(pid_file)
+    fd_outputs
 }
@@ -1,18 +1,39 @@
 use std::{
+    collections::{BTreeMap, VecDeque},
     ffi::{OsStr, OsString},
+    iter,
     path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
-use russh::{client, ChannelMsg};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use russh::{client, Channel, ChannelMsg};
 use russh_sftp::{
-    client::fs::{File, Metadata},
-    protocol::{FileAttributes, FileType, OpenFlags},
+    client::{
+        error::Error as SftpError,
+        fs::{File, Metadata},
+        RawSftpSession,
+    },
+    protocol::{self, FileAttributes, FileType, OpenFlags, Packet, StatusCode},
 };
-use std::io::{self};
+use shell_escape::unix::escape;
+use std::{
+    io::{self},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt, ReadBuf};
 
-use crate::filesystem::{
-    LinuxDirEntry, LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions,
+use crate::{
+    acl_ext, checksum,
+    filesystem::{
+        self, LinuxAcl, LinuxChecksumAlgorithm, LinuxDirEntry, LinuxFile, LinuxFileLock, LinuxFileMetadata,
+        LinuxFileType, LinuxFilesystem, LinuxFsStats, LinuxLockHolder, LinuxLockMode, LinuxMountFlags,
+        LinuxOpenOptions, LinuxPermissions,
+    },
+    lock_ext, stat_ext, xattr_ext,
 };
 
 use super::RusshLinux;
@@ -29,7 +50,7 @@ where
             .map_err(io::Error::other)
     }
 
-    async fn open_file(&self, path: &OsStr, open_options: &LinuxOpenOptions) -> io::Result<File> {
+    async fn open_file(&self, path: &OsStr, open_options: &LinuxOpenOptions) -> io::Result<RusshFile> {
         let mut flags = OpenFlags::empty();
         if open_options.is_read() {
             flags.insert(OpenFlags::READ);
@@ -46,11 +67,43 @@ where
         if open_options.is_create() {
             flags.insert(OpenFlags::CREATE);
         }
+        if open_options.is_create_new() {
+            flags.insert(OpenFlags::CREATE | OpenFlags::EXCLUDE);
+        }
 
-        self.sftp_session
+        let mut file_type = None;
+        if open_options.needs_sftp_file_type() {
+            let attrs = match open_options.is_no_follow() {
+                true => self.sftp_session.symlink_metadata(conv_path(path)).await,
+                false => self.sftp_session.metadata(conv_path(path)).await,
+            };
+            file_type = attrs.ok().map(|attrs| LinuxFileType::from(&attrs));
+        }
+        open_options.check_sftp_file_type(file_type)?;
+
+        let file = self
+            .sftp_session
             .open_with_flags(conv_path(path), flags)
             .await
-            .map_err(io::Error::other)
+            .map_err(io::Error::other)?;
+        if let Some(mode) = open_options.sftp_mode_to_apply(file_type) {
+            file.set_metadata(Metadata {
+                size: None,
+                uid: None,
+                user: None,
+                gid: None,
+                group: None,
+                permissions: Some(mode.bits()),
+                atime: None,
+                mtime: None,
+            })
+            .await
+            .map_err(io::Error::other)?;
+        }
+        Ok(RusshFile {
+            file,
+            fsync_supported: self.fsync_supported,
+        })
     }
 
     async fn create_file(&self, path: &OsStr) -> io::Result<()> {
@@ -64,6 +117,10 @@ where
     }
 
     async fn rename_file(&self, old_path: &OsStr, new_path: &OsStr) -> io::Result<()> {
+        if self.posix_rename_supported {
+            return posix_rename(&self.raw_sftp_session, old_path, new_path).await;
+        }
+
         self.sftp_session
             .rename(conv_path(old_path), conv_path(new_path))
             .await
@@ -71,9 +128,21 @@ where
     }
 
     async fn copy_file(&self, old_path: &OsStr, new_path: &OsStr) -> io::Result<Option<u64>> {
-        run_fs_command(self, format!("cp {} {}", conv_path(old_path), conv_path(new_path)))
+        let command = format!(
+            "cp -- {} {}",
+            escape(conv_path(old_path).into()),
+            escape(conv_path(new_path).into())
+        );
+        match run_fs_command(self, command).await? {
+            Some(0) => {}
+            _ => return Err(io::Error::other("cp exited with a non-zero status code")),
+        }
+
+        self.sftp_session
+            .metadata(conv_path(new_path))
             .await
-            .map(|_| None)
+            .map(|attrs| attrs.size)
+            .map_err(io::Error::other)
     }
 
     async fn canonicalize(&self, path: &OsStr) -> io::Result<OsString> {
@@ -103,6 +172,40 @@ where
         }
     }
 
+    async fn create_fifo(&self, path: &OsStr, permissions: LinuxPermissions) -> io::Result<()> {
+        let command = format!(
+            "mkfifo -m {:o} -- {}",
+            permissions.bits() & 0o7777,
+            escape(conv_path(path).into())
+        );
+        match run_fs_command(self, command).await? {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other("mkfifo exited with a non-zero status code")),
+        }
+    }
+
+    async fn create_device_node(
+        &self,
+        path: &OsStr,
+        file_type: LinuxFileType,
+        major: u32,
+        minor: u32,
+        permissions: LinuxPermissions,
+    ) -> io::Result<()> {
+        let command = format!(
+            "mknod -m {:o} -- {} {} {} {}",
+            permissions.bits() & 0o7777,
+            escape(conv_path(path).into()),
+            filesystem::mknod_type_arg(file_type)?,
+            major,
+            minor
+        );
+        match run_fs_command(self, command).await? {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other("mknod exited with a non-zero status code")),
+        }
+    }
+
     async fn read_link(&self, link_path: &OsStr) -> io::Result<OsString> {
         self.sftp_session
             .read_link(conv_path(link_path))
@@ -130,6 +233,219 @@ where
             .map_err(io::Error::other)
     }
 
+    async fn set_times(&self, path: &OsStr, accessed_time: SystemTime, modified_time: SystemTime) -> io::Result<()> {
+        self.sftp_session
+            .set_metadata(
+                conv_path(path),
+                Metadata {
+                    size: None,
+                    uid: None,
+                    user: None,
+                    gid: None,
+                    group: None,
+                    permissions: None,
+                    atime: Some(conv_timestamp(accessed_time)?),
+                    mtime: Some(conv_timestamp(modified_time)?),
+                },
+            )
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn set_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        // SFTP sets the user and group together, so a missing one is filled in with its current value
+        let (user_id, group_id) = match (user_id, group_id) {
+            (Some(user_id), Some(group_id)) => (user_id, group_id),
+            (None, None) => return Ok(()),
+            _ => {
+                let current = self
+                    .sftp_session
+                    .metadata(conv_path(path))
+                    .await
+                    .map_err(io::Error::other)?;
+                (
+                    user_id.or(current.uid).ok_or(io::Error::other("file has no user id"))?,
+                    group_id
+                        .or(current.gid)
+                        .ok_or(io::Error::other("file has no group id"))?,
+                )
+            }
+        };
+
+        self.sftp_session
+            .set_metadata(
+                conv_path(path),
+                Metadata {
+                    size: None,
+                    uid: Some(user_id),
+                    user: None,
+                    gid: Some(group_id),
+                    group: None,
+                    permissions: None,
+                    atime: None,
+                    mtime: None,
+                },
+            )
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn set_symlink_owner(&self, path: &OsStr, user_id: Option<u32>, group_id: Option<u32>) -> io::Result<()> {
+        // SFTP v3 setstat always follows symlinks
+        let Some(owner) = filesystem::chown_owner_arg(user_id, group_id) else {
+            return Ok(());
+        };
+        let command = format!("chown -h {} -- {}", owner, escape(conv_path(path).into()));
+        match run_fs_command(self, command).await? {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other("chown exited with a non-zero status code")),
+        }
+    }
+
+    async fn set_len(&self, path: &OsStr, size: u64) -> io::Result<()> {
+        self.sftp_session
+            .set_metadata(
+                conv_path(path),
+                Metadata {
+                    size: Some(size),
+                    uid: None,
+                    user: None,
+                    gid: None,
+                    group: None,
+                    permissions: None,
+                    atime: None,
+                    mtime: None,
+                },
+            )
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn allocate(&self, path: &OsStr, offset: u64, len: u64) -> io::Result<()> {
+        let command = format!(
+            "fallocate -o {} -l {} -- {}",
+            offset,
+            len,
+            escape(conv_path(path).into())
+        );
+        match run_fs_command(self, command).await? {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other("fallocate exited with a non-zero status code")),
+        }
+    }
+
+    async fn sync_file(&self, path: &OsStr) -> io::Result<()> {
+        if !self.fsync_supported {
+            return Err(filesystem::fsync_unsupported_error());
+        }
+        let mut file = self
+            .sftp_session
+            .open_with_flags(conv_path(path), OpenFlags::READ)
+            .await
+            .map_err(io::Error::other)?;
+        file.sync_all().await.map_err(io::Error::other)?;
+        file.shutdown().await
+    }
+
+    async fn get_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<Option<Vec<u8>>> {
+        let str_path = conv_path(path);
+        let output = run_attr_command(self, "extended attributes", "getfattr", xattr_ext::dump_args(&str_path)).await?;
+        Ok(xattr_ext::parse_dump(&output)?
+            .into_iter()
+            .find(|(xattr_name, _)| xattr_name == name)
+            .map(|(_, value)| value))
+    }
+
+    async fn set_xattr(&self, path: &OsStr, name: &OsStr, value: &[u8]) -> io::Result<()> {
+        let args = xattr_ext::set_args(&conv_path(path), &conv_path(name), value);
+        run_attr_command(
+            self,
+            "extended attributes",
+            "setfattr",
+            args.iter().map(|arg| arg.as_str()).collect(),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn list_xattrs(&self, path: &OsStr) -> io::Result<Vec<OsString>> {
+        let str_path = conv_path(path);
+        let output = run_attr_command(self, "extended attributes", "getfattr", xattr_ext::dump_args(&str_path)).await?;
+        Ok(xattr_ext::parse_dump(&output)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    async fn remove_xattr(&self, path: &OsStr, name: &OsStr) -> io::Result<()> {
+        let (str_path, str_name) = (conv_path(path), conv_path(name));
+        run_attr_command(
+            self,
+            "extended attributes",
+            "setfattr",
+            xattr_ext::remove_args(&str_path, &str_name),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn get_acl(&self, path: &OsStr) -> io::Result<LinuxAcl> {
+        let str_path = conv_path(path);
+        let output = run_attr_command(self, "ACLs", "getfacl", acl_ext::getfacl_args(&str_path)).await?;
+        acl_ext::parse_getfacl(&output)
+    }
+
+    async fn set_acl(&self, path: &OsStr, acl: &LinuxAcl) -> io::Result<()> {
+        let str_path = conv_path(path);
+        if acl.default.is_empty() {
+            run_attr_command(self, "ACLs", "setfacl", acl_ext::remove_default_args(&str_path)).await?;
+        }
+        let args = acl_ext::setfacl_args(&str_path, acl);
+        run_attr_command(self, "ACLs", "setfacl", args.iter().map(|arg| arg.as_str()).collect())
+            .await
+            .map(|_| ())
+    }
+
+    async fn lock_file(
+        &self,
+        path: &OsStr,
+        mode: LinuxLockMode,
+        timeout: Option<Duration>,
+    ) -> io::Result<LinuxFileLock> {
+        let str_path = conv_path(path);
+        let args = lock_ext::flock_args(&str_path, mode, timeout);
+        let command = args
+            .iter()
+            .map(|arg| escape(arg.as_str().into()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // the channel stays open for as long as the lock is held, so it can't go through run_fs_command
+        let handle = self.handle_mutex.lock().await;
+        let mut channel = handle.channel_open_session().await.map_err(io::Error::other)?;
+        drop(handle);
+        channel
+            .exec(true, format!("flock {}", command))
+            .await
+            .map_err(io::Error::other)?;
+
+        let mut code = None;
+        let mut stdout = Vec::new();
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { data } => {
+                    stdout.extend_from_slice(&data);
+                    if lock_ext::is_locked(&stdout) {
+                        return Ok(LinuxFileLock::new(RusshLockHolder { channel: Some(channel) }));
+                    }
+                }
+                ChannelMsg::ExitStatus { exit_status } => code = Some(exit_status),
+                _ => {}
+            }
+        }
+        Err(lock_ext::status_error(&str_path, code))
+    }
+
     async fn remove_file(&self, path: &OsStr) -> io::Result<()> {
         self.sftp_session
             .remove_file(conv_path(path))
@@ -190,7 +506,7 @@ where
         let entries = read_dir
             .map(|dir_entry| LinuxDirEntry {
                 name: dir_entry.file_name().into(),
-                file_type: dir_entry.file_type().into(),
+                file_type: LinuxFileType::from(&dir_entry.metadata()),
                 path: PathBuf::from(path).join(Path::new(&dir_entry.file_name())).into(),
             })
             .collect::<Vec<_>>();
@@ -198,6 +514,109 @@ where
         Ok(entries)
     }
 
+    async fn list_dir_with_metadata(&self, path: &OsStr) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        // readdir replies already carry the attributes of every entry, so no further stat requests are needed
+        let read_dir = self
+            .sftp_session
+            .read_dir(conv_path(path))
+            .await
+            .map_err(io::Error::other)?;
+        let mut entries = read_dir
+            .map(|dir_entry| {
+                (
+                    LinuxDirEntry {
+                        name: dir_entry.file_name().into(),
+                        file_type: LinuxFileType::from(&dir_entry.metadata()),
+                        path: PathBuf::from(path).join(Path::new(&dir_entry.file_name())).into(),
+                    },
+                    dir_entry.metadata().into(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (_, metadata) in entries.iter_mut() {
+            self.name_cache.apply_names(self, metadata).await;
+        }
+        Ok(entries)
+    }
+
+    async fn list_dir_with_extended_metadata(
+        &self,
+        path: &OsStr,
+    ) -> io::Result<Vec<(LinuxDirEntry, LinuxFileMetadata)>> {
+        let mut entries = self.list_dir_with_metadata(path).await?;
+        for batch in entries.chunks_mut(stat_ext::STAT_BATCH_SIZE) {
+            let paths = batch
+                .iter()
+                .map(|(dir_entry, _)| conv_path(&dir_entry.path))
+                .collect::<Vec<_>>();
+            let stdout = run_stat_command(self, &paths, false).await?;
+            stat_ext::apply_stat_output(batch.iter_mut().map(|(_, metadata)| metadata), &stdout)?;
+        }
+        Ok(entries)
+    }
+
+    async fn read_dir_stream(&self, path: &OsStr) -> io::Result<BoxStream<'static, io::Result<LinuxDirEntry>>> {
+        let handle = self
+            .raw_sftp_session
+            .opendir(conv_path(path))
+            .await
+            .map_err(io::Error::other)?
+            .handle;
+        let state = RawReadDirState {
+            raw_sftp_session: self.raw_sftp_session.clone(),
+            handle: Some(handle),
+            path: PathBuf::from(path),
+            batch: VecDeque::new(),
+        };
+
+        Ok(stream::try_unfold(state, |mut state| async move {
+            match state.next_file().await? {
+                Some(file) => {
+                    let dir_entry = LinuxDirEntry {
+                        path: state.path.join(&file.filename).into_os_string(),
+                        name: file.filename.into(),
+                        file_type: LinuxFileType::from(&file.attrs),
+                    };
+                    Ok(Some((dir_entry, state)))
+                }
+                None => Ok(None),
+            }
+        })
+        .boxed())
+    }
+
+    async fn checksum(&self, path: &OsStr, algorithm: LinuxChecksumAlgorithm) -> io::Result<String> {
+        if let (true, Some(algorithm_name)) = (self.check_file_supported, algorithm.check_file_name()) {
+            return check_file(&self.raw_sftp_session, path, algorithm_name).await;
+        }
+
+        let command = format!("{} -- {}", algorithm.sum_command(), escape(conv_path(path).into()));
+        run_sum_command(self, command)
+            .await?
+            .into_iter()
+            .next()
+            .map(|(_, checksum)| checksum)
+            .ok_or_else(|| io::Error::other("checksum command didn't output anything"))
+    }
+
+    async fn checksum_tree(
+        &self,
+        path: &OsStr,
+        algorithm: LinuxChecksumAlgorithm,
+    ) -> io::Result<BTreeMap<OsString, String>> {
+        // a single find invocation instead of a round trip per file
+        let command = format!(
+            "find {} -type f -exec {} -- {{}} +",
+            escape(conv_path(path).into()),
+            algorithm.sum_command()
+        );
+        Ok(checksum::relativize_checksums(
+            path,
+            run_sum_command(self, command).await?,
+        ))
+    }
+
     async fn remove_dir(&self, path: &OsStr) -> io::Result<()> {
         self.sftp_session
             .remove_dir(conv_path(path))
@@ -206,33 +625,156 @@ where
     }
 
     async fn remove_dir_recursively(&self, path: &OsStr) -> io::Result<()> {
-        let str_path = path
-            .to_str()
-            .ok_or(io::Error::other("could not convert &OsStr to str"))?;
-
-        run_fs_command(self, format!("rm -r {}", str_path)).await.map(|_| ())
+        match run_fs_command(self, format!("rm -r -- {}", escape(conv_path(path).into()))).await? {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other("rm exited with a non-zero status code")),
+        }
     }
 
     async fn get_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
-        self.sftp_session
+        let mut metadata = self
+            .sftp_session
             .metadata(conv_path(path))
             .await
             .map(|attrs| attrs.into())
-            .map_err(io::Error::other)
+            .map_err(io::Error::other)?;
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
     }
 
     async fn get_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
-        self.sftp_session
+        let mut metadata = self
+            .sftp_session
             .symlink_metadata(conv_path(path))
             .await
             .map(|attrs| attrs.into())
-            .map_err(io::Error::other)
+            .map_err(io::Error::other)?;
+        self.name_cache.apply_names(self, &mut metadata).await;
+        Ok(metadata)
+    }
+
+    async fn get_extended_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        let mut metadata = self.get_metadata(path).await?;
+        let stdout = run_stat_command(self, &[conv_path(path)], true).await?;
+        stat_ext::apply_stat_output(iter::once(&mut metadata), &stdout)?;
+        Ok(metadata)
+    }
+
+    async fn get_extended_symlink_metadata(&self, path: &OsStr) -> io::Result<LinuxFileMetadata> {
+        let mut metadata = self.get_symlink_metadata(path).await?;
+        let stdout = run_stat_command(self, &[conv_path(path)], false).await?;
+        stat_ext::apply_stat_output(iter::once(&mut metadata), &stdout)?;
+        Ok(metadata)
+    }
+
+    async fn fs_stats(&self, path: &OsStr) -> io::Result<LinuxFsStats> {
+        let statvfs = self
+            .sftp_session
+            .fs_info(conv_path(path))
+            .await
+            .map_err(io::Error::other)?;
+        if let Some(statvfs) = statvfs {
+            return Ok(LinuxFsStats {
+                block_size: statvfs.block_size,
+                fragment_size: statvfs.fragment_size,
+                total_blocks: statvfs.blocks,
+                free_blocks: statvfs.blocks_free,
+                available_blocks: statvfs.blocks_avail,
+                total_inodes: statvfs.inodes,
+                free_inodes: statvfs.inodes_free,
+                available_inodes: statvfs.inodes_avail,
+                mount_flags: Some(LinuxMountFlags::from_bits_truncate(statvfs.flags)),
+            });
+        }
+
+        // the server lacks the statvfs extension
+        let command = stat_ext::fs_stat_args(&conv_path(path))
+            .into_iter()
+            .map(|arg| escape(arg.into()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match run_fs_command_with_output(self, format!("stat {}", command)).await? {
+            (Some(0), stdout) => stat_ext::parse_fs_stat_output(&stdout),
+            _ => Err(io::Error::other("stat exited with a non-zero status code")),
+        }
+    }
+
+    async fn disk_usage(&self, path: &OsStr) -> io::Result<u64> {
+        let command = stat_ext::du_args(&conv_path(path))
+            .into_iter()
+            .map(|arg| escape(arg.into()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match run_fs_command_with_output(self, format!("du {}", command)).await? {
+            (Some(0), stdout) => stat_ext::parse_du_output(&stdout),
+            _ => Err(io::Error::other("du exited with a non-zero status code")),
+        }
+    }
+
+    async fn lookup_user(&self, user_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_user(self, user_id).await
+    }
+
+    async fn lookup_group(&self, group_id: u32) -> io::Result<Option<String>> {
+        self.name_cache.lookup_group(self, group_id).await
+    }
+}
+
+struct RawReadDirState {
+    raw_sftp_session: Arc<RawSftpSession>,
+    handle: Option<String>,
+    path: PathBuf,
+    batch: VecDeque<protocol::File>,
+}
+
+impl RawReadDirState {
+    // pops the next entry from the current batch, requesting a new batch from the server once it is drained
+    async fn next_file(&mut self) -> io::Result<Option<protocol::File>> {
+        loop {
+            if let Some(file) = self.batch.pop_front() {
+                if file.filename == "." || file.filename == ".." {
+                    continue;
+                }
+                return Ok(Some(file));
+            }
+
+            let Some(handle) = &self.handle else {
+                return Ok(None);
+            };
+
+            match self.raw_sftp_session.readdir(handle.as_str()).await {
+                Ok(name) => self.batch.extend(name.files),
+                Err(SftpError::Status(status)) if status.status_code == StatusCode::Eof => {
+                    if let Some(handle) = self.handle.take() {
+                        self.raw_sftp_session.close(handle).await.map_err(io::Error::other)?;
+                    }
+                }
+                Err(err) => return Err(io::Error::other(err)),
+            }
+        }
+    }
+}
+
+impl Drop for RawReadDirState {
+    fn drop(&mut self) {
+        // the handle stays open when the stream is dropped before reaching the end, so it's closed on the runtime
+        if let (Some(handle), Ok(runtime)) = (self.handle.take(), tokio::runtime::Handle::try_current()) {
+            let raw_sftp_session = self.raw_sftp_session.clone();
+            runtime.spawn(async move {
+                let _ = raw_sftp_session.close(handle).await;
+            });
+        }
     }
 }
 
-impl From<FileType> for LinuxFileType {
-    fn from(value: FileType) -> Self {
-        match value {
+// russh's FileType doesn't tell special files apart, so the type is taken from the mode bits whenever present
+impl From<&FileAttributes> for LinuxFileType {
+    fn from(value: &FileAttributes) -> Self {
+        if let Some(permissions) = value.permissions {
+            return LinuxFileType::from_mode(permissions);
+        }
+
+        match value.file_type() {
             FileType::Dir => LinuxFileType::Dir,
             FileType::File => LinuxFileType::File,
             FileType::Symlink => LinuxFileType::Symlink,
@@ -245,49 +787,287 @@ async fn run_fs_command<H>(instance: &RusshLinux<H>, command: String) -> io::Res
 where
     H: client::Handler,
 {
-    let mut chan = instance.fs_channel_mutex.lock().await;
+    run_fs_command_with_output(instance, command)
+        .await
+        .map(|(code, _)| code)
+}
+
+async fn run_fs_command_with_output<H>(instance: &RusshLinux<H>, command: String) -> io::Result<(Option<u32>, Vec<u8>)>
+where
+    H: client::Handler,
+{
+    // a session channel only accepts a single exec request, so every command needs its own channel
+    let handle = instance.handle_mutex.lock().await;
+    let mut chan = handle.channel_open_session().await.map_err(io::Error::other)?;
+    drop(handle);
+
     let exec_result = chan.exec(true, command).await;
     if let Err(err) = exec_result {
         return Err(io::Error::other(err));
     }
 
     let mut code = None;
+    let mut stdout = Vec::new();
 
     loop {
         let Some(msg) = chan.wait().await else {
             break;
         };
         match msg {
-            ChannelMsg::ExitStatus { exit_status } => {
-                code = Some(exit_status);
-            }
+            ChannelMsg::Data { data } => stdout.extend_from_slice(&data),
+            ChannelMsg::ExitStatus { exit_status } => code = Some(exit_status),
             _ => {}
         }
     }
 
-    Ok(code)
+    Ok((code, stdout))
+}
+
+// runs a coreutils-style *sum command, possibly through find, and parses its output
+async fn run_sum_command<H>(instance: &RusshLinux<H>, command: String) -> io::Result<Vec<(OsString, String)>>
+where
+    H: client::Handler,
+{
+    match run_fs_command_with_output(instance, command).await? {
+        (Some(0), stdout) => checksum::parse_sum_output(&stdout),
+        _ => Err(io::Error::other("checksum command exited with a non-zero status code")),
+    }
+}
+
+// SFTP v3 attributes lack inodes, link counts and the like, so extended metadata is completed by running stat for
+// the paths, returning its stdout
+async fn run_stat_command<H>(instance: &RusshLinux<H>, paths: &[String], follow_symlinks: bool) -> io::Result<Vec<u8>>
+where
+    H: client::Handler,
+{
+    let command = stat_ext::stat_args(paths.iter().map(String::as_str), follow_symlinks)
+        .into_iter()
+        .map(|arg| escape(arg.into()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    match run_fs_command_with_output(instance, format!("stat {}", command)).await? {
+        (Some(0), stdout) => Ok(stdout),
+        _ => Err(io::Error::other("stat exited with a non-zero status code")),
+    }
+}
+
+// runs a tool that hosts may lack, such as getfattr or setfacl, returning the stdout
+async fn run_attr_command<H>(
+    instance: &RusshLinux<H>,
+    feature: &str,
+    program: &str,
+    args: Vec<&str>,
+) -> io::Result<Vec<u8>>
+where
+    H: client::Handler,
+{
+    let args = args
+        .into_iter()
+        .map(|arg| escape(arg.into()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    match run_fs_command_with_output(instance, format!("{} {}", program, args)).await? {
+        (Some(0), stdout) => Ok(stdout),
+        (status_code, _) => Err(xattr_ext::status_error(feature, program, status_code)),
+    }
+}
+
+// renames with the posix-rename@openssh.com extension, which atomically replaces an existing new path
+async fn posix_rename(raw_sftp_session: &RawSftpSession, old_path: &OsStr, new_path: &OsStr) -> io::Result<()> {
+    let mut data = Vec::new();
+    for field in [conv_path(old_path), conv_path(new_path)] {
+        data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+
+    match raw_sftp_session.extended("posix-rename@openssh.com", data).await {
+        Ok(Packet::Status(status)) if status.status_code == StatusCode::Ok => Ok(()),
+        Ok(Packet::Status(status)) => Err(io::Error::other(SftpError::Status(status))),
+        Ok(_) => Err(io::Error::other(SftpError::UnexpectedPacket)),
+        Err(err) => Err(io::Error::other(err)),
+    }
+}
+
+// computes a checksum with the check-file-name request of the SFTP check-file extension
+async fn check_file(raw_sftp_session: &RawSftpSession, path: &OsStr, algorithm_name: &str) -> io::Result<String> {
+    let mut data = Vec::new();
+    for field in [conv_path(path).as_bytes(), algorithm_name.as_bytes()] {
+        data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        data.extend_from_slice(field);
+    }
+    // hash the whole file as a single block: a start offset, length and block size of 0
+    data.extend_from_slice(&[0; 8 + 8 + 4]);
+
+    let reply = match raw_sftp_session.extended("check-file-name", data).await {
+        Ok(Packet::ExtendedReply(reply)) => reply,
+        Ok(Packet::Status(status)) => return Err(io::Error::other(SftpError::Status(status))),
+        Ok(_) => return Err(io::Error::other(SftpError::UnexpectedPacket)),
+        Err(err) => return Err(io::Error::other(err)),
+    };
+
+    // the reply consists of the "check-file" string, the name of the used algorithm and the raw hash
+    let mut rest = reply.data.as_slice();
+    for _ in 0..2 {
+        let length = rest
+            .get(..4)
+            .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize)
+            .filter(|length| rest.len() >= 4 + length)
+            .ok_or_else(|| io::Error::other("malformed check-file reply"))?;
+        rest = &rest[4 + length..];
+    }
+
+    Ok(checksum::encode_hex(rest))
 }
 
 fn conv_path(path: &OsStr) -> String {
     String::from(path.to_str().unwrap())
 }
 
-impl Into<LinuxFileMetadata> for FileAttributes {
-    fn into(self) -> LinuxFileMetadata {
+// SFTP v3 timestamps are 32-bit seconds since the epoch
+fn conv_timestamp(time: SystemTime) -> io::Result<u32> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|duration| u32::try_from(duration.as_secs()).ok())
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "timestamp isn't representable in SFTP",
+        ))
+}
+
+// russh-sftp files skip syncs while reporting success when the server lacks the fsync extension, so they're wrapped
+// to know whether it's supported
+pub struct RusshFile {
+    file: File,
+    fsync_supported: bool,
+}
+
+impl AsyncRead for RusshFile {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RusshFile {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.file).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for RusshFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        Pin::new(&mut self.file).start_seek(position)
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.file).poll_complete(cx)
+    }
+}
+
+#[async_trait]
+impl LinuxFile for RusshFile {
+    async fn metadata(&mut self) -> io::Result<LinuxFileMetadata> {
+        self.file
+            .metadata()
+            .await
+            .map(|metadata| metadata.into())
+            .map_err(io::Error::other)
+    }
+
+    async fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.file
+            .set_metadata(Metadata {
+                size: Some(size),
+                uid: None,
+                user: None,
+                gid: None,
+                group: None,
+                permissions: None,
+                atime: None,
+                mtime: None,
+            })
+            .await
+            .map_err(io::Error::other)
+    }
+
+    async fn sync_all(&mut self) -> io::Result<()> {
+        // writes complete before returning, so there's nothing to flush to the server
+        if !self.fsync_supported {
+            return Err(filesystem::fsync_unsupported_error());
+        }
+        self.file.sync_all().await.map_err(io::Error::other)
+    }
+
+    async fn close(mut self: Box<Self>) -> io::Result<()> {
+        // writes complete before returning, so closing the handle is all that's left
+        self.file.shutdown().await
+    }
+}
+
+// the channel of the flock command, which exits and releases the lock once its stdin reaches EOF
+struct RusshLockHolder {
+    channel: Option<Channel<client::Msg>>,
+}
+
+#[async_trait]
+impl LinuxLockHolder for RusshLockHolder {
+    async fn release(mut self: Box<Self>) -> io::Result<()> {
+        let Some(mut channel) = self.channel.take() else {
+            return Ok(());
+        };
+        channel.eof().await.map_err(io::Error::other)?;
+
+        let mut code = None;
+        while let Some(msg) = channel.wait().await {
+            if let ChannelMsg::ExitStatus { exit_status } = msg {
+                code = Some(exit_status);
+            }
+        }
+        match code {
+            Some(0) => Ok(()),
+            _ => Err(io::Error::other(format!("flock exited with status code {:?}", code))),
+        }
+    }
+}
+
+impl Drop for RusshLockHolder {
+    fn drop(&mut self) {
+        // sending EOF is async, so it's left to the runtime. Without one, the lock lasts until the connection drops
+        if let (Some(channel), Ok(runtime)) = (self.channel.take(), tokio::runtime::Handle::try_current()) {
+            runtime.spawn(async move {
+                let _ = channel.eof().await;
+            });
+        }
+    }
+}
+
+impl From<FileAttributes> for LinuxFileMetadata {
+    fn from(value: FileAttributes) -> Self {
         LinuxFileMetadata {
-            file_type: Some(self.file_type().into()),
-            size: self.size,
-            permissions: match self.permissions {
-                Some(bit) => Some(LinuxPermissions::from_bits_retain(bit)),
-                None => None,
-            },
-            modified_time: self.modified().ok(),
-            accessed_time: self.accessed().ok(),
+            file_type: Some(LinuxFileType::from(&value)),
+            size: value.size,
+            permissions: value.permissions.map(LinuxPermissions::from_bits_retain),
+            modified_time: value.modified().ok(),
+            accessed_time: value.accessed().ok(),
             created_time: None,
-            user_id: self.uid,
-            user_name: self.user,
-            group_id: self.gid,
-            group_name: self.group,
+            changed_time: None,
+            user_id: value.uid,
+            user_name: value.user,
+            group_id: value.gid,
+            group_name: value.group,
+            inode: None,
+            device_id: None,
+            special_device_id: None,
+            hard_link_count: None,
+            block_size: None,
+            block_count: None,
         }
     }
 }
@@ -19,6 +19,9 @@ use russh::{
 use russh_keys::key::PublicKey;
 use tokio::sync::Mutex;
 
+#[cfg(feature = "filesystem")]
+use crate::name_cache::NameCache;
+
 #[allow(unused)]
 pub struct RusshLinux<H>
 where
@@ -27,8 +30,16 @@ where
     id: u16,
     pty_options: RusshPtyOptions,
     handle_mutex: Arc<Mutex<client::Handle<WrappingHandler<H>>>>,
-    fs_channel_mutex: Arc<Mutex<Channel<Msg>>>,
     sftp_session: Arc<russh_sftp::client::SftpSession>,
+    raw_sftp_session: Arc<russh_sftp::client::RawSftpSession>,
+    // whether the server advertised the check-file extension for computing checksums
+    check_file_supported: bool,
+    // whether the server advertised the posix-rename extension, which replaces existing files unlike plain renames
+    posix_rename_supported: bool,
+    // whether the server advertised the fsync extension, without which russh-sftp skips syncs while reporting success
+    fsync_supported: bool,
+    #[cfg(feature = "filesystem")]
+    name_cache: Arc<NameCache>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1,20 +1,54 @@
 // Modules
 
+#[cfg(feature = "executor")]
+pub mod archive;
 #[cfg(feature = "executor")]
 pub mod executor;
 #[cfg(feature = "filesystem")]
 pub mod filesystem;
 #[cfg(feature = "network")]
 pub mod network;
+#[cfg(feature = "filesystem")]
+pub mod sync;
+#[cfg(feature = "filesystem")]
+pub mod transfer;
 
 // Out of the box implementations
 
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+#[cfg(feature = "filesystem")]
+pub(crate) mod acl_ext;
+#[cfg(feature = "filesystem")]
+pub(crate) mod checksum;
 #[cfg(feature = "impl-ssh-common")]
 #[cfg(feature = "executor")]
 pub(crate) mod derive_ext;
+#[cfg(feature = "filesystem")]
+pub(crate) mod glob_expander;
+#[cfg(feature = "filesystem")]
+pub(crate) mod glob_pattern;
 #[cfg(feature = "impl-native")]
 pub mod impl_native;
 #[cfg(feature = "impl-openssh")]
 pub mod impl_openssh;
 #[cfg(feature = "impl-russh")]
 pub mod impl_russh;
+#[cfg(feature = "impl-ssh-common")]
+#[cfg(feature = "filesystem")]
+pub(crate) mod lock_ext;
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+#[cfg(feature = "filesystem")]
+pub(crate) mod name_cache;
+#[cfg(any(feature = "impl-native", feature = "impl-ssh-common"))]
+#[cfg(feature = "executor")]
+pub(crate) mod script_ext;
+#[cfg(feature = "impl-ssh-common")]
+#[cfg(feature = "filesystem")]
+pub(crate) mod stat_ext;
+#[cfg(feature = "executor")]
+pub(crate) mod tar_codec;
+#[cfg(feature = "filesystem")]
+pub(crate) mod walker;
+#[cfg(feature = "impl-ssh-common")]
+#[cfg(feature = "filesystem")]
+pub(crate) mod xattr_ext;
@@ -0,0 +1,58 @@
+use std::{io, time::Duration};
+
+use crate::filesystem::LinuxLockMode;
+
+// printed by the shell flock runs once the lock is held
+const LOCKED_MARKER: &str = "locked";
+
+// the exit status of flock when the lock couldn't be taken in time, EX_TEMPFAIL
+const CONFLICT_STATUS: u32 = 75;
+// the exit status of a shell when the command can't be found
+const COMMAND_NOT_FOUND_STATUS: u32 = 127;
+
+// the arguments of a flock invocation that holds the lock until its stdin is closed, or the connection drops
+pub(crate) fn flock_args(path: &str, mode: LinuxLockMode, timeout: Option<Duration>) -> Vec<String> {
+    let mut args = vec![
+        match mode {
+            LinuxLockMode::Shared => "-s".to_string(),
+            LinuxLockMode::Exclusive => "-x".to_string(),
+        },
+        "-E".to_string(),
+        CONFLICT_STATUS.to_string(),
+    ];
+    match timeout {
+        Some(Duration::ZERO) => args.push("-n".to_string()),
+        Some(timeout) => args.extend(["-w".to_string(), timeout.as_secs_f64().to_string()]),
+        None => {}
+    }
+    args.extend([
+        "--".to_string(),
+        path.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("echo {}; exec cat >/dev/null", LOCKED_MARKER),
+    ]);
+    args
+}
+
+// whether the stdout received so far shows that the lock is held
+pub(crate) fn is_locked(stdout: &[u8]) -> bool {
+    stdout
+        .strip_prefix(LOCKED_MARKER.as_bytes())
+        .is_some_and(|rest| rest.starts_with(b"\n"))
+}
+
+// the error of a flock invocation that exited before printing the marker
+pub(crate) fn status_error(path: &str, status_code: Option<u32>) -> io::Error {
+    match status_code {
+        Some(CONFLICT_STATUS) => io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("timed out waiting for the lock on {}", path),
+        ),
+        Some(COMMAND_NOT_FOUND_STATUS) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file locks are unsupported, since flock isn't installed on the host",
+        ),
+        _ => io::Error::other(format!("flock exited with status code {:?}", status_code)),
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use crate::filesystem::{LinuxFileMetadata, LinuxFilesystem};
+
+// user and group names of a host, read from its /etc/passwd and /etc/group on first use. Concurrent first uses may
+// each read the files, which is harmless since they'd load the same names
+pub(crate) struct NameCache {
+    user_names: Mutex<Option<Arc<HashMap<u32, String>>>>,
+    group_names: Mutex<Option<Arc<HashMap<u32, String>>>>,
+}
+
+impl NameCache {
+    pub(crate) const fn new() -> NameCache {
+        NameCache {
+            user_names: Mutex::new(None),
+            group_names: Mutex::new(None),
+        }
+    }
+
+    pub(crate) async fn lookup_user<F>(&self, filesystem: &F, user_id: u32) -> io::Result<Option<String>>
+    where
+        F: LinuxFilesystem + Sync + ?Sized,
+    {
+        let user_names = load_cached(&self.user_names, filesystem, "/etc/passwd").await?;
+        Ok(user_names.get(&user_id).cloned())
+    }
+
+    pub(crate) async fn lookup_group<F>(&self, filesystem: &F, group_id: u32) -> io::Result<Option<String>>
+    where
+        F: LinuxFilesystem + Sync + ?Sized,
+    {
+        let group_names = load_cached(&self.group_names, filesystem, "/etc/group").await?;
+        Ok(group_names.get(&group_id).cloned())
+    }
+
+    // fills in the names of the owning user and group if they're missing. Names that can't be resolved are left
+    // empty instead of failing the metadata request
+    pub(crate) async fn apply_names<F>(&self, filesystem: &F, metadata: &mut LinuxFileMetadata)
+    where
+        F: LinuxFilesystem + Sync + ?Sized,
+    {
+        if let (None, Some(user_id)) = (&metadata.user_name, metadata.user_id) {
+            metadata.user_name = self.lookup_user(filesystem, user_id).await.ok().flatten();
+        }
+        if let (None, Some(group_id)) = (&metadata.group_name, metadata.group_id) {
+            metadata.group_name = self.lookup_group(filesystem, group_id).await.ok().flatten();
+        }
+    }
+}
+
+async fn load_cached<F>(
+    cell: &Mutex<Option<Arc<HashMap<u32, String>>>>,
+    filesystem: &F,
+    path: &str,
+) -> io::Result<Arc<HashMap<u32, String>>>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    if let Some(names) = cell
+        .lock()
+        .map_err(|_| io::Error::other("name cache poisoned"))?
+        .as_ref()
+    {
+        return Ok(names.clone());
+    }
+
+    let names = Arc::new(parse_names(&filesystem.read_to_string(OsStr::new(path)).await?));
+    *cell.lock().map_err(|_| io::Error::other("name cache poisoned"))? = Some(names.clone());
+    Ok(names)
+}
+
+// both /etc/passwd and /etc/group lines start with the name, a password placeholder and the id. When an id appears
+// several times, the first name wins, like with getpwuid
+fn parse_names(content: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in content.lines() {
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if name.is_empty() || name.starts_with('#') {
+            continue;
+        }
+        if let Ok(id) = id.parse() {
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
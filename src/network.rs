@@ -5,7 +5,7 @@ use async_trait::async_trait;
 #[derive(Debug)]
 pub enum LinuxNetworkError {
     ForwardingNotSupported,
-    Other(Box<dyn std::error::Error>),
+    Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
 pub enum LinuxNetworkSocket {
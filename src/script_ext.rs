@@ -0,0 +1,80 @@
+use std::ffi::OsString;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+    executor::{FinishedLinuxProcessOutput, LinuxExecutor, LinuxProcessConfiguration, LinuxProcessError},
+    filesystem::{LinuxFile, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions},
+};
+
+#[async_trait]
+pub trait ScriptExt {
+    async fn execute_script_via_filesystem(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError>;
+}
+
+#[async_trait]
+impl<T> ScriptExt for T
+where
+    T: LinuxExecutor + LinuxFilesystem + Sync,
+{
+    async fn execute_script_via_filesystem(
+        &self,
+        interpreter: &str,
+        script_body: &str,
+        process_configuration: &LinuxProcessConfiguration,
+    ) -> Result<FinishedLinuxProcessOutput, LinuxProcessError> {
+        // the body is written into a temporary file, so that the script's own stdin stays free for the process,
+        // and is then run as: interpreter /tmp/script-UUID arg1 arg2 ...
+        let script_file = OsString::from(format!("/tmp/script-{}", Uuid::new_v4()));
+
+        // only the owner may read the script, and an existing file or symlink at the path makes the open fail
+        let open_options = *LinuxOpenOptions::new()
+            .write()
+            .create_new()
+            .mode(LinuxPermissions::from_bits_truncate(0o600));
+        let mut writer = self
+            .open_file(&script_file, &open_options)
+            .await
+            .map_err(LinuxProcessError::IO)?;
+        let write_result = async {
+            writer.write_all(script_body.as_bytes()).await?;
+            Box::new(writer).close().await?;
+            // a process running as another user still needs to read the script
+            if process_configuration.user_id.is_some() || process_configuration.group_id.is_some() {
+                self.set_owner(
+                    &script_file,
+                    process_configuration.user_id,
+                    process_configuration.group_id,
+                )
+                .await?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(err) = write_result {
+            let _ = self.remove_file(&script_file).await;
+            return Err(LinuxProcessError::IO(err));
+        }
+
+        let mut script_configuration = process_configuration.clone();
+        script_configuration.program = interpreter.into();
+        script_configuration
+            .args
+            .insert(0, script_file.to_string_lossy().into_owned());
+
+        // clean up regardless of whether the execution itself succeeded
+        let execution_result = self.execute(&script_configuration).await;
+        let cleanup_result = self.remove_file(&script_file).await;
+
+        let output = execution_result?;
+        cleanup_result.map_err(LinuxProcessError::IO)?;
+        Ok(output)
+    }
+}
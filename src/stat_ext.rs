@@ -0,0 +1,150 @@
+use std::{
+    io,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::filesystem::{LinuxFileMetadata, LinuxFsStats};
+
+// inode, device, hard link count, preferred I/O block size, allocated blocks and their unit, major and minor number of
+// special files in hex, and nanosecond atime, mtime and ctime
+const STAT_FORMAT: &str = "%i %d %h %o %b %B %t %T %.9X %.9Y %.9Z";
+
+// paths per stat invocation, keeping the command line well below ARG_MAX for large directories
+pub(crate) const STAT_BATCH_SIZE: usize = 256;
+
+// the preferred and fundamental block sizes, total, free and available blocks, and total and free inodes. stat can't
+// print the available inodes or mount flags
+const FS_STAT_FORMAT: &str = "%s %S %b %f %a %c %d";
+
+// the arguments of a GNU stat invocation printing what SFTP v3 attributes lack, one line per path
+pub(crate) fn stat_args<'a>(paths: impl IntoIterator<Item = &'a str>, follow_symlinks: bool) -> Vec<&'a str> {
+    let mut args = Vec::with_capacity(5);
+    if follow_symlinks {
+        args.push("-L");
+    }
+    args.extend(["-c", STAT_FORMAT, "--"]);
+    args.extend(paths);
+    args
+}
+
+// fills in the fields missing from SFTP attributes, and replaces the second-precision times with nanosecond ones. The
+// metadata must be given in the order of the paths passed to stat
+pub(crate) fn apply_stat_output<'a>(
+    metadata: impl ExactSizeIterator<Item = &'a mut LinuxFileMetadata>,
+    output: &[u8],
+) -> io::Result<()> {
+    let output = std::str::from_utf8(output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let lines = output.lines().collect::<Vec<_>>();
+    if lines.len() != metadata.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {} lines of stat output, got {}", metadata.len(), lines.len()),
+        ));
+    }
+
+    for (metadata, line) in metadata.zip(lines) {
+        apply_stat_line(metadata, line)?;
+    }
+    Ok(())
+}
+
+fn apply_stat_line(metadata: &mut LinuxFileMetadata, line: &str) -> io::Result<()> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let [inode, device_id, hard_link_count, block_size, blocks, block_unit, major, minor, accessed_time, modified_time, changed_time] =
+        fields[..]
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected stat output: {}", line.trim()),
+        ));
+    };
+
+    metadata.inode = Some(parse_number(inode, 10)?);
+    metadata.device_id = Some(parse_number(device_id, 10)?);
+    metadata.hard_link_count = Some(parse_number(hard_link_count, 10)?);
+    metadata.block_size = Some(parse_number(block_size, 10)?);
+    metadata.block_count = Some(parse_number(blocks, 10)? * parse_number(block_unit, 10)? / 512);
+    metadata.special_device_id = Some(make_device_id(parse_number(major, 16)?, parse_number(minor, 16)?));
+    metadata.accessed_time = Some(parse_time(accessed_time)?);
+    metadata.modified_time = Some(parse_time(modified_time)?);
+    metadata.changed_time = Some(parse_time(changed_time)?);
+    Ok(())
+}
+
+// the arguments of a GNU stat invocation printing the statistics of the filesystem containing the path
+pub(crate) fn fs_stat_args(path: &str) -> Vec<&str> {
+    vec!["-f", "-c", FS_STAT_FORMAT, "--", path]
+}
+
+pub(crate) fn parse_fs_stat_output(output: &[u8]) -> io::Result<LinuxFsStats> {
+    let output = std::str::from_utf8(output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let fields = output
+        .split_whitespace()
+        .map(|field| parse_number(field, 10))
+        .collect::<io::Result<Vec<_>>>()?;
+    let [block_size, fragment_size, total_blocks, free_blocks, available_blocks, total_inodes, free_inodes] =
+        fields[..]
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected stat output: {}", output.trim()),
+        ));
+    };
+
+    Ok(LinuxFsStats {
+        block_size,
+        fragment_size,
+        total_blocks,
+        free_blocks,
+        available_blocks,
+        total_inodes,
+        free_inodes,
+        // only some filesystems reserve inodes for root, so the free ones are the closest approximation
+        available_inodes: free_inodes,
+        mount_flags: None,
+    })
+}
+
+// the arguments of a du invocation printing the allocated bytes of a tree in total
+pub(crate) fn du_args(path: &str) -> Vec<&str> {
+    vec!["-s", "-B1", "--", path]
+}
+
+// parses the "bytes<tab>path" line of du
+pub(crate) fn parse_du_output(output: &[u8]) -> io::Result<u64> {
+    let output = std::str::from_utf8(output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    match output.split_whitespace().next() {
+        Some(usage) => parse_number(usage, 10),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "empty du output")),
+    }
+}
+
+fn parse_number(field: &str, radix: u32) -> io::Result<u64> {
+    u64::from_str_radix(field, radix).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// parses seconds since the epoch with an optional fraction, such as "-1.250000000"
+fn parse_time(field: &str) -> io::Result<SystemTime> {
+    let (is_negative, field) = match field.strip_prefix('-') {
+        Some(field) => (true, field),
+        None => (false, field),
+    };
+    let (secs, fraction) = field.split_once('.').unwrap_or((field, ""));
+    let nanos = match fraction.len() {
+        0 => 0,
+        1..=9 => parse_number(fraction, 10)? * 10u64.pow(9 - fraction.len() as u32),
+        _ => parse_number(&fraction[..9], 10)?,
+    };
+    let duration = Duration::from_secs(parse_number(secs, 10)?) + Duration::from_nanos(nanos);
+
+    match is_negative {
+        true => UNIX_EPOCH.checked_sub(duration),
+        false => UNIX_EPOCH.checked_add(duration),
+    }
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "timestamp out of range"))
+}
+
+// the dev_t encoding of glibc's makedev, so that the ids match what NativeLinux reports
+fn make_device_id(major: u64, minor: u64) -> u64 {
+    ((major & 0xfffff000) << 32) | ((major & 0xfff) << 8) | ((minor & 0xffffff00) << 12) | (minor & 0xff)
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    io,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use futures_util::{
+    future::{try_join, BoxFuture},
+    FutureExt, TryStreamExt,
+};
+
+use crate::{
+    filesystem::{
+        LinuxChecksumAlgorithm, LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxPermissions, LinuxWalkEntry,
+        LinuxWalkOptions,
+    },
+    transfer::{self, LinuxCopyOptions},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinuxSyncOptions {
+    comparison: LinuxSyncComparison,
+    delete_extraneous: bool,
+    preserve_permissions: bool,
+    preserve_symlinks: bool,
+    dry_run: bool,
+    exclude_patterns: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinuxSyncComparison {
+    /// Consider files unchanged when their sizes and modification times (in whole seconds) match, like rsync does
+    #[default]
+    SizeAndModifiedTime,
+    /// Consider files unchanged when their checksums match, computed server-side where the backend supports it
+    Checksum(LinuxChecksumAlgorithm),
+}
+
+/// The changes made by a synchronisation, or that would be made during a dry run. Paths are relative to the
+/// synchronised directories
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinuxSyncReport {
+    pub created: Vec<OsString>,
+    pub updated: Vec<OsString>,
+    pub deleted: Vec<OsString>,
+    pub unchanged: u64,
+    pub bytes: u64,
+}
+
+impl Default for LinuxSyncOptions {
+    fn default() -> Self {
+        LinuxSyncOptions::new()
+    }
+}
+
+impl LinuxSyncOptions {
+    /// Options comparing sizes and modification times, preserving permissions and symlinks and never deleting
+    pub fn new() -> LinuxSyncOptions {
+        LinuxSyncOptions {
+            comparison: LinuxSyncComparison::default(),
+            delete_extraneous: false,
+            preserve_permissions: true,
+            preserve_symlinks: true,
+            dry_run: false,
+            exclude_patterns: Vec::new(),
+        }
+    }
+
+    pub fn comparison(&mut self, comparison: LinuxSyncComparison) -> &mut LinuxSyncOptions {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Delete entries of the destination that don't exist in the source
+    pub fn delete_extraneous(&mut self, delete_extraneous: bool) -> &mut LinuxSyncOptions {
+        self.delete_extraneous = delete_extraneous;
+        self
+    }
+
+    pub fn preserve_permissions(&mut self, preserve_permissions: bool) -> &mut LinuxSyncOptions {
+        self.preserve_permissions = preserve_permissions;
+        self
+    }
+
+    /// Recreate symlinks as symlinks instead of synchronising what they point to
+    pub fn preserve_symlinks(&mut self, preserve_symlinks: bool) -> &mut LinuxSyncOptions {
+        self.preserve_symlinks = preserve_symlinks;
+        self
+    }
+
+    /// Only compute the report without changing the destination
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut LinuxSyncOptions {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Skip entries matching the glob pattern on both sides, so that excluded entries are never deleted either
+    pub fn exclude(&mut self, pattern: impl Into<String>) -> &mut LinuxSyncOptions {
+        self.exclude_patterns.push(pattern.into());
+        self
+    }
+}
+
+/// Make the destination directory mirror the source directory, copying only new and changed files. The
+/// filesystems may be backed by different implementations or hosts. Modification times of copied files are always
+/// carried over, since later comparisons rely on them
+pub fn sync_dir<'a, S, D>(
+    src_fs: &'a S,
+    src_path: &'a OsStr,
+    dst_fs: &'a D,
+    dst_path: &'a OsStr,
+    sync_options: &'a LinuxSyncOptions,
+) -> BoxFuture<'a, io::Result<LinuxSyncReport>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    // boxed, since the compiler can't prove the unboxed future to be Send for callers spawning it onto a runtime
+    sync_dir_inner(src_fs, src_path, dst_fs, dst_path, sync_options).boxed()
+}
+
+async fn sync_dir_inner<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    sync_options: &LinuxSyncOptions,
+) -> io::Result<LinuxSyncReport>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let mut walk_options = LinuxWalkOptions::new();
+    for pattern in &sync_options.exclude_patterns {
+        walk_options.exclude(pattern.clone());
+    }
+    let mut src_walk_options = walk_options.clone();
+    if !sync_options.preserve_symlinks {
+        src_walk_options.follow_symlinks();
+    }
+
+    let src_entries = src_fs
+        .walk_dir(src_path, &src_walk_options)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let mut dst_entries = match dst_fs.get_metadata(dst_path).await {
+        Ok(_) => {
+            dst_fs
+                .walk_dir(dst_path, &walk_options)
+                .map_ok(|walk_entry| (relative_path(dst_path, &walk_entry), walk_entry))
+                .try_collect::<BTreeMap<_, _>>()
+                .await?
+        }
+        Err(_) => {
+            if !sync_options.dry_run {
+                dst_fs.create_dir_recursively(dst_path).await?;
+            }
+            BTreeMap::new()
+        }
+    };
+
+    let copy_options = *LinuxCopyOptions::new()
+        .preserve_permissions(sync_options.preserve_permissions)
+        .preserve_times(true);
+    let mut report = LinuxSyncReport::default();
+    // directory permissions are applied last, so that read-only directories can still be filled
+    let mut finished_dirs = Vec::new();
+
+    // the walk is in pre-order, so directories are always handled before their contents
+    for src_entry in src_entries {
+        let relative_path = relative_path(src_path, &src_entry);
+        let entry_src_path = src_entry.dir_entry.path;
+        let entry_dst_path = Path::new(dst_path).join(&relative_path).into_os_string();
+        let src_type = src_entry.metadata.file_type.unwrap_or(src_entry.dir_entry.file_type);
+        let dst_entry = dst_entries.remove(&relative_path);
+        let dst_type = dst_entry
+            .as_ref()
+            .map(|dst_entry| dst_entry.metadata.file_type.unwrap_or(dst_entry.dir_entry.file_type));
+
+        let is_unchanged = match (src_type, dst_type) {
+            (_, None) => false,
+            (LinuxFileType::Dir, Some(LinuxFileType::Dir)) => true,
+            (LinuxFileType::Symlink, Some(LinuxFileType::Symlink)) => {
+                src_fs.read_link(&entry_src_path).await? == dst_fs.read_link(&entry_dst_path).await?
+            }
+            (LinuxFileType::File, Some(LinuxFileType::File)) => {
+                is_file_unchanged(
+                    src_fs,
+                    &entry_src_path,
+                    &src_entry.metadata,
+                    dst_fs,
+                    &entry_dst_path,
+                    dst_entry.as_ref().map(|dst_entry| &dst_entry.metadata),
+                    sync_options.comparison,
+                )
+                .await?
+            }
+            _ => false,
+        };
+
+        if is_unchanged {
+            let dst_metadata = dst_entry.map(|dst_entry| dst_entry.metadata);
+            let permissions_differ = sync_options.preserve_permissions
+                && src_type != LinuxFileType::Symlink
+                && mode_of(&src_entry.metadata) != dst_metadata.as_ref().and_then(mode_of);
+            if permissions_differ {
+                report.updated.push(relative_path);
+            } else {
+                report.unchanged += 1;
+            }
+
+            match src_type {
+                LinuxFileType::Dir => finished_dirs.push((entry_dst_path, src_entry.metadata)),
+                _ if permissions_differ && !sync_options.dry_run => {
+                    if let Some(mode) = mode_of(&src_entry.metadata) {
+                        dst_fs.set_permissions(&entry_dst_path, mode).await?;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match dst_type {
+            None => report.created.push(relative_path),
+            Some(dst_type) => {
+                // a changed file is overwritten in place, anything else is removed first
+                let is_overwritten = src_type == LinuxFileType::File && dst_type == LinuxFileType::File;
+                if !sync_options.dry_run && !is_overwritten {
+                    remove_entry(dst_fs, &entry_dst_path, dst_type).await?;
+                }
+                if dst_type == LinuxFileType::Dir {
+                    // the contents of a replaced directory are gone and mustn't be compared or deleted later
+                    let replaced_dir = Path::new(&relative_path).to_path_buf();
+                    dst_entries.retain(|path, _| !Path::new(path).starts_with(&replaced_dir));
+                }
+                report.updated.push(relative_path);
+            }
+        }
+        if sync_options.dry_run {
+            report.bytes += match src_type {
+                LinuxFileType::File => src_entry.metadata.size.unwrap_or_default(),
+                _ => 0,
+            };
+            continue;
+        }
+
+        match src_type {
+            LinuxFileType::Dir => {
+                dst_fs.create_dir(&entry_dst_path).await?;
+                finished_dirs.push((entry_dst_path, src_entry.metadata));
+            }
+            LinuxFileType::Symlink => {
+                let target = src_fs.read_link(&entry_src_path).await?;
+                dst_fs.create_symlink(&target, &entry_dst_path).await?;
+            }
+            LinuxFileType::File => {
+                report.bytes += transfer::copy_file_contents(src_fs, &entry_src_path, dst_fs, &entry_dst_path).await?;
+                transfer::apply_metadata(dst_fs, &entry_dst_path, &src_entry.metadata, &copy_options).await?;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("cannot synchronise special file {}", entry_src_path.to_string_lossy()),
+                ))
+            }
+        }
+    }
+
+    if sync_options.delete_extraneous {
+        // entries below a deleted directory go along with it
+        let mut deleted_dirs: Vec<OsString> = Vec::new();
+        for (relative_path, dst_entry) in dst_entries {
+            if deleted_dirs
+                .iter()
+                .any(|deleted_dir| Path::new(&relative_path).starts_with(deleted_dir))
+            {
+                continue;
+            }
+
+            let dst_type = dst_entry.metadata.file_type.unwrap_or(dst_entry.dir_entry.file_type);
+            if !sync_options.dry_run {
+                remove_entry(dst_fs, &dst_entry.dir_entry.path, dst_type).await?;
+            }
+            if dst_type == LinuxFileType::Dir {
+                deleted_dirs.push(relative_path.clone());
+            }
+            report.deleted.push(relative_path);
+        }
+    }
+
+    if sync_options.preserve_permissions && !sync_options.dry_run {
+        for (entry_dst_path, metadata) in finished_dirs.into_iter().rev() {
+            if let Some(mode) = mode_of(&metadata) {
+                dst_fs.set_permissions(&entry_dst_path, mode).await?;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn relative_path(root: &OsStr, walk_entry: &LinuxWalkEntry) -> OsString {
+    Path::new(&walk_entry.dir_entry.path)
+        .strip_prefix(root)
+        .map(|relative_path| relative_path.as_os_str().to_os_string())
+        .unwrap_or_else(|_| walk_entry.dir_entry.name.clone())
+}
+
+// remote impls report the file type bits of the mode as well
+fn mode_of(metadata: &LinuxFileMetadata) -> Option<LinuxPermissions> {
+    metadata
+        .permissions
+        .map(|permissions| LinuxPermissions::from_bits_truncate(permissions.bits()))
+}
+
+async fn is_file_unchanged<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    src_metadata: &LinuxFileMetadata,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    dst_metadata: Option<&LinuxFileMetadata>,
+    comparison: LinuxSyncComparison,
+) -> io::Result<bool>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let Some(dst_metadata) = dst_metadata else {
+        return Ok(false);
+    };
+    if src_metadata.size != dst_metadata.size {
+        return Ok(false);
+    }
+
+    match comparison {
+        LinuxSyncComparison::SizeAndModifiedTime => {
+            // SFTP only carries whole seconds, so finer differences are ignored
+            let as_secs = |metadata: &LinuxFileMetadata| {
+                metadata
+                    .modified_time
+                    .and_then(|modified_time| modified_time.duration_since(UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs())
+            };
+            Ok(as_secs(src_metadata).is_some() && as_secs(src_metadata) == as_secs(dst_metadata))
+        }
+        LinuxSyncComparison::Checksum(algorithm) => {
+            let (src_checksum, dst_checksum) = try_join(
+                src_fs.checksum(src_path, algorithm),
+                dst_fs.checksum(dst_path, algorithm),
+            )
+            .await?;
+            Ok(src_checksum == dst_checksum)
+        }
+    }
+}
+
+async fn remove_entry<D>(dst_fs: &D, path: &OsStr, file_type: LinuxFileType) -> io::Result<()>
+where
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    match file_type {
+        LinuxFileType::Dir => dst_fs.remove_dir_recursively(path).await,
+        _ => dst_fs.remove_file(path).await,
+    }
+}
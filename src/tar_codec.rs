@@ -0,0 +1,350 @@
+use std::{
+    io,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// a minimal tar codec: ustar headers with pax extended headers for long paths, large sizes and precise timestamps
+// when writing, and ustar, pax and GNU long name headers when reading, which covers what GNU tar and busybox emit
+
+pub(crate) const BLOCK_SIZE: usize = 512;
+
+const NAME_LENGTH: usize = 100;
+const MAX_OCTAL_SIZE: u64 = 0o77777777777;
+const MAX_OCTAL_ID: u32 = 0o7777777;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TarEntryKind {
+    File,
+    Dir,
+    Symlink,
+    HardLink,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TarEntry {
+    // relative to the archive root, without a trailing slash for directories
+    pub(crate) path: Vec<u8>,
+    pub(crate) kind: TarEntryKind,
+    pub(crate) link_target: Vec<u8>,
+    pub(crate) size: u64,
+    pub(crate) mode: u32,
+    pub(crate) user_id: u32,
+    pub(crate) group_id: u32,
+    pub(crate) modified_time: SystemTime,
+    pub(crate) accessed_time: Option<SystemTime>,
+}
+
+// the result of parsing a single header block, before extended headers are applied
+pub(crate) enum TarHeader {
+    Entry(TarEntry),
+    // a pax extended header applying to the next entry, followed by this many bytes of records
+    PaxExtension { size: u64 },
+    // a pax global header or another header whose content is skipped
+    Skipped { size: u64 },
+    // a GNU header whose content is the long path or link target of the next entry
+    GnuLongPath { size: u64 },
+    GnuLongLinkTarget { size: u64 },
+    Unsupported { path: Vec<u8>, type_flag: u8 },
+}
+
+// encodes the header blocks of an entry, including a pax extended header with its records if needed
+pub(crate) fn encode_header(entry: &TarEntry, include_times: bool) -> Vec<u8> {
+    let mut path = entry.path.clone();
+    if entry.kind == TarEntryKind::Dir {
+        path.push(b'/');
+    }
+    let (modified_secs, modified_nanos) = split_time(entry.modified_time);
+
+    let mut pax_records = Vec::new();
+    if path.len() > NAME_LENGTH {
+        push_pax_record(&mut pax_records, "path", &path);
+    }
+    if entry.link_target.len() > NAME_LENGTH {
+        push_pax_record(&mut pax_records, "linkpath", &entry.link_target);
+    }
+    if entry.size > MAX_OCTAL_SIZE {
+        push_pax_record(&mut pax_records, "size", entry.size.to_string().as_bytes());
+    }
+    if entry.user_id > MAX_OCTAL_ID {
+        push_pax_record(&mut pax_records, "uid", entry.user_id.to_string().as_bytes());
+    }
+    if entry.group_id > MAX_OCTAL_ID {
+        push_pax_record(&mut pax_records, "gid", entry.group_id.to_string().as_bytes());
+    }
+    if include_times {
+        if modified_nanos != 0 {
+            push_pax_record(&mut pax_records, "mtime", format_time(entry.modified_time).as_bytes());
+        }
+        if let Some(accessed_time) = entry.accessed_time {
+            push_pax_record(&mut pax_records, "atime", format_time(accessed_time).as_bytes());
+        }
+    }
+
+    let mut output = Vec::with_capacity(3 * BLOCK_SIZE);
+    if !pax_records.is_empty() {
+        let mut pax_header = [0; BLOCK_SIZE];
+        write_bytes(&mut pax_header[0..NAME_LENGTH], b"././@PaxHeader");
+        write_octal(&mut pax_header[100..108], 0o644);
+        write_octal(&mut pax_header[108..116], 0);
+        write_octal(&mut pax_header[116..124], 0);
+        write_octal(&mut pax_header[124..136], pax_records.len() as u64);
+        write_octal(&mut pax_header[136..148], modified_secs);
+        pax_header[156] = b'x';
+        finish_header(&mut pax_header);
+        output.extend_from_slice(&pax_header);
+        output.extend_from_slice(&pax_records);
+        output.resize(output.len() + padding(pax_records.len() as u64), 0);
+    }
+
+    let mut header = [0; BLOCK_SIZE];
+    write_bytes(&mut header[0..NAME_LENGTH], &path);
+    write_octal(&mut header[100..108], u64::from(entry.mode & 0o7777));
+    write_octal(&mut header[108..116], u64::from(entry.user_id.min(MAX_OCTAL_ID)));
+    write_octal(&mut header[116..124], u64::from(entry.group_id.min(MAX_OCTAL_ID)));
+    write_octal(&mut header[124..136], entry.size.min(MAX_OCTAL_SIZE));
+    write_octal(&mut header[136..148], modified_secs);
+    header[156] = match entry.kind {
+        TarEntryKind::File => b'0',
+        TarEntryKind::HardLink => b'1',
+        TarEntryKind::Symlink => b'2',
+        TarEntryKind::Dir => b'5',
+    };
+    write_bytes(&mut header[157..257], &entry.link_target);
+    finish_header(&mut header);
+    output.extend_from_slice(&header);
+
+    output
+}
+
+// the two zero blocks marking the end of an archive
+pub(crate) fn end_of_archive() -> [u8; 2 * BLOCK_SIZE] {
+    [0; 2 * BLOCK_SIZE]
+}
+
+// the number of zero bytes following content of the given size, up to the next block
+pub(crate) fn padding(size: u64) -> usize {
+    ((BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64) as usize
+}
+
+// returns None for a zero block, which marks the end of the archive
+pub(crate) fn decode_header(block: &[u8; BLOCK_SIZE]) -> io::Result<Option<TarHeader>> {
+    if block.iter().all(|byte| *byte == 0) {
+        return Ok(None);
+    }
+
+    let expected_checksum = parse_number(&block[148..156])?;
+    let unsigned_checksum = block
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| {
+            if (148..156).contains(&index) {
+                32
+            } else {
+                u64::from(*byte)
+            }
+        })
+        .sum::<u64>();
+    let signed_checksum = block
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| {
+            if (148..156).contains(&index) {
+                32
+            } else {
+                i64::from(*byte as i8)
+            }
+        })
+        .sum::<i64>();
+    if expected_checksum != unsigned_checksum && expected_checksum as i64 != signed_checksum {
+        return Err(invalid_data("tar header checksum mismatch"));
+    }
+
+    let mut path = read_bytes(&block[0..NAME_LENGTH]);
+    // only POSIX ustar headers have a prefix, GNU headers use the same space for other fields
+    if &block[257..263] == b"ustar\0" {
+        let prefix = read_bytes(&block[345..500]);
+        if !prefix.is_empty() {
+            path = [prefix, b"/".to_vec(), path].concat();
+        }
+    }
+    let size = parse_number(&block[124..136])?;
+    let type_flag = block[156];
+
+    let kind = match type_flag {
+        b'0' | b'\0' | b'7' => TarEntryKind::File,
+        b'1' => TarEntryKind::HardLink,
+        b'2' => TarEntryKind::Symlink,
+        b'5' => TarEntryKind::Dir,
+        b'x' => return Ok(Some(TarHeader::PaxExtension { size })),
+        b'g' => return Ok(Some(TarHeader::Skipped { size })),
+        b'L' => return Ok(Some(TarHeader::GnuLongPath { size })),
+        b'K' => return Ok(Some(TarHeader::GnuLongLinkTarget { size })),
+        _ => return Ok(Some(TarHeader::Unsupported { path, type_flag })),
+    };
+    // old archives mark directories with a trailing slash only
+    let kind = match (kind, path.last()) {
+        (TarEntryKind::File, Some(b'/')) => TarEntryKind::Dir,
+        (kind, _) => kind,
+    };
+
+    Ok(Some(TarHeader::Entry(TarEntry {
+        path: trim_path(path),
+        kind,
+        link_target: read_bytes(&block[157..257]),
+        size: match kind {
+            TarEntryKind::File => size,
+            _ => 0,
+        },
+        mode: parse_number(&block[100..108])? as u32,
+        user_id: parse_number(&block[108..116])? as u32,
+        group_id: parse_number(&block[116..124])? as u32,
+        modified_time: UNIX_EPOCH + Duration::from_secs(parse_number(&block[136..148])?),
+        accessed_time: None,
+    })))
+}
+
+// applies the records of a pax extended header to the entry following it
+pub(crate) fn apply_pax_records(entry: &mut TarEntry, records: &[u8]) -> io::Result<()> {
+    let mut rest = records;
+    while !rest.is_empty() {
+        let space_index = rest
+            .iter()
+            .position(|byte| *byte == b' ')
+            .ok_or_else(|| invalid_data("malformed pax record"))?;
+        let length = std::str::from_utf8(&rest[..space_index])
+            .ok()
+            .and_then(|length| length.parse::<usize>().ok())
+            .filter(|length| *length > space_index + 1 && *length <= rest.len())
+            .ok_or_else(|| invalid_data("malformed pax record length"))?;
+        // the record is "LENGTH KEY=VALUE\n"
+        let record = &rest[space_index + 1..length - 1];
+        rest = &rest[length..];
+
+        let equals_index = record
+            .iter()
+            .position(|byte| *byte == b'=')
+            .ok_or_else(|| invalid_data("malformed pax record"))?;
+        let (key, value) = (&record[..equals_index], &record[equals_index + 1..]);
+        let value_str = || String::from_utf8_lossy(value).into_owned();
+
+        match key {
+            b"path" => entry.path = trim_path(value.to_vec()),
+            b"linkpath" => entry.link_target = value.to_vec(),
+            b"size" => entry.size = parse_decimal(&value_str())?,
+            b"uid" => entry.user_id = parse_decimal(&value_str())? as u32,
+            b"gid" => entry.group_id = parse_decimal(&value_str())? as u32,
+            b"mtime" => entry.modified_time = parse_time(&value_str())?,
+            b"atime" => entry.accessed_time = Some(parse_time(&value_str())?),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// strips trailing slashes and NUL bytes, as well as a leading "./" from a path read from the archive
+pub(crate) fn trim_path(mut path: Vec<u8>) -> Vec<u8> {
+    while matches!(path.last(), Some(b'/') | Some(b'\0')) && path.len() > 1 {
+        path.pop();
+    }
+    while path.starts_with(b"./") {
+        path.drain(..2);
+    }
+    path
+}
+
+fn push_pax_record(records: &mut Vec<u8>, key: &str, value: &[u8]) {
+    // the length includes its own digits, so grow it until it is stable
+    let base_length = key.len() + value.len() + 3;
+    let mut length = base_length + 1;
+    while base_length + length.to_string().len() != length {
+        length = base_length + length.to_string().len();
+    }
+
+    records.extend_from_slice(length.to_string().as_bytes());
+    records.push(b' ');
+    records.extend_from_slice(key.as_bytes());
+    records.push(b'=');
+    records.extend_from_slice(value);
+    records.push(b'\n');
+}
+
+fn finish_header(header: &mut [u8; BLOCK_SIZE]) {
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|byte| u32::from(*byte)).sum::<u32>();
+    // six octal digits, a NUL and a space
+    header[148..154].copy_from_slice(format!("{:06o}", checksum).as_bytes());
+    header[154] = 0;
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    field[..width].copy_from_slice(format!("{:0width$o}", value).as_bytes());
+    field[width] = 0;
+}
+
+// writes as much of the value as fits, longer values are carried by pax records
+fn write_bytes(field: &mut [u8], value: &[u8]) {
+    let length = value.len().min(field.len());
+    field[..length].copy_from_slice(&value[..length]);
+}
+
+fn read_bytes(field: &[u8]) -> Vec<u8> {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    field[..end].to_vec()
+}
+
+// parses an octal field, or a base-256 one as written by GNU tar for large values
+fn parse_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|byte| byte & 0x80 != 0) {
+        return Ok(field[1..].iter().fold(u64::from(field[0] & 0x7f), |value, byte| {
+            (value << 8) | u64::from(*byte)
+        }));
+    }
+
+    let digits = field
+        .iter()
+        .filter(|byte| **byte != 0 && **byte != b' ')
+        .map(|byte| *byte as char)
+        .collect::<String>();
+    match digits.is_empty() {
+        true => Ok(0),
+        false => u64::from_str_radix(&digits, 8).map_err(|_| invalid_data("malformed number in tar header")),
+    }
+}
+
+fn parse_decimal(value: &str) -> io::Result<u64> {
+    value
+        .parse::<u64>()
+        .map_err(|_| invalid_data("malformed number in pax record"))
+}
+
+fn split_time(time: SystemTime) -> (u64, u32) {
+    // timestamps before the epoch are clamped, since tar headers can't carry them
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (duration.as_secs(), duration.subsec_nanos())
+}
+
+fn format_time(time: SystemTime) -> String {
+    match split_time(time) {
+        (secs, 0) => secs.to_string(),
+        (secs, nanos) => format!("{}.{:09}", secs, nanos),
+    }
+}
+
+fn parse_time(value: &str) -> io::Result<SystemTime> {
+    let (secs, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let secs = parse_decimal(secs)?;
+    // the fraction may have any number of digits, only nanoseconds are kept
+    let nanos = format!("{:0<9}", fraction.chars().take(9).collect::<String>());
+    let nanos = nanos
+        .parse::<u32>()
+        .map_err(|_| invalid_data("malformed timestamp in pax record"))?;
+    Ok(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
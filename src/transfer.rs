@@ -0,0 +1,582 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures_util::{
+    future::{try_join, try_join_all, BoxFuture},
+    FutureExt,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::filesystem::{
+    LinuxChecksumAlgorithm, LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions,
+};
+#[cfg(feature = "impl-native")]
+use crate::impl_native::NativeLinux;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinuxCopyOptions {
+    preserve_permissions: bool,
+    preserve_symlinks: bool,
+    preserve_times: bool,
+    preserve_ownership: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinuxCopyReport {
+    pub files: u64,
+    pub dirs: u64,
+    pub symlinks: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone)]
+pub struct LinuxTransferOptions {
+    chunk_size: u64,
+    parallel_chunks: usize,
+    resume: bool,
+    // None picks the verification depending on whether the transfer resumes
+    verification: Option<LinuxTransferVerification>,
+    progress_callback: Option<Arc<dyn Fn(LinuxTransferProgress) + Send + Sync>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinuxTransferVerification {
+    /// Trust the transfer without checking the destination
+    None,
+    /// Compare the size of the destination to that of the source
+    #[default]
+    Size,
+    /// Compare sizes, then compare the SHA-256 checksums of both files, computed server-side where possible
+    Sha256,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinuxTransferProgress {
+    /// Bytes present at the destination so far, including a resumed prefix
+    pub transferred_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinuxTransferReport {
+    pub total_bytes: u64,
+    /// The offset the transfer was resumed from, 0 if it started over
+    pub resumed_from: u64,
+    /// The hex-encoded SHA-256 hash of the file, only present with [`LinuxTransferVerification::Sha256`]
+    pub sha256: Option<String>,
+}
+
+enum CopyStep {
+    Copy {
+        src_path: OsString,
+        dst_path: OsString,
+    },
+    // applies the attributes of a directory once its contents have been copied, so that they don't alter its mtime
+    FinishDir {
+        dst_path: OsString,
+        metadata: Box<LinuxFileMetadata>,
+    },
+}
+
+impl Default for LinuxCopyOptions {
+    fn default() -> Self {
+        LinuxCopyOptions::new()
+    }
+}
+
+impl Default for LinuxTransferOptions {
+    fn default() -> Self {
+        LinuxTransferOptions::new()
+    }
+}
+
+impl fmt::Debug for LinuxTransferOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinuxTransferOptions")
+            .field("chunk_size", &self.chunk_size)
+            .field("parallel_chunks", &self.parallel_chunks)
+            .field("resume", &self.resume)
+            .field("verification", &self.verification)
+            .field("progress_callback", &self.progress_callback.is_some())
+            .finish()
+    }
+}
+
+impl LinuxCopyOptions {
+    /// Options preserving permissions and symlinks, but neither timestamps nor ownership
+    pub fn new() -> LinuxCopyOptions {
+        LinuxCopyOptions {
+            preserve_permissions: true,
+            preserve_symlinks: true,
+            preserve_times: false,
+            preserve_ownership: false,
+        }
+    }
+
+    pub fn is_preserve_permissions(&self) -> bool {
+        self.preserve_permissions
+    }
+
+    pub fn is_preserve_symlinks(&self) -> bool {
+        self.preserve_symlinks
+    }
+
+    pub fn is_preserve_times(&self) -> bool {
+        self.preserve_times
+    }
+
+    pub fn is_preserve_ownership(&self) -> bool {
+        self.preserve_ownership
+    }
+
+    pub fn preserve_permissions(&mut self, preserve_permissions: bool) -> &mut LinuxCopyOptions {
+        self.preserve_permissions = preserve_permissions;
+        self
+    }
+
+    /// Recreate symlinks as symlinks instead of copying what they point to
+    pub fn preserve_symlinks(&mut self, preserve_symlinks: bool) -> &mut LinuxCopyOptions {
+        self.preserve_symlinks = preserve_symlinks;
+        self
+    }
+
+    /// Carry over access and modification times
+    pub fn preserve_times(&mut self, preserve_times: bool) -> &mut LinuxCopyOptions {
+        self.preserve_times = preserve_times;
+        self
+    }
+
+    /// Carry over the owning user and group IDs, which usually requires root on the destination
+    pub fn preserve_ownership(&mut self, preserve_ownership: bool) -> &mut LinuxCopyOptions {
+        self.preserve_ownership = preserve_ownership;
+        self
+    }
+}
+
+impl LinuxTransferOptions {
+    /// Options for a sequential transfer in 1 MiB chunks that starts over and only verifies the size
+    pub fn new() -> LinuxTransferOptions {
+        LinuxTransferOptions {
+            chunk_size: 1024 * 1024,
+            parallel_chunks: 1,
+            resume: false,
+            verification: None,
+            progress_callback: None,
+        }
+    }
+
+    pub fn chunk_size(&mut self, chunk_size: u64) -> &mut LinuxTransferOptions {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Copy this many chunks at once, each over its own pair of handles. SFTP backends benefit from this, since
+    /// their throughput is otherwise bound by the round trip of every read and write
+    pub fn parallel_chunks(&mut self, parallel_chunks: usize) -> &mut LinuxTransferOptions {
+        self.parallel_chunks = parallel_chunks.max(1);
+        self
+    }
+
+    /// Continue from the end of a destination that partially exists, assuming it holds a prefix of the source.
+    /// Since parallel chunks complete out of order, an interrupted transfer can leave holes before the end of the
+    /// destination, so resumed transfers are verified by SHA-256 unless another verification is set
+    pub fn resume(&mut self, resume: bool) -> &mut LinuxTransferOptions {
+        self.resume = resume;
+        self
+    }
+
+    pub fn verification(&mut self, verification: LinuxTransferVerification) -> &mut LinuxTransferOptions {
+        self.verification = Some(verification);
+        self
+    }
+
+    fn effective_verification(&self) -> LinuxTransferVerification {
+        match (self.verification, self.resume) {
+            (Some(verification), _) => verification,
+            (None, true) => LinuxTransferVerification::Sha256,
+            (None, false) => LinuxTransferVerification::default(),
+        }
+    }
+
+    /// Call the given function every time a chunk has been written
+    pub fn on_progress(
+        &mut self,
+        progress_callback: impl Fn(LinuxTransferProgress) + Send + Sync + 'static,
+    ) -> &mut LinuxTransferOptions {
+        self.progress_callback = Some(Arc::new(progress_callback));
+        self
+    }
+}
+
+/// Copy a file, symlink or directory tree from one filesystem to another, which may be backed by different
+/// implementations or hosts. The destination's parent must exist, while existing files at the destination are
+/// overwritten and existing directories are merged into
+pub fn transfer<'a, S, D>(
+    src_fs: &'a S,
+    src_path: &'a OsStr,
+    dst_fs: &'a D,
+    dst_path: &'a OsStr,
+    copy_options: &'a LinuxCopyOptions,
+) -> BoxFuture<'a, io::Result<LinuxCopyReport>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    // boxed, since the compiler can't prove the unboxed future to be Send for callers spawning it onto a runtime
+    copy_tree(src_fs, src_path, dst_fs, dst_path, copy_options, false).boxed()
+}
+
+// the tree is copied iteratively, since recursive async fns would need to be boxed at every level. When both sides
+// are the same instance, files are copied with copy_file, letting backends copy them without a round trip
+pub(crate) async fn copy_tree<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    copy_options: &LinuxCopyOptions,
+    same_instance: bool,
+) -> io::Result<LinuxCopyReport>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let mut report = LinuxCopyReport::default();
+    let mut steps = vec![CopyStep::Copy {
+        src_path: src_path.to_os_string(),
+        dst_path: dst_path.to_os_string(),
+    }];
+
+    while let Some(step) = steps.pop() {
+        let (src_path, dst_path) = match step {
+            CopyStep::Copy { src_path, dst_path } => (src_path, dst_path),
+            CopyStep::FinishDir { dst_path, metadata } => {
+                apply_metadata(dst_fs, &dst_path, &metadata, copy_options).await?;
+                continue;
+            }
+        };
+
+        let mut metadata = src_fs.get_symlink_metadata(&src_path).await?;
+        if metadata.file_type == Some(LinuxFileType::Symlink) {
+            if copy_options.preserve_symlinks {
+                let target = src_fs.read_link(&src_path).await?;
+                if dst_fs.get_symlink_metadata(&dst_path).await.is_ok() {
+                    dst_fs.remove_file(&dst_path).await?;
+                }
+                dst_fs.create_symlink(&target, &dst_path).await?;
+                report.symlinks += 1;
+                continue;
+            }
+            metadata = src_fs.get_metadata(&src_path).await?;
+        }
+
+        match metadata.file_type {
+            Some(LinuxFileType::Dir) => {
+                match dst_fs.get_metadata(&dst_path).await {
+                    Ok(dst_metadata) if dst_metadata.file_type == Some(LinuxFileType::Dir) => {}
+                    _ => dst_fs.create_dir(&dst_path).await?,
+                }
+                report.dirs += 1;
+
+                let mut entries = src_fs.list_dir(&src_path).await?;
+                entries.sort_by(|first, second| first.name.cmp(&second.name));
+                steps.push(CopyStep::FinishDir {
+                    dst_path: dst_path.clone(),
+                    metadata: Box::new(metadata),
+                });
+                for entry in entries.into_iter().rev() {
+                    steps.push(CopyStep::Copy {
+                        dst_path: Path::new(&dst_path).join(&entry.name).into_os_string(),
+                        src_path: entry.path,
+                    });
+                }
+            }
+            Some(LinuxFileType::File) => {
+                report.bytes += match same_instance {
+                    true => match src_fs.copy_file(&src_path, &dst_path).await? {
+                        Some(bytes) => bytes,
+                        None => metadata.size.unwrap_or_default(),
+                    },
+                    false => copy_file_contents(src_fs, &src_path, dst_fs, &dst_path).await?,
+                };
+                report.files += 1;
+                apply_metadata(dst_fs, &dst_path, &metadata, copy_options).await?;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("cannot copy special file {}", src_path.to_string_lossy()),
+                ))
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub(crate) async fn copy_file_contents<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+) -> io::Result<u64>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let read_options = *LinuxOpenOptions::new().read();
+    let write_options = *LinuxOpenOptions::new().write().create().truncate();
+    let mut reader = src_fs.open_file(src_path, &read_options).await?;
+    let mut writer = dst_fs.open_file(dst_path, &write_options).await?;
+    let bytes = tokio::io::copy(&mut reader, &mut writer).await?;
+    writer.shutdown().await?;
+    Ok(bytes)
+}
+
+pub(crate) async fn apply_metadata<D>(
+    dst_fs: &D,
+    dst_path: &OsStr,
+    metadata: &LinuxFileMetadata,
+    copy_options: &LinuxCopyOptions,
+) -> io::Result<()>
+where
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    // ownership goes first, since changing it can clear the setuid and setgid bits
+    if copy_options.preserve_ownership && (metadata.user_id.is_some() || metadata.group_id.is_some()) {
+        dst_fs.set_owner(dst_path, metadata.user_id, metadata.group_id).await?;
+    }
+
+    if copy_options.preserve_permissions {
+        if let Some(permissions) = metadata.permissions {
+            // remote impls report the file type bits of the mode as well
+            let permissions = LinuxPermissions::from_bits_truncate(permissions.bits());
+            dst_fs.set_permissions(dst_path, permissions).await?;
+        }
+    }
+
+    if copy_options.preserve_times {
+        if let (Some(accessed_time), Some(modified_time)) = (metadata.accessed_time, metadata.modified_time) {
+            dst_fs.set_times(dst_path, accessed_time, modified_time).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a single regular file from one filesystem to another in chunks, reporting progress, optionally resuming a
+/// partial destination and verifying the result afterwards
+pub fn transfer_file<'a, S, D>(
+    src_fs: &'a S,
+    src_path: &'a OsStr,
+    dst_fs: &'a D,
+    dst_path: &'a OsStr,
+    transfer_options: &'a LinuxTransferOptions,
+) -> BoxFuture<'a, io::Result<LinuxTransferReport>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    transfer_file_inner(src_fs, src_path, dst_fs, dst_path, transfer_options).boxed()
+}
+
+/// Upload a local file to the given filesystem, see [`transfer_file`]
+#[cfg(feature = "impl-native")]
+pub fn upload_file<'a, D>(
+    local_path: &'a OsStr,
+    dst_fs: &'a D,
+    dst_path: &'a OsStr,
+    transfer_options: &'a LinuxTransferOptions,
+) -> BoxFuture<'a, io::Result<LinuxTransferReport>>
+where
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    async move {
+        let local_fs = NativeLinux::new();
+        transfer_file(&local_fs, local_path, dst_fs, dst_path, transfer_options).await
+    }
+    .boxed()
+}
+
+/// Download a file from the given filesystem to a local path, see [`transfer_file`]
+#[cfg(feature = "impl-native")]
+pub fn download_file<'a, S>(
+    src_fs: &'a S,
+    src_path: &'a OsStr,
+    local_path: &'a OsStr,
+    transfer_options: &'a LinuxTransferOptions,
+) -> BoxFuture<'a, io::Result<LinuxTransferReport>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+{
+    async move {
+        let local_fs = NativeLinux::new();
+        transfer_file(src_fs, src_path, &local_fs, local_path, transfer_options).await
+    }
+    .boxed()
+}
+
+async fn transfer_file_inner<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    transfer_options: &LinuxTransferOptions,
+) -> io::Result<LinuxTransferReport>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let src_metadata = src_fs.get_metadata(src_path).await?;
+    if src_metadata.file_type != Some(LinuxFileType::File) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a regular file", src_path.to_string_lossy()),
+        ));
+    }
+    let total_bytes = src_metadata
+        .size
+        .ok_or_else(|| io::Error::other("the size of the source is unknown"))?;
+
+    // a destination larger than the source can't be a prefix of it, so the transfer starts over
+    let resumed_from = match transfer_options.resume {
+        true => match dst_fs.get_metadata(dst_path).await {
+            Ok(dst_metadata) => dst_metadata.size.filter(|size| *size <= total_bytes).unwrap_or(0),
+            Err(_) => 0,
+        },
+        false => 0,
+    };
+    if resumed_from == 0 {
+        let create_options = *LinuxOpenOptions::new().write().create().truncate();
+        dst_fs.open_file(dst_path, &create_options).await?.shutdown().await?;
+    }
+
+    let transferred_bytes = AtomicU64::new(resumed_from);
+    let chunk_size = transfer_options.chunk_size;
+    let chunk_count = (total_bytes - resumed_from).div_ceil(chunk_size);
+    let worker_count = (transfer_options.parallel_chunks as u64).min(chunk_count);
+    // worker n copies chunks n, n + worker_count, n + 2 * worker_count and so on
+    try_join_all((0..worker_count).map(|worker_index| {
+        let offsets = (worker_index..chunk_count)
+            .step_by(worker_count as usize)
+            .map(|chunk_index| resumed_from + chunk_index * chunk_size);
+        let progress = ChunkProgress {
+            transferred_bytes: &transferred_bytes,
+            total_bytes,
+            transfer_options,
+        };
+        copy_chunks(src_fs, src_path, dst_fs, dst_path, offsets, total_bytes, progress)
+    }))
+    .await?;
+
+    let sha256 = verify_transfer(src_fs, src_path, dst_fs, dst_path, total_bytes, transfer_options).await?;
+
+    Ok(LinuxTransferReport {
+        total_bytes,
+        resumed_from,
+        sha256,
+    })
+}
+
+struct ChunkProgress<'a> {
+    transferred_bytes: &'a AtomicU64,
+    total_bytes: u64,
+    transfer_options: &'a LinuxTransferOptions,
+}
+
+async fn copy_chunks<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    offsets: impl Iterator<Item = u64>,
+    total_bytes: u64,
+    progress: ChunkProgress<'_>,
+) -> io::Result<()>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let read_options = *LinuxOpenOptions::new().read();
+    let write_options = *LinuxOpenOptions::new().write();
+    let mut reader = src_fs.open_file(src_path, &read_options).await?;
+    let mut writer = dst_fs.open_file(dst_path, &write_options).await?;
+    let chunk_size = progress.transfer_options.chunk_size;
+    let mut buf = vec![0; chunk_size.min(total_bytes) as usize];
+
+    for offset in offsets {
+        let chunk = &mut buf[..chunk_size.min(total_bytes - offset) as usize];
+        reader.seek(io::SeekFrom::Start(offset)).await?;
+        reader.read_exact(chunk).await?;
+        writer.seek(io::SeekFrom::Start(offset)).await?;
+        writer.write_all(chunk).await?;
+
+        let transferred_bytes = progress
+            .transferred_bytes
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed)
+            + chunk.len() as u64;
+        if let Some(progress_callback) = &progress.transfer_options.progress_callback {
+            progress_callback(LinuxTransferProgress {
+                transferred_bytes,
+                total_bytes: progress.total_bytes,
+            });
+        }
+    }
+
+    writer.shutdown().await
+}
+
+async fn verify_transfer<S, D>(
+    src_fs: &S,
+    src_path: &OsStr,
+    dst_fs: &D,
+    dst_path: &OsStr,
+    total_bytes: u64,
+    transfer_options: &LinuxTransferOptions,
+) -> io::Result<Option<String>>
+where
+    S: LinuxFilesystem + Sync + ?Sized,
+    D: LinuxFilesystem + Sync + ?Sized,
+{
+    let verification = transfer_options.effective_verification();
+    if verification == LinuxTransferVerification::None {
+        return Ok(None);
+    }
+
+    let dst_size = dst_fs.get_metadata(dst_path).await?.size;
+    if dst_size != Some(total_bytes) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "size mismatch after transfer: expected {} bytes, found {:?}",
+                total_bytes, dst_size
+            ),
+        ));
+    }
+    if verification == LinuxTransferVerification::Size {
+        return Ok(None);
+    }
+
+    let (src_hash, dst_hash) = try_join(
+        src_fs.checksum(src_path, LinuxChecksumAlgorithm::Sha256),
+        dst_fs.checksum(dst_path, LinuxChecksumAlgorithm::Sha256),
+    )
+    .await?;
+    if src_hash != dst_hash {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "hash mismatch after transfer: expected {}, found {}",
+                src_hash, dst_hash
+            ),
+        ));
+    }
+
+    Ok(Some(src_hash))
+}
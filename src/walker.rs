@@ -0,0 +1,184 @@
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use futures_util::{
+    future::join_all,
+    stream::{self, BoxStream, StreamExt},
+};
+
+use crate::{
+    filesystem::{LinuxFileType, LinuxFilesystem, LinuxWalkEntry, LinuxWalkOptions, LinuxWalkOrder},
+    glob_pattern::GlobPattern,
+};
+
+struct WalkState<'a, F: ?Sized> {
+    filesystem: &'a F,
+    root: PathBuf,
+    walk_options: LinuxWalkOptions,
+    include_patterns: Vec<GlobPattern>,
+    exclude_patterns: Vec<GlobPattern>,
+    // a stack of pending steps, popped from the end
+    steps: Vec<WalkStep>,
+    // canonical paths of visited directories, only tracked when following symlinks in order to break cycles
+    visited_dirs: HashSet<OsString>,
+}
+
+enum WalkStep {
+    Visit { path: OsString, depth: usize },
+    Yield(Box<LinuxWalkEntry>),
+}
+
+pub(crate) fn walk_dir<'a, F>(
+    filesystem: &'a F,
+    path: &OsStr,
+    walk_options: &LinuxWalkOptions,
+) -> BoxStream<'a, io::Result<LinuxWalkEntry>>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    let (include_patterns, exclude_patterns) = match (
+        compile_patterns(&walk_options.include_patterns),
+        compile_patterns(&walk_options.exclude_patterns),
+    ) {
+        (Ok(include_patterns), Ok(exclude_patterns)) => (include_patterns, exclude_patterns),
+        (Err(err), _) | (_, Err(err)) => return stream::once(async { Err(err) }).boxed(),
+    };
+
+    let mut state = WalkState {
+        filesystem,
+        root: PathBuf::from(path),
+        walk_options: walk_options.clone(),
+        include_patterns,
+        exclude_patterns,
+        steps: Vec::new(),
+        visited_dirs: HashSet::new(),
+    };
+    // the root itself isn't yielded, so a depth of 0 leaves nothing to walk
+    if walk_options.max_depth != Some(0) {
+        state.steps.push(WalkStep::Visit {
+            path: path.to_os_string(),
+            depth: 0,
+        });
+    }
+
+    stream::try_unfold(state, |mut state| async move {
+        Ok(state.next_entry().await?.map(|walk_entry| (walk_entry, state)))
+    })
+    .boxed()
+}
+
+fn compile_patterns(patterns: &[String]) -> io::Result<Vec<GlobPattern>> {
+    patterns
+        .iter()
+        .map(|pattern| GlobPattern::new(pattern).map_err(io::Error::from))
+        .collect()
+}
+
+impl<F> WalkState<'_, F>
+where
+    F: LinuxFilesystem + Sync + ?Sized,
+{
+    async fn next_entry(&mut self) -> io::Result<Option<LinuxWalkEntry>> {
+        while let Some(step) = self.steps.pop() {
+            match step {
+                WalkStep::Yield(walk_entry) => return Ok(Some(*walk_entry)),
+                WalkStep::Visit { path, depth } => self.visit(path, depth).await?,
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn visit(&mut self, path: OsString, depth: usize) -> io::Result<()> {
+        if self.walk_options.follow_symlinks {
+            let canonical_path = self.filesystem.canonicalize(&path).await?;
+            if !self.visited_dirs.insert(canonical_path) {
+                return Ok(());
+            }
+        }
+
+        let mut children = self.filesystem.list_dir_with_metadata(&path).await?;
+        children.sort_by(|(first, _), (second, _)| first.name.cmp(&second.name));
+
+        if self.walk_options.follow_symlinks {
+            // resolve all symlink targets of the directory at once, dangling symlinks keep their own metadata
+            let filesystem = self.filesystem;
+            let targets = join_all(children.iter().map(|(dir_entry, _)| async move {
+                match dir_entry.file_type {
+                    LinuxFileType::Symlink => filesystem.get_metadata(&dir_entry.path).await.ok(),
+                    _ => None,
+                }
+            }))
+            .await;
+            for ((_, metadata), target_metadata) in children.iter_mut().zip(targets) {
+                if let Some(target_metadata) = target_metadata {
+                    *metadata = target_metadata;
+                }
+            }
+        }
+
+        let child_depth = depth + 1;
+        // pushed in reverse, so that the first child ends up on top of the stack
+        for (dir_entry, metadata) in children.into_iter().rev() {
+            let relative_path = Path::new(&dir_entry.path)
+                .strip_prefix(&self.root)
+                .map(|relative_path| relative_path.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| dir_entry.name.to_string_lossy().into_owned());
+            if self
+                .exclude_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&relative_path))
+            {
+                continue;
+            }
+
+            let file_type = metadata.file_type.unwrap_or(dir_entry.file_type);
+            let should_yield = (self.include_patterns.is_empty()
+                || self
+                    .include_patterns
+                    .iter()
+                    .any(|pattern| pattern.is_match(&relative_path)))
+                && (self.walk_options.file_types.is_empty() || self.walk_options.file_types.contains(&file_type));
+            let should_descend = file_type == LinuxFileType::Dir
+                && !matches!(self.walk_options.max_depth, Some(max_depth) if child_depth >= max_depth);
+
+            let child_path = dir_entry.path.clone();
+            let walk_entry = LinuxWalkEntry {
+                dir_entry,
+                metadata,
+                depth: child_depth,
+            };
+
+            match self.walk_options.order {
+                LinuxWalkOrder::PreOrder => {
+                    if should_descend {
+                        self.steps.push(WalkStep::Visit {
+                            path: child_path,
+                            depth: child_depth,
+                        });
+                    }
+                    if should_yield {
+                        self.steps.push(WalkStep::Yield(Box::new(walk_entry)));
+                    }
+                }
+                LinuxWalkOrder::PostOrder => {
+                    if should_yield {
+                        self.steps.push(WalkStep::Yield(Box::new(walk_entry)));
+                    }
+                    if should_descend {
+                        self.steps.push(WalkStep::Visit {
+                            path: child_path,
+                            depth: child_depth,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
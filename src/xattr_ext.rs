@@ -0,0 +1,112 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    os::unix::ffi::OsStrExt,
+};
+
+use crate::checksum;
+
+// the exit status of a shell when the command can't be found
+const COMMAND_NOT_FOUND_STATUS: u32 = 127;
+
+// the arguments of a getfattr invocation dumping all attributes of all namespaces as hex
+pub(crate) fn dump_args(path: &str) -> Vec<&str> {
+    vec!["--absolute-names", "-d", "-m", "-", "-e", "hex", "--", path]
+}
+
+pub(crate) fn set_args(path: &str, name: &str, value: &[u8]) -> Vec<String> {
+    let mut args = vec!["-n".to_string(), name.to_string()];
+    // setfattr can't take an empty hex value, but sets an empty value when none is given
+    if !value.is_empty() {
+        args.extend(["-v".to_string(), format!("0x{}", checksum::encode_hex(value))]);
+    }
+    args.extend(["--".to_string(), path.to_string()]);
+    args
+}
+
+pub(crate) fn remove_args<'a>(path: &'a str, name: &'a str) -> Vec<&'a str> {
+    vec!["-x", name, "--", path]
+}
+
+// turns a missing tool, such as getfattr or setfacl, into an unsupported error instead of a generic failure
+pub(crate) fn status_error(feature: &str, program: &str, status_code: Option<u32>) -> io::Error {
+    match status_code {
+        Some(COMMAND_NOT_FOUND_STATUS) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} are unsupported, since {} isn't installed on the host",
+                feature, program
+            ),
+        ),
+        _ => io::Error::other(format!("{} exited with status code {:?}", program, status_code)),
+    }
+}
+
+// parses "name=0xHEX" lines, skipping the "# file:" comments. Names have special characters escaped as octal
+pub(crate) fn parse_dump(output: &[u8]) -> io::Result<Vec<(OsString, Vec<u8>)>> {
+    let mut xattrs = Vec::new();
+
+    for line in output.split(|byte| *byte == b'\n') {
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let (name, value) = match line.iter().position(|byte| *byte == b'=') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => (line, &b""[..]),
+        };
+        let value = match value {
+            [] | b"\"\"" => Vec::new(),
+            _ => decode_hex(value.strip_prefix(b"0x").unwrap_or(value))?,
+        };
+        xattrs.push((OsStr::from_bytes(&unescape_name(name)).to_os_string(), value));
+    }
+
+    Ok(xattrs)
+}
+
+fn decode_hex(hex: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid hex value: {}", String::from_utf8_lossy(hex)),
+        )
+    };
+    if hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+
+    hex.chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+fn unescape_name(name: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(name.len());
+    let mut index = 0;
+    while index < name.len() {
+        let escaped = name
+            .get(index + 1..index + 4)
+            .filter(|digits| name[index] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit)))
+            .map(|digits| {
+                digits
+                    .iter()
+                    .fold(0u32, |value, digit| value * 8 + u32::from(digit - b'0'))
+            });
+        match escaped {
+            Some(value) if value <= 0xff => {
+                output.push(value as u8);
+                index += 4;
+            }
+            _ => {
+                output.push(name[index]);
+                index += 1;
+            }
+        }
+    }
+    output
+}
@@ -7,7 +7,7 @@ use async_trait::async_trait;
 use openssh::{Session, SessionBuilder};
 use openssh_sftp_client::{Sftp, SftpOptions};
 use remoteify::{
-    filesystem::{LinuxDirEntry, LinuxFileType},
+    filesystem::{LinuxAcl, LinuxAclEntry, LinuxAclPermissions, LinuxAclTag, LinuxDirEntry, LinuxFileType},
     impl_openssh::OpensshLinux,
     impl_russh::{
         connection::{RusshAuthentication, RusshConnectionOptions},
@@ -25,7 +25,7 @@ use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 pub fn gen_tmp_path() -> OsString {
-    format!("/tmp/{}", Uuid::new_v4().to_string()).into()
+    format!("/tmp/{}", Uuid::new_v4()).into()
 }
 
 #[allow(unused)]
@@ -60,18 +60,15 @@ impl RusshData {
 
         let mut handle_option: Option<Handle<AcceptingHandler>> = None;
         loop {
-            match client::connect(
+            if let Ok(handle) = client::connect(
                 Arc::new(Config::default()),
                 ("localhost", ssh_port),
                 AcceptingHandler {},
             )
             .await
             {
-                Ok(handle) => {
-                    handle_option = Some(handle);
-                    break;
-                }
-                Err(_) => {}
+                handle_option = Some(handle);
+                break;
             }
         }
 
@@ -220,22 +217,59 @@ async fn get_ssh_port() -> u16 {
                 .await
                 .expect("Could not start SSH container");
             let ports = container.ports().await.expect("Could not get SSH container ports");
-            let port = ports
+
+            ports
                 .map_to_host_port_ipv4(ContainerPort::Tcp(22))
-                .expect("Could not get SSH container port corresponding to 22");
-            port
+                .expect("Could not get SSH container port corresponding to 22")
         })
         .await;
     *ssh_port
 }
 
 #[allow(unused)]
-pub fn entries_contain(entries: &Vec<LinuxDirEntry>, expected_type: LinuxFileType, expected_path: &OsStr) {
+pub fn entries_contain(entries: &[LinuxDirEntry], expected_type: LinuxFileType, expected_path: &OsStr) {
     assert!(entries
         .iter()
         .any(|entry| { matches!(entry.file_type, expected_type) && entry.path.as_os_str() == expected_path }))
 }
 
+// an ACL of a directory granting a named user and group access, which new entries inside it inherit
+#[allow(unused)]
+pub fn extended_acl() -> LinuxAcl {
+    let entries = vec![
+        acl_entry(LinuxAclTag::UserOwner, 0o7),
+        acl_entry(LinuxAclTag::User(1000), 0o6),
+        acl_entry(LinuxAclTag::GroupOwner, 0o5),
+        acl_entry(LinuxAclTag::Group(1000), 0o4),
+        acl_entry(LinuxAclTag::Mask, 0o7),
+        acl_entry(LinuxAclTag::Other, 0o0),
+    ];
+    LinuxAcl {
+        access: entries.clone(),
+        default: entries,
+    }
+}
+
+// the ACL equivalent to a mode of 0o750
+#[allow(unused)]
+pub fn mode_acl() -> LinuxAcl {
+    LinuxAcl {
+        access: vec![
+            acl_entry(LinuxAclTag::UserOwner, 0o7),
+            acl_entry(LinuxAclTag::GroupOwner, 0o5),
+            acl_entry(LinuxAclTag::Other, 0o0),
+        ],
+        default: Vec::new(),
+    }
+}
+
+fn acl_entry(tag: LinuxAclTag, bits: u16) -> LinuxAclEntry {
+    LinuxAclEntry {
+        tag,
+        permissions: LinuxAclPermissions::from_bits(bits).unwrap(),
+    }
+}
+
 #[derive(Debug)]
 pub struct AcceptingHandler {}
 
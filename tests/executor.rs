@@ -2,7 +2,9 @@ use common::{OpensshData, RusshData};
 use futures::{future::BoxFuture, FutureExt};
 use nix::sys::signal::Signal;
 use remoteify::{
-    executor::{FinishedLinuxProcessOutput, LinuxExecutor, LinuxProcessConfiguration},
+    executor::{
+        FinishedLinuxProcessOutput, LinuxExecutor, LinuxOutputTarget, LinuxProcessConfiguration, LinuxShellMode,
+    },
     impl_native::NativeLinux,
 };
 use uuid::Uuid;
@@ -190,6 +192,81 @@ async fn interactive_command_with_env_vars_and_working_dir() {
     .await;
 }
 
+#[tokio::test]
+async fn interactive_command_with_full_and_piped_stdin_writes() {
+    executor_test(|executor| {
+        async move {
+            let mut config = LinuxProcessConfiguration::new("/usr/bin/bash");
+            config.redirect_stdout().redirect_stdin().redirect_stderr();
+            let mut process = executor.begin_execute(&config).await.unwrap();
+            process.write_all_to_stdin(b"echo first\n").await.unwrap();
+            let mut reader: &[u8] = b"echo second\necho third\n";
+            let piped = process.pipe_stdin_from(&mut reader).await.unwrap();
+            assert_eq!(piped, 23);
+            process.close_stdin().await.unwrap();
+            let process_output = process.await_exit_with_output().await.unwrap();
+            assert_ok_execution(process_output, "first\nsecond\nthird\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn simple_command_with_stdin_from_file() {
+    executor_test(|executor| {
+        async move {
+            let path = format!("/tmp/{}", Uuid::new_v4());
+            let mut write_config = LinuxProcessConfiguration::new("/usr/bin/bash");
+            write_config.args(vec!["-c", format!("echo content > {}", path).as_str()]);
+            executor.execute(&write_config).await.unwrap();
+
+            let mut config = LinuxProcessConfiguration::new("/usr/bin/cat");
+            config.redirect_stdout().redirect_stderr().stdin_file(path);
+            let process_output = executor.execute(&config).await.unwrap();
+            assert_ok_execution(process_output, "content\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn simple_command_with_output_redirected_to_files() {
+    executor_test(|executor| {
+        async move {
+            let stdout_path = format!("/tmp/{}", Uuid::new_v4());
+            let stderr_path = format!("/tmp/{}", Uuid::new_v4());
+            let mut config = LinuxProcessConfiguration::new("/usr/bin/bash");
+            config
+                .stdout_target(LinuxOutputTarget::File {
+                    path: stdout_path.clone(),
+                    append: false,
+                })
+                .stderr_target(LinuxOutputTarget::File {
+                    path: stderr_path.clone(),
+                    append: true,
+                })
+                .args(vec!["-c", "echo stdout && echo stderr >&2"]);
+            for _ in 0..2 {
+                let process_output = executor.execute(&config).await.unwrap();
+                assert_eq!(process_output.status_code, Some(0));
+                assert!(process_output.stdout.is_empty());
+                assert!(process_output.stderr.is_empty());
+            }
+
+            let mut read_config = LinuxProcessConfiguration::new("/usr/bin/cat");
+            read_config.redirect_stdout().redirect_stderr().arg(stdout_path);
+            assert_ok_execution(executor.execute(&read_config).await.unwrap(), "stdout\n");
+            let mut read_config = LinuxProcessConfiguration::new("/usr/bin/cat");
+            read_config.redirect_stdout().redirect_stderr().arg(stderr_path);
+            assert_ok_execution(executor.execute(&read_config).await.unwrap(), "stderr\nstderr\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn interactive_command_receiving_sigterm() {
     executor_test(|executor| {
@@ -207,6 +284,99 @@ async fn interactive_command_receiving_sigterm() {
     .await;
 }
 
+#[tokio::test]
+async fn simple_command_inside_login_shell() {
+    executor_test(|executor| {
+        async move {
+            let mut config = LinuxProcessConfiguration::new("echo");
+            config
+                .redirect_stdout()
+                .redirect_stderr()
+                .shell_mode(LinuxShellMode::BashLogin)
+                .working_dir("/tmp")
+                .args(vec!["$HOME", "two words"]);
+            let process_output = executor.execute(&config).await.unwrap();
+            assert_ok_execution(process_output, "$HOME two words\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn simple_command_inside_custom_shell() {
+    executor_test(|executor| {
+        async move {
+            let mut config = LinuxProcessConfiguration::new("printenv");
+            config
+                .redirect_stdout()
+                .redirect_stderr()
+                .shell_mode(LinuxShellMode::Custom {
+                    shell: "/usr/bin/bash".into(),
+                    args: vec!["-c".into()],
+                })
+                .env("ENV_KEY", "ENV_VALUE")
+                .arg("ENV_KEY");
+            let process_output = executor.execute(&config).await.unwrap();
+            assert_ok_execution(process_output, "ENV_VALUE\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn simple_command_with_captured_extra_fds() {
+    executor_test(|executor| {
+        async move {
+            let mut config = LinuxProcessConfiguration::new("/usr/bin/bash");
+            config
+                .redirect_stdout()
+                .redirect_stderr()
+                .capture_fd(3)
+                .capture_fd(4)
+                .args(vec!["-c", "echo stdout && echo three >&3 && echo four >&4"]);
+            let process_output = executor.execute(&config).await.unwrap();
+            assert_eq!(process_output.stdout_extended.get(&3).unwrap(), b"three\n");
+            assert_eq!(process_output.stdout_extended.get(&4).unwrap(), b"four\n");
+            assert_ok_execution(process_output, "stdout\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn native_execution_of_missing_program_with_captured_fds_should_fail() {
+    let mut config = LinuxProcessConfiguration::new(format!("/tmp/{}", Uuid::new_v4()));
+    // the pipes of the captured fds take up the lowest free fds, which is where std's exec error pipe would be created
+    // next, on one of the upper captured fds
+    for fd in 150..250 {
+        config.capture_fd(fd);
+    }
+    assert!(NativeLinux::new().execute(&config).await.is_err());
+}
+
+#[tokio::test]
+async fn script_execution_with_args_env_vars_and_working_dir() {
+    executor_test(|executor| {
+        async move {
+            let mut config = LinuxProcessConfiguration::new("ignored");
+            config
+                .redirect_stdout()
+                .redirect_stderr()
+                .working_dir("/tmp")
+                .env("ENV", "VAL")
+                .args(vec!["first", "second"]);
+            let script = "pwd\necho $ENV\nfor arg in \"$@\"; do\n  echo $arg\ndone\n";
+            let process_output = executor.execute_script("/usr/bin/bash", script, &config).await.unwrap();
+            assert_ok_execution(process_output, "/tmp\nVAL\nfirst\nsecond\n");
+        }
+        .boxed()
+    })
+    .await;
+}
+
 fn assert_ok_execution(process_output: FinishedLinuxProcessOutput, expectation: &str) {
     assert_eq!(process_output.status_code, Some(0));
     assert!(process_output.stderr.is_empty());
@@ -219,7 +389,7 @@ where
     F: FnOnce(Box<dyn LinuxExecutor + Send + '_>) -> BoxFuture<()>,
     F: Copy,
 {
-    let native = NativeLinux {};
+    let native = NativeLinux::new();
     let russh_data = RusshData::setup().await;
     let openssh_data = OpensshData::setup().await;
 
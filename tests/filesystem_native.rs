@@ -1,24 +1,39 @@
 use std::{
-    ffi::OsString,
-    fs::Metadata,
-    os::unix::fs::{MetadataExt, PermissionsExt},
+    ffi::{OsStr, OsString},
+    fs::{Metadata, Permissions},
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
 };
 
-use common::{entries_contain, gen_nested_tmp_path, gen_tmp_path};
+use common::{entries_contain, extended_acl, gen_nested_tmp_path, gen_tmp_path, mode_acl};
+use futures::TryStreamExt;
+use nix::errno::Errno;
 use remoteify::{
-    filesystem::{LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions},
+    archive::{download_dir, upload_dir, LinuxArchiveCompression, LinuxArchiveOptions},
+    filesystem::{
+        LinuxChecksumAlgorithm, LinuxDirEntry, LinuxFile, LinuxFileMetadata, LinuxFileType, LinuxFilesystem,
+        LinuxLockMode, LinuxOpenOptions, LinuxPermissions, LinuxWalkEntry, LinuxWalkOptions, LinuxWalkOrder,
+    },
     impl_native::NativeLinux,
+    sync::{sync_dir, LinuxSyncComparison, LinuxSyncOptions, LinuxSyncReport},
+    transfer::{
+        transfer, transfer_file, LinuxCopyOptions, LinuxCopyReport, LinuxTransferOptions, LinuxTransferVerification,
+    },
 };
 use tokio::{
     fs::{
-        create_dir, create_dir_all, metadata, read_to_string, remove_dir, remove_dir_all, remove_file, symlink,
-        symlink_metadata, try_exists, write, File,
+        create_dir, create_dir_all, metadata, read, read_link, read_to_string, remove_dir, remove_dir_all, remove_file,
+        set_permissions, symlink, symlink_metadata, try_exists, write, File,
     },
     io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixListener,
 };
 mod common;
 
-static IMPL: NativeLinux = NativeLinux {};
+static IMPL: NativeLinux = NativeLinux::new();
 
 #[tokio::test]
 async fn exists_is_false_for_missing_item() {
@@ -47,7 +62,7 @@ async fn open_file_with_read_should_work() {
     let path = gen_tmp_path();
     write(&path, b"content").await.unwrap();
     let mut handle = IMPL
-        .open_file(&path, &LinuxOpenOptions::new().read())
+        .open_file(&path, LinuxOpenOptions::new().read())
         .await
         .expect("Call failed");
     let mut buf = String::new();
@@ -61,7 +76,7 @@ async fn open_file_with_write_should_work() {
     let path = gen_tmp_path();
     write(&path, b"content").await.unwrap();
     let mut handle = IMPL
-        .open_file(&path, &LinuxOpenOptions::new().write())
+        .open_file(&path, LinuxOpenOptions::new().write())
         .await
         .expect("Call failed");
     handle.write_all(b"CON").await.unwrap();
@@ -74,7 +89,7 @@ async fn open_file_with_append_should_work() {
     let path = gen_tmp_path();
     write(&path, "first").await.unwrap();
     let mut handle = IMPL
-        .open_file(&path, &LinuxOpenOptions::new().append())
+        .open_file(&path, LinuxOpenOptions::new().append())
         .await
         .expect("Call failed");
     handle.write_all(b"second").await.unwrap();
@@ -87,7 +102,7 @@ async fn open_file_with_truncate_should_work() {
     let path = gen_tmp_path();
     write(&path, "first").await.unwrap();
     let mut handle = IMPL
-        .open_file(&path, &LinuxOpenOptions::new().write().truncate())
+        .open_file(&path, LinuxOpenOptions::new().write().truncate())
         .await
         .expect("Call failed");
     handle.write_all(b"second").await.unwrap();
@@ -99,7 +114,7 @@ async fn open_file_with_truncate_should_work() {
 async fn open_file_with_create_should_work() {
     let path = gen_tmp_path();
     let mut handle = IMPL
-        .open_file(&path, &LinuxOpenOptions::new().create().write())
+        .open_file(&path, LinuxOpenOptions::new().create().write())
         .await
         .expect("Call failed");
     handle.write_all(b"content").await.unwrap();
@@ -107,6 +122,92 @@ async fn open_file_with_create_should_work() {
     remove_file(&path).await.unwrap();
 }
 
+#[tokio::test]
+async fn open_file_with_create_new_and_mode_should_work() {
+    let path = gen_tmp_path();
+    IMPL.open_file(
+        &path,
+        LinuxOpenOptions::new()
+            .write()
+            .create_new()
+            .mode(LinuxPermissions::from_bits(0o600).unwrap()),
+    )
+    .await
+    .expect("Call failed");
+    assert_eq!(metadata(&path).await.unwrap().mode() & 0o777, 0o600);
+    assert_eq!(
+        IMPL.open_file(&path, LinuxOpenOptions::new().write().create_new())
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::AlreadyExists)
+    );
+    remove_file(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn open_file_with_no_follow_and_directory_should_check_type() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+    let link_path = gen_tmp_path();
+    symlink(&path, &link_path).await.unwrap();
+    assert!(IMPL
+        .open_file(&link_path, LinuxOpenOptions::new().read().no_follow())
+        .await
+        .is_err());
+    assert!(IMPL
+        .open_file(&path, LinuxOpenOptions::new().read().no_follow().sync())
+        .await
+        .is_ok());
+    assert!(IMPL
+        .open_file(&path, LinuxOpenOptions::new().read().directory())
+        .await
+        .is_err());
+    let dir_handle = IMPL
+        .open_file(OsStr::new("/tmp"), LinuxOpenOptions::new().read().directory())
+        .await
+        .expect("Call failed");
+    dir_handle.sync_all().await.expect("Call failed");
+}
+
+#[tokio::test]
+async fn opened_file_should_support_positional_io() {
+    let path = gen_tmp_path();
+    write(&path, b"content").await.unwrap();
+    let mut handle: Box<dyn LinuxFile> = Box::new(
+        IMPL.open_file(&path, LinuxOpenOptions::new().read().write())
+            .await
+            .expect("Call failed"),
+    );
+    handle.write_all(b"C").await.unwrap();
+    assert_eq!(handle.write_at(b"T", 3).await.unwrap(), 1);
+    let mut buf = [0u8; 3];
+    assert_eq!(handle.read_at(&mut buf, 3).await.unwrap(), 3);
+    assert_eq!(&buf, b"Ten");
+    // the cursor stays behind the first write
+    let mut rest = String::new();
+    handle.read_to_string(&mut rest).await.unwrap();
+    assert_eq!(rest, "onTent");
+    handle.set_len(4).await.expect("Call failed");
+    assert_eq!(handle.metadata().await.unwrap().size, Some(4));
+    handle.sync_all().await.expect("Call failed");
+    handle.close().await.expect("Call failed");
+    assert_eq!(read_to_string(&path).await.unwrap(), "ConT");
+}
+
+#[tokio::test]
+async fn closing_file_should_report_write_errors() {
+    let mut handle: Box<dyn LinuxFile> = Box::new(
+        IMPL.open_file(OsStr::new("/dev/full"), LinuxOpenOptions::new().write())
+            .await
+            .expect("Call failed"),
+    );
+    // the write happens in the background, so its error only shows up when closing
+    handle.write_all(b"content").await.unwrap();
+    let error = handle.close().await.expect_err("Call succeeded");
+    assert_eq!(error.raw_os_error(), Some(Errno::ENOSPC as i32));
+}
+
 #[tokio::test]
 async fn create_file_should_persist() {
     let path = gen_tmp_path();
@@ -115,6 +216,47 @@ async fn create_file_should_persist() {
     remove_file(&path).await.unwrap();
 }
 
+#[tokio::test]
+async fn write_file_and_read_file_should_roundtrip() {
+    let path = gen_tmp_path();
+    IMPL.write_file(&path, b"first content").await.expect("Call failed");
+    IMPL.write_file(&path, b"second").await.expect("Call failed");
+    assert_eq!(IMPL.read_file(&path).await.expect("Call failed"), b"second");
+    assert_eq!(IMPL.read_to_string(&path).await.expect("Call failed"), "second");
+
+    write(&path, [0xff, 0xfe]).await.unwrap();
+    assert_eq!(
+        IMPL.read_to_string(&path).await.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+    remove_file(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn write_file_atomic_should_replace_file_and_keep_mode() {
+    let dir_path = gen_tmp_path();
+    create_dir(&dir_path).await.unwrap();
+    let path = Path::new(&dir_path).join("config");
+    write(&path, "old content").await.unwrap();
+    set_permissions(&path, Permissions::from_mode(0o640)).await.unwrap();
+
+    IMPL.write_file_atomic(path.as_os_str(), b"new content")
+        .await
+        .expect("Call failed");
+
+    assert_eq!(read_to_string(&path).await.unwrap(), "new content");
+    assert_eq!(metadata(&path).await.unwrap().permissions().mode() & 0o777, 0o640);
+    // the temporary sibling was renamed away
+    assert_eq!(IMPL.list_dir(&dir_path).await.unwrap().len(), 1);
+
+    let new_path = Path::new(&dir_path).join("new");
+    IMPL.write_file_atomic(new_path.as_os_str(), b"created")
+        .await
+        .expect("Call failed");
+    assert_eq!(read_to_string(&new_path).await.unwrap(), "created");
+    remove_dir_all(&dir_path).await.unwrap();
+}
+
 #[tokio::test]
 async fn rename_file_should_persist() {
     let old_path = gen_tmp_path();
@@ -158,6 +300,52 @@ async fn symlink_should_establish_link() {
     remove_file(&dst_path).await.unwrap();
 }
 
+#[tokio::test]
+async fn create_fifo_should_establish_pipe_with_exact_permissions() {
+    let path = gen_tmp_path();
+    IMPL.create_fifo(&path, LinuxPermissions::from_bits(0o666).unwrap())
+        .await
+        .expect("Call failed");
+    let metadata = symlink_metadata(&path).await.unwrap();
+    assert!(metadata.file_type().is_fifo());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o666);
+    assert_eq!(
+        IMPL.get_metadata(&path).await.unwrap().file_type,
+        Some(LinuxFileType::Fifo)
+    );
+    remove_file(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn create_device_node_should_reject_non_device_types() {
+    let error = IMPL
+        .create_device_node(
+            &gen_tmp_path(),
+            LinuxFileType::File,
+            1,
+            3,
+            LinuxPermissions::from_bits(0o600).unwrap(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn special_file_types_should_be_told_apart() {
+    let socket_path = gen_tmp_path();
+    let _listener = UnixListener::bind(&socket_path).unwrap();
+    assert_eq!(
+        IMPL.get_metadata(&socket_path).await.unwrap().file_type,
+        Some(LinuxFileType::Socket)
+    );
+    assert_eq!(
+        IMPL.get_metadata(OsStr::new("/dev/null")).await.unwrap().file_type,
+        Some(LinuxFileType::CharDevice)
+    );
+    remove_file(&socket_path).await.unwrap();
+}
+
 #[tokio::test]
 async fn hard_link_should_establish_link() {
     let src_path = gen_tmp_path();
@@ -193,6 +381,594 @@ async fn set_permissions_should_perform_update() {
     remove_file(&path).await.unwrap();
 }
 
+#[tokio::test]
+async fn set_times_should_perform_update() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+    let accessed_time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let modified_time = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    IMPL.set_times(&path, accessed_time, modified_time)
+        .await
+        .expect("Call failed");
+    let meta = metadata(&path).await.unwrap();
+    assert_eq!(meta.accessed().unwrap(), accessed_time);
+    assert_eq!(meta.modified().unwrap(), modified_time);
+}
+
+#[tokio::test]
+async fn set_owner_should_keep_unspecified_ids() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+    let meta = metadata(&path).await.unwrap();
+    IMPL.set_owner(&path, None, Some(meta.gid()))
+        .await
+        .expect("Call failed");
+    let new_meta = metadata(&path).await.unwrap();
+    assert_eq!(new_meta.uid(), meta.uid());
+    assert_eq!(new_meta.gid(), meta.gid());
+}
+
+#[tokio::test]
+async fn set_symlink_owner_should_not_follow_symlink() {
+    let path = gen_tmp_path();
+    // a dangling symlink, so that following it would fail
+    symlink(gen_tmp_path(), &path).await.unwrap();
+    let meta = symlink_metadata(&path).await.unwrap();
+    IMPL.set_symlink_owner(&path, Some(meta.uid()), Some(meta.gid()))
+        .await
+        .expect("Call failed");
+    let new_meta = symlink_metadata(&path).await.unwrap();
+    assert_eq!(new_meta.uid(), meta.uid());
+    assert_eq!(new_meta.gid(), meta.gid());
+    remove_file(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn set_len_should_truncate_and_extend() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+    IMPL.set_len(&path, 3).await.expect("Call failed");
+    assert_eq!(read(&path).await.unwrap(), b"con");
+    IMPL.set_len(&path, 5).await.expect("Call failed");
+    assert_eq!(read(&path).await.unwrap(), b"con\0\0");
+}
+
+#[tokio::test]
+async fn allocate_should_reserve_blocks() {
+    let path = gen_tmp_path();
+    write(&path, "").await.unwrap();
+    IMPL.allocate(&path, 0, 65536).await.expect("Call failed");
+    let meta = metadata(&path).await.unwrap();
+    assert_eq!(meta.len(), 65536);
+    assert!(meta.blocks() * 512 >= 65536);
+}
+
+#[tokio::test]
+async fn sync_operations_should_succeed() {
+    let path = gen_tmp_path();
+    create_dir(&path).await.unwrap();
+    let file_path = Path::new(&path).join("file");
+    write(&file_path, "content").await.unwrap();
+    IMPL.sync_file(file_path.as_os_str()).await.expect("Call failed");
+    IMPL.sync_file_data(file_path.as_os_str()).await.expect("Call failed");
+    IMPL.sync_dir(&path).await.expect("Call failed");
+}
+
+#[tokio::test]
+async fn acls_should_round_trip() {
+    let path = gen_tmp_path();
+    create_dir(&path).await.unwrap();
+    IMPL.set_acl(&path, &extended_acl()).await.expect("Call failed");
+    assert_eq!(IMPL.get_acl(&path).await.unwrap(), extended_acl());
+    // without named entries and a default ACL, only the mode remains
+    IMPL.set_acl(&path, &mode_acl()).await.expect("Call failed");
+    assert_eq!(IMPL.get_acl(&path).await.unwrap(), mode_acl());
+    assert_eq!(
+        IMPL.get_metadata(&path).await.unwrap().permissions.unwrap().bits() & 0o777,
+        0o750
+    );
+}
+
+#[tokio::test]
+async fn lock_file_should_exclude_conflicting_locks() {
+    let path = gen_tmp_path();
+    let timeout = Some(Duration::from_millis(100));
+    let lock = IMPL
+        .lock_file(&path, LinuxLockMode::Exclusive, None)
+        .await
+        .expect("Call failed");
+    assert!(Path::new(&path).exists());
+    assert_eq!(
+        IMPL.lock_file(&path, LinuxLockMode::Shared, timeout)
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::TimedOut)
+    );
+    drop(lock);
+
+    let shared_lock = IMPL.lock_file(&path, LinuxLockMode::Shared, timeout).await.unwrap();
+    let other_shared_lock = IMPL.lock_file(&path, LinuxLockMode::Shared, timeout).await.unwrap();
+    assert_eq!(
+        IMPL.lock_file(&path, LinuxLockMode::Exclusive, Some(Duration::ZERO))
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::TimedOut)
+    );
+    shared_lock.unlock().await.expect("Call failed");
+    other_shared_lock.unlock().await.expect("Call failed");
+    IMPL.lock_file(&path, LinuxLockMode::Exclusive, Some(Duration::ZERO))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn xattrs_should_round_trip() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+    let name = OsStr::new("user.remoteify");
+    IMPL.set_xattr(&path, name, &[0, 255, b'a']).await.expect("Call failed");
+    assert_eq!(IMPL.get_xattr(&path, name).await.unwrap(), Some(vec![0, 255, b'a']));
+    assert!(IMPL.list_xattrs(&path).await.unwrap().contains(&name.to_os_string()));
+    IMPL.remove_xattr(&path, name).await.expect("Call failed");
+    assert_eq!(IMPL.get_xattr(&path, name).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn copy_dir_recursively_should_preserve_tree() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("file"), "content").await.unwrap();
+    write(src.join("nested/script"), "#!/bin/sh").await.unwrap();
+    set_permissions(src.join("nested/script"), Permissions::from_mode(0o750))
+        .await
+        .unwrap();
+    symlink("nested/script", src.join("link")).await.unwrap();
+    let modified_time = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    IMPL.set_times(src.join("file").as_os_str(), modified_time, modified_time)
+        .await
+        .unwrap();
+
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let report = IMPL
+        .copy_dir_recursively(&src_path, &dst_path, LinuxCopyOptions::new().preserve_times(true))
+        .await
+        .expect("Call failed");
+
+    assert_eq!(
+        report,
+        LinuxCopyReport {
+            files: 2,
+            dirs: 2,
+            symlinks: 1,
+            bytes: 16
+        }
+    );
+    assert_eq!(read_to_string(dst.join("file")).await.unwrap(), "content");
+    assert_eq!(
+        metadata(dst.join("file")).await.unwrap().modified().unwrap(),
+        modified_time
+    );
+    assert_eq!(
+        metadata(dst.join("nested/script")).await.unwrap().permissions().mode() & 0o777,
+        0o750
+    );
+    assert_eq!(read_link(dst.join("link")).await.unwrap(), Path::new("nested/script"));
+}
+
+#[tokio::test]
+async fn transfer_should_copy_between_instances() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("nested/file"), "content").await.unwrap();
+    symlink("nested/file", src.join("link")).await.unwrap();
+
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let report = transfer(
+        &IMPL,
+        &src_path,
+        &NativeLinux::new(),
+        &dst_path,
+        LinuxCopyOptions::new().preserve_symlinks(false),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.files, 2);
+    assert_eq!(report.symlinks, 0);
+    assert_eq!(read_to_string(dst.join("nested/file")).await.unwrap(), "content");
+    assert!(!symlink_metadata(dst.join("link")).await.unwrap().is_symlink());
+}
+
+#[tokio::test]
+async fn upload_dir_should_match_copy_dir_recursively() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    let long_name = "d".repeat(120);
+    create_dir_all(src.join("nested").join(&long_name)).await.unwrap();
+    write(src.join("file"), "content").await.unwrap();
+    write(src.join("nested").join(&long_name).join("script"), "#!/bin/sh")
+        .await
+        .unwrap();
+    set_permissions(
+        src.join("nested").join(&long_name).join("script"),
+        Permissions::from_mode(0o750),
+    )
+    .await
+    .unwrap();
+    symlink("file", src.join("link")).await.unwrap();
+    let modified_time = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    IMPL.set_times(src.join("file").as_os_str(), modified_time, modified_time)
+        .await
+        .unwrap();
+
+    let copy_options = *LinuxCopyOptions::new().preserve_times(true);
+    let expected_report = IMPL
+        .copy_dir_recursively(&src_path, &gen_tmp_path(), &copy_options)
+        .await
+        .unwrap();
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let report = upload_dir(
+        &IMPL,
+        &src_path,
+        &NativeLinux::new(),
+        &dst_path,
+        LinuxArchiveOptions::new().copy_options(&copy_options),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report, expected_report);
+    assert_eq!(read_to_string(dst.join("file")).await.unwrap(), "content");
+    assert_eq!(
+        metadata(dst.join("file")).await.unwrap().modified().unwrap(),
+        modified_time
+    );
+    assert_eq!(
+        metadata(dst.join("nested").join(&long_name).join("script"))
+            .await
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777,
+        0o750
+    );
+    assert_eq!(read_link(dst.join("link")).await.unwrap(), Path::new("file"));
+}
+
+#[tokio::test]
+async fn download_dir_should_match_copy_dir_recursively() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    let long_name = "f".repeat(150);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("nested").join(&long_name), "content").await.unwrap();
+    write(src.join("empty"), "").await.unwrap();
+    symlink("nested", src.join("link")).await.unwrap();
+    let modified_time = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    IMPL.set_times(src.join("empty").as_os_str(), modified_time, modified_time)
+        .await
+        .unwrap();
+
+    for compression in [LinuxArchiveCompression::None, LinuxArchiveCompression::Gzip] {
+        let copy_options = *LinuxCopyOptions::new().preserve_times(true).preserve_symlinks(false);
+        let expected_report = IMPL
+            .copy_dir_recursively(&src_path, &gen_tmp_path(), &copy_options)
+            .await
+            .unwrap();
+        let dst_path = gen_tmp_path();
+        let dst = Path::new(&dst_path);
+        let report = download_dir(
+            &IMPL,
+            &src_path,
+            &NativeLinux::new(),
+            &dst_path,
+            LinuxArchiveOptions::new()
+                .compression(compression)
+                .copy_options(&copy_options),
+        )
+        .await
+        .expect("Call failed");
+
+        assert_eq!(report, expected_report);
+        assert_eq!(
+            read_to_string(dst.join("nested").join(&long_name)).await.unwrap(),
+            "content"
+        );
+        assert_eq!(
+            read_to_string(dst.join("link").join(&long_name)).await.unwrap(),
+            "content"
+        );
+        assert!(!symlink_metadata(dst.join("link")).await.unwrap().is_symlink());
+        assert_eq!(
+            metadata(dst.join("empty")).await.unwrap().modified().unwrap(),
+            modified_time
+        );
+    }
+}
+
+#[tokio::test]
+async fn transfer_file_should_copy_parallel_chunks_with_progress() {
+    let src_path = gen_tmp_path();
+    let content = (0..10_000).map(|index| (index % 251) as u8).collect::<Vec<_>>();
+    write(&src_path, &content).await.unwrap();
+    let dst_path = gen_tmp_path();
+    let progress = Arc::new(Mutex::new(Vec::new()));
+
+    let progress_clone = progress.clone();
+    let report = transfer_file(
+        &IMPL,
+        &src_path,
+        &NativeLinux::new(),
+        &dst_path,
+        LinuxTransferOptions::new()
+            .chunk_size(1000)
+            .parallel_chunks(4)
+            .verification(LinuxTransferVerification::Sha256)
+            .on_progress(move |update| progress_clone.lock().unwrap().push(update)),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.total_bytes, 10_000);
+    assert_eq!(report.resumed_from, 0);
+    assert_eq!(report.sha256.map(|sha256| sha256.len()), Some(64));
+    assert_eq!(read(&dst_path).await.unwrap(), content);
+    let progress = progress.lock().unwrap().clone();
+    assert_eq!(progress.len(), 10);
+    assert!(progress.iter().all(|update| update.total_bytes == 10_000));
+    assert_eq!(
+        progress.iter().map(|update| update.transferred_bytes).max(),
+        Some(10_000)
+    );
+    remove_file(&src_path).await.unwrap();
+    remove_file(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_file_should_resume_partial_destination() {
+    let src_path = gen_tmp_path();
+    let dst_path = gen_tmp_path();
+    write(&src_path, "0123456789").await.unwrap();
+    write(&dst_path, "01234").await.unwrap();
+
+    let report = transfer_file(
+        &IMPL,
+        &src_path,
+        &IMPL,
+        &dst_path,
+        LinuxTransferOptions::new()
+            .chunk_size(2)
+            .resume(true)
+            .verification(LinuxTransferVerification::Sha256),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.resumed_from, 5);
+    assert_eq!(read_to_string(&dst_path).await.unwrap(), "0123456789");
+    remove_file(&src_path).await.unwrap();
+    remove_file(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_file_should_detect_hash_mismatch() {
+    let src_path = gen_tmp_path();
+    let dst_path = gen_tmp_path();
+    write(&src_path, "0123456789").await.unwrap();
+    write(&dst_path, "abc").await.unwrap();
+
+    let error = transfer_file(
+        &IMPL,
+        &src_path,
+        &IMPL,
+        &dst_path,
+        LinuxTransferOptions::new()
+            .resume(true)
+            .verification(LinuxTransferVerification::Sha256),
+    )
+    .await
+    .expect_err("Call succeeded");
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    remove_file(&src_path).await.unwrap();
+    remove_file(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_file_should_verify_resumed_holes_by_default() {
+    let src_path = gen_tmp_path();
+    let dst_path = gen_tmp_path();
+    write(&src_path, "0123456789").await.unwrap();
+    // what an interrupted parallel transfer leaves behind when a later chunk completed before an earlier one
+    write(&dst_path, b"01\x00\x004").await.unwrap();
+
+    let error = transfer_file(
+        &IMPL,
+        &src_path,
+        &IMPL,
+        &dst_path,
+        LinuxTransferOptions::new()
+            .chunk_size(2)
+            .parallel_chunks(2)
+            .resume(true),
+    )
+    .await
+    .expect_err("Call succeeded");
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    remove_file(&src_path).await.unwrap();
+    remove_file(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_dir_should_only_transfer_changes() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("file"), "content").await.unwrap();
+    write(src.join("nested/kept"), "kept").await.unwrap();
+    write(src.join("nested/removed"), "removed").await.unwrap();
+    symlink("file", src.join("link")).await.unwrap();
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let mut sync_options = LinuxSyncOptions::new();
+    sync_options.delete_extraneous(true);
+
+    let report = sync_dir(&IMPL, &src_path, &NativeLinux::new(), &dst_path, &sync_options)
+        .await
+        .expect("Call failed");
+    assert_eq!(
+        report.created,
+        vec![
+            OsString::from("file"),
+            OsString::from("link"),
+            OsString::from("nested"),
+            OsString::from("nested/kept"),
+            OsString::from("nested/removed"),
+        ]
+    );
+    assert_eq!(report.bytes, 18);
+    assert_eq!(read_link(dst.join("link")).await.unwrap(), Path::new("file"));
+
+    write(src.join("file"), "new content").await.unwrap();
+    write(src.join("added"), "added").await.unwrap();
+    remove_file(src.join("nested/removed")).await.unwrap();
+    set_permissions(src.join("nested/kept"), Permissions::from_mode(0o600))
+        .await
+        .unwrap();
+    let report = sync_dir(&IMPL, &src_path, &NativeLinux::new(), &dst_path, &sync_options)
+        .await
+        .expect("Call failed");
+
+    assert_eq!(
+        report,
+        LinuxSyncReport {
+            created: vec![OsString::from("added")],
+            updated: vec![OsString::from("file"), OsString::from("nested/kept")],
+            deleted: vec![OsString::from("nested/removed")],
+            unchanged: 2,
+            bytes: 16,
+        }
+    );
+    assert_eq!(read_to_string(dst.join("file")).await.unwrap(), "new content");
+    assert!(!try_exists(dst.join("nested/removed")).await.unwrap());
+    assert_eq!(
+        metadata(dst.join("nested/kept")).await.unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    assert_eq!(
+        metadata(dst.join("file")).await.unwrap().modified().unwrap(),
+        metadata(src.join("file")).await.unwrap().modified().unwrap()
+    );
+    remove_dir_all(&src_path).await.unwrap();
+    remove_dir_all(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_dir_should_support_checksums_dry_runs_and_excludes() {
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    create_dir_all(src).await.unwrap();
+    create_dir_all(dst).await.unwrap();
+    write(src.join("same"), "content").await.unwrap();
+    write(dst.join("same"), "content").await.unwrap();
+    write(src.join("changed"), "content").await.unwrap();
+    write(dst.join("changed"), "CONTENT").await.unwrap();
+    write(dst.join("extra"), "extra").await.unwrap();
+    write(dst.join("kept.log"), "log").await.unwrap();
+
+    let report = sync_dir(
+        &IMPL,
+        &src_path,
+        &IMPL,
+        &dst_path,
+        LinuxSyncOptions::new()
+            .comparison(LinuxSyncComparison::Checksum(LinuxChecksumAlgorithm::Sha256))
+            .delete_extraneous(true)
+            .exclude("*.log")
+            .dry_run(true),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.updated, vec![OsString::from("changed")]);
+    assert_eq!(report.deleted, vec![OsString::from("extra")]);
+    assert_eq!(report.unchanged, 1);
+    assert_eq!(read_to_string(dst.join("changed")).await.unwrap(), "CONTENT");
+    assert!(try_exists(dst.join("extra")).await.unwrap());
+    assert!(try_exists(dst.join("kept.log")).await.unwrap());
+    remove_dir_all(&src_path).await.unwrap();
+    remove_dir_all(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn checksum_should_support_algorithms() {
+    let path = gen_tmp_path();
+    write(&path, "content").await.unwrap();
+
+    for (algorithm, expected_checksum) in [
+        (LinuxChecksumAlgorithm::Md5, "9a0364b9e99bb480dd25e1f0284c8555"),
+        (LinuxChecksumAlgorithm::Sha1, "040f06fd774092478d450774f5ba30c5da78acc8"),
+        (
+            LinuxChecksumAlgorithm::Sha256,
+            "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73",
+        ),
+    ] {
+        assert_eq!(
+            IMPL.checksum(&path, algorithm).await.expect("Call failed"),
+            expected_checksum
+        );
+    }
+    // BLAKE3 is computed by a local b3sum
+    match IMPL.checksum(&path, LinuxChecksumAlgorithm::Blake3).await {
+        Ok(checksum) => assert_eq!(
+            checksum,
+            "3fba5250be9ac259c56e7250c526bc83bacb4be825f2799d3d59e5b4878dd74e"
+        ),
+        Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+    }
+    remove_file(&path).await.unwrap();
+}
+
+#[tokio::test]
+async fn checksum_tree_should_hash_regular_files() {
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    create_dir_all(root.join("nested")).await.unwrap();
+    write(root.join("file"), "content").await.unwrap();
+    write(root.join("nested/other file"), "other").await.unwrap();
+    symlink("file", root.join("link")).await.unwrap();
+
+    let checksums = IMPL
+        .checksum_tree(&root_path, LinuxChecksumAlgorithm::Sha256)
+        .await
+        .expect("Call failed");
+
+    assert_eq!(
+        checksums.into_iter().collect::<Vec<_>>(),
+        vec![
+            (
+                OsString::from("file"),
+                "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73".to_string()
+            ),
+            (
+                OsString::from("nested/other file"),
+                "d9298a10d1b0735837dc4bd85dac641b0f3cef27a47e5d53a54f2f3f5b2fcffa".to_string()
+            ),
+        ]
+    );
+    remove_dir_all(&root_path).await.unwrap();
+}
+
 #[tokio::test]
 async fn remove_file_should_persist_changes() {
     let path = gen_tmp_path();
@@ -234,6 +1010,215 @@ async fn list_dir_returns_correct_results() {
     entries_contain(&entries, LinuxFileType::Symlink, &symlink_path);
 }
 
+#[tokio::test]
+async fn read_dir_stream_returns_all_entries() {
+    let dir_path = gen_tmp_path();
+    create_dir(&dir_path).await.unwrap();
+    for index in 0..300 {
+        write(Path::new(&dir_path).join(index.to_string()), "content")
+            .await
+            .unwrap();
+    }
+    create_dir(Path::new(&dir_path).join("subdir")).await.unwrap();
+
+    let entries: Vec<LinuxDirEntry> = IMPL
+        .read_dir_stream(&dir_path)
+        .await
+        .expect("Call failed")
+        .try_collect()
+        .await
+        .expect("Stream failed");
+
+    assert_eq!(entries.len(), 301);
+    entries_contain(
+        &entries,
+        LinuxFileType::File,
+        Path::new(&dir_path).join("0").as_os_str(),
+    );
+    entries_contain(
+        &entries,
+        LinuxFileType::Dir,
+        Path::new(&dir_path).join("subdir").as_os_str(),
+    );
+}
+
+#[tokio::test]
+async fn walk_dir_respects_depth_filters_and_order() {
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    create_dir_all(root.join("a/b/c")).await.unwrap();
+    create_dir_all(root.join("skipped")).await.unwrap();
+    write(root.join("a/one.txt"), "content").await.unwrap();
+    write(root.join("a/b/two.txt"), "content").await.unwrap();
+    write(root.join("a/b/c/three.txt"), "content").await.unwrap();
+    write(root.join("a/b/other.log"), "content").await.unwrap();
+    write(root.join("skipped/four.txt"), "content").await.unwrap();
+
+    let walk = |walk_options: LinuxWalkOptions| {
+        let root_path = root_path.clone();
+        async move {
+            IMPL.walk_dir(&root_path, &walk_options)
+                .map_ok(|walk_entry| {
+                    Path::new(&walk_entry.dir_entry.path)
+                        .strip_prefix(&root_path)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .try_collect::<Vec<_>>()
+                .await
+                .expect("Walk failed")
+        }
+    };
+
+    assert_eq!(
+        walk(LinuxWalkOptions::new()).await,
+        vec![
+            "a",
+            "a/b",
+            "a/b/c",
+            "a/b/c/three.txt",
+            "a/b/other.log",
+            "a/b/two.txt",
+            "a/one.txt",
+            "skipped",
+            "skipped/four.txt"
+        ]
+    );
+    assert_eq!(
+        walk(
+            LinuxWalkOptions::new()
+                .max_depth(2)
+                .order(LinuxWalkOrder::PostOrder)
+                .clone()
+        )
+        .await,
+        vec!["a/b", "a/one.txt", "a", "skipped/four.txt", "skipped"]
+    );
+    assert_eq!(
+        walk(LinuxWalkOptions::new().max_depth(1).clone()).await,
+        vec!["a", "skipped"]
+    );
+    assert!(walk(LinuxWalkOptions::new().max_depth(0).clone()).await.is_empty());
+    assert_eq!(
+        walk(
+            LinuxWalkOptions::new()
+                .include("**/*.txt")
+                .exclude("skipped")
+                .file_type(LinuxFileType::File)
+                .clone()
+        )
+        .await,
+        vec!["a/b/c/three.txt", "a/b/two.txt", "a/one.txt"]
+    );
+}
+
+#[tokio::test]
+async fn walk_dir_follows_symlinks_without_looping() {
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    create_dir_all(root.join("dir")).await.unwrap();
+    write(root.join("dir/file"), "content").await.unwrap();
+    symlink(root.join("dir"), root.join("dir/loop")).await.unwrap();
+
+    let entries: Vec<LinuxWalkEntry> = IMPL
+        .walk_dir(&root_path, LinuxWalkOptions::new().follow_symlinks())
+        .try_collect()
+        .await
+        .expect("Walk failed");
+    let loop_entry = entries
+        .iter()
+        .find(|walk_entry| walk_entry.dir_entry.name == "loop")
+        .unwrap();
+    assert_eq!(loop_entry.dir_entry.file_type, LinuxFileType::Symlink);
+    assert_eq!(loop_entry.metadata.file_type, Some(LinuxFileType::Dir));
+    assert_eq!(entries.len(), 3);
+}
+
+#[tokio::test]
+async fn glob_expands_patterns_in_order() {
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    create_dir_all(root.join("logs/nested/deeper")).await.unwrap();
+    create_dir_all(root.join(".hidden")).await.unwrap();
+    for file in [
+        "logs/a.gz",
+        "logs/b1.gz",
+        "logs/b2.txt",
+        "logs/nested/c.gz",
+        "logs/nested/deeper/d.gz",
+        ".hidden/e.gz",
+    ] {
+        write(root.join(file), "content").await.unwrap();
+    }
+
+    let glob = |pattern: &str| {
+        let pattern = format!("{}/{}", root_path.to_string_lossy(), pattern);
+        let root_path = root_path.clone();
+        async move {
+            IMPL.glob(&pattern)
+                .map_ok(|dir_entry| {
+                    Path::new(&dir_entry.path)
+                        .strip_prefix(&root_path)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .try_collect::<Vec<_>>()
+                .await
+                .expect("Glob failed")
+        }
+    };
+
+    assert_eq!(
+        glob("**/*.gz").await,
+        vec!["logs/a.gz", "logs/b1.gz", "logs/nested/c.gz", "logs/nested/deeper/d.gz"]
+    );
+    assert_eq!(
+        glob("logs/b[[:digit:]].{gz,txt}").await,
+        vec!["logs/b1.gz", "logs/b2.txt"]
+    );
+    assert_eq!(
+        glob("logs/[!a]*").await,
+        vec!["logs/b1.gz", "logs/b2.txt", "logs/nested"]
+    );
+    assert_eq!(glob(".*/*.gz").await, vec![".hidden/e.gz"]);
+    assert_eq!(
+        glob("**/{d,c}.gz").await,
+        vec!["logs/nested/c.gz", "logs/nested/deeper/d.gz"]
+    );
+    assert_eq!(glob("{logs,.hidden}/{a,e}.gz").await, vec!["logs/a.gz", ".hidden/e.gz"]);
+    assert_eq!(
+        glob("{logs/nested,.hidden}/*.gz").await,
+        vec!["logs/nested/c.gz", ".hidden/e.gz"]
+    );
+    assert_eq!(glob("logs/nested").await, vec!["logs/nested"]);
+    assert!(glob("missing/*.gz").await.is_empty());
+    assert!(IMPL.glob("{unclosed").try_collect::<Vec<_>>().await.is_err());
+}
+
+#[tokio::test]
+async fn glob_should_match_runs_of_wildcards_quickly() {
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    create_dir_all(&root).await.unwrap();
+    write(root.join("a".repeat(100)), "content").await.unwrap();
+
+    let count_matches = |pattern: String| {
+        let pattern = format!("{}/{}", root_path.to_string_lossy(), pattern);
+        async move {
+            IMPL.glob(&pattern)
+                .try_collect::<Vec<_>>()
+                .await
+                .expect("Glob failed")
+                .len()
+        }
+    };
+
+    assert_eq!(count_matches("a*".repeat(20)).await, 1);
+    assert_eq!(count_matches(format!("{}b", "a*".repeat(20))).await, 0);
+}
+
 #[tokio::test]
 async fn remove_dir_should_persist() {
     let path = gen_tmp_path();
@@ -260,6 +1245,17 @@ async fn get_metadata_should_return_correct_result() {
     remove_file(&path).await.unwrap();
 }
 
+#[tokio::test]
+async fn get_extended_metadata_should_match_get_metadata() {
+    let path = gen_tmp_path();
+    write(&path, b"content").await.unwrap();
+    assert_eq!(
+        IMPL.get_extended_metadata(&path).await.expect("Call failed"),
+        IMPL.get_metadata(&path).await.unwrap()
+    );
+    remove_file(&path).await.unwrap();
+}
+
 #[tokio::test]
 async fn get_symlink_metadata_should_return_correct_result() {
     let src_path = gen_tmp_path();
@@ -273,6 +1269,47 @@ async fn get_symlink_metadata_should_return_correct_result() {
     remove_file(&symlink_path).await.unwrap();
 }
 
+#[tokio::test]
+async fn get_metadata_should_resolve_owner_names() {
+    let metadata = IMPL.get_metadata(OsStr::new("/")).await.expect("Call failed");
+    assert_eq!(metadata.user_name.as_deref(), Some("root"));
+    assert_eq!(metadata.group_name.as_deref(), Some("root"));
+}
+
+#[tokio::test]
+async fn fs_stats_should_report_capacity() {
+    let fs_stats = IMPL.fs_stats(OsStr::new("/tmp")).await.expect("Call failed");
+    assert!(fs_stats.total_blocks > 0);
+    assert!(fs_stats.available_blocks <= fs_stats.free_blocks);
+    assert!(fs_stats.free_inodes <= fs_stats.total_inodes);
+    assert!(fs_stats.mount_flags.is_some());
+}
+
+#[tokio::test]
+async fn disk_usage_should_count_hard_links_once() {
+    let path = gen_tmp_path();
+    create_dir(&path).await.unwrap();
+    let file_path = Path::new(&path).join("file");
+    write(&file_path, vec![1u8; 10000]).await.unwrap();
+    std::fs::hard_link(&file_path, Path::new(&path).join("link")).unwrap();
+    let expected_usage = (metadata(&path).await.unwrap().blocks() + metadata(&file_path).await.unwrap().blocks()) * 512;
+    assert_eq!(IMPL.disk_usage(&path).await.expect("Call failed"), expected_usage);
+    assert_eq!(
+        IMPL.disk_usage(file_path.as_os_str()).await.expect("Call failed"),
+        metadata(&file_path).await.unwrap().blocks() * 512
+    );
+}
+
+#[tokio::test]
+async fn lookup_user_and_group_should_resolve_ids() {
+    assert_eq!(IMPL.lookup_user(0).await.expect("Call failed").as_deref(), Some("root"));
+    assert_eq!(
+        IMPL.lookup_group(0).await.expect("Call failed").as_deref(),
+        Some("root")
+    );
+    assert_eq!(IMPL.lookup_user(u32::MAX - 1).await.expect("Call failed"), None);
+}
+
 fn assert_metadata(expected_metadata: Metadata, actual_metadata: LinuxFileMetadata, _file_type: LinuxFileType) {
     assert!(matches!(actual_metadata.file_type.unwrap(), _file_type));
     assert_eq!(actual_metadata.size.unwrap(), expected_metadata.size());
@@ -293,7 +1330,15 @@ fn assert_metadata(expected_metadata: Metadata, actual_metadata: LinuxFileMetada
         expected_metadata.created().unwrap()
     );
     assert_eq!(actual_metadata.user_id.unwrap(), expected_metadata.uid());
-    assert_eq!(actual_metadata.user_name, None);
     assert_eq!(actual_metadata.group_id.unwrap(), expected_metadata.gid());
-    assert_eq!(actual_metadata.group_name, None);
+    assert_eq!(
+        actual_metadata.changed_time.unwrap(),
+        UNIX_EPOCH + Duration::new(expected_metadata.ctime() as u64, expected_metadata.ctime_nsec() as u32)
+    );
+    assert_eq!(actual_metadata.inode.unwrap(), expected_metadata.ino());
+    assert_eq!(actual_metadata.device_id.unwrap(), expected_metadata.dev());
+    assert_eq!(actual_metadata.special_device_id.unwrap(), expected_metadata.rdev());
+    assert_eq!(actual_metadata.hard_link_count.unwrap(), expected_metadata.nlink());
+    assert_eq!(actual_metadata.block_size.unwrap(), expected_metadata.blksize());
+    assert_eq!(actual_metadata.block_count.unwrap(), expected_metadata.blocks());
 }
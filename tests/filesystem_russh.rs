@@ -1,9 +1,30 @@
-use std::ffi::OsString;
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use common::{entries_contain, gen_nested_tmp_path, gen_tmp_path, RusshData};
-use remoteify::filesystem::{LinuxFileMetadata, LinuxFileType, LinuxFilesystem, LinuxOpenOptions, LinuxPermissions};
+use common::{entries_contain, extended_acl, gen_nested_tmp_path, gen_tmp_path, mode_acl, RusshData};
+use futures::TryStreamExt;
+use remoteify::{
+    archive::{download_dir, upload_dir, LinuxArchiveOptions},
+    filesystem::{
+        LinuxChecksumAlgorithm, LinuxDirEntry, LinuxFile, LinuxFileMetadata, LinuxFileType, LinuxFilesystem,
+        LinuxLockMode, LinuxOpenOptions, LinuxPermissions, LinuxWalkEntry, LinuxWalkOptions,
+    },
+    impl_native::NativeLinux,
+    sync::{sync_dir, LinuxSyncOptions},
+    transfer::{
+        download_file, transfer, upload_file, LinuxCopyOptions, LinuxCopyReport, LinuxTransferOptions,
+        LinuxTransferVerification,
+    },
+};
 use russh_sftp::protocol::FileAttributes;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::{
+    fs::{create_dir_all, read, read_to_string, remove_dir_all, remove_file, write},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
 
 mod common;
 
@@ -63,7 +84,7 @@ async fn open_file_with_append_should_work() {
     let path = test_data.init_file("first").await;
     let mut handle = test_data
         .implementation
-        .open_file(&path, &LinuxOpenOptions::new().write().append())
+        .open_file(&path, LinuxOpenOptions::new().write().append())
         .await
         .expect("Call failed");
     handle.write_all(b"second").await.unwrap();
@@ -76,7 +97,7 @@ async fn open_file_with_truncate_should_work() {
     let path = test_data.init_file("current").await;
     let mut handle = test_data
         .implementation
-        .open_file(&path, &LinuxOpenOptions::new().write().truncate())
+        .open_file(&path, LinuxOpenOptions::new().write().truncate())
         .await
         .expect("Call failed");
     handle.write_all(b"next").await.unwrap();
@@ -89,13 +110,111 @@ async fn open_file_with_create_should_work() {
     let path = gen_tmp_path();
     let mut handle = test_data
         .implementation
-        .open_file(&path, &LinuxOpenOptions::new().write().create())
+        .open_file(&path, LinuxOpenOptions::new().write().create())
         .await
         .expect("Call failed");
     handle.write_all(b"content").await.unwrap();
     test_data.assert_file(&path, "content").await;
 }
 
+#[tokio::test]
+async fn open_file_with_create_new_and_mode_should_work() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    let handle = test_data
+        .implementation
+        .open_file(
+            &path,
+            LinuxOpenOptions::new()
+                .write()
+                .create_new()
+                .mode(LinuxPermissions::from_bits(0o600).unwrap()),
+        )
+        .await
+        .expect("Call failed");
+    Box::new(handle).close().await.unwrap();
+    assert_eq!(
+        test_data
+            .implementation
+            .get_metadata(&path)
+            .await
+            .unwrap()
+            .permissions
+            .unwrap()
+            .bits()
+            & 0o777,
+        0o600
+    );
+    assert!(test_data
+        .implementation
+        .open_file(&path, LinuxOpenOptions::new().write().create_new())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn open_file_with_no_follow_and_directory_should_check_type() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    let link_path = gen_tmp_path();
+    test_data
+        .sftp
+        .symlink(path.to_string_lossy(), link_path.to_string_lossy())
+        .await
+        .unwrap();
+    assert!(test_data
+        .implementation
+        .open_file(&link_path, LinuxOpenOptions::new().read().no_follow())
+        .await
+        .is_err());
+    assert!(test_data
+        .implementation
+        .open_file(&path, LinuxOpenOptions::new().read().directory())
+        .await
+        .is_err());
+    assert!(test_data
+        .implementation
+        .open_file(OsStr::new("/tmp"), LinuxOpenOptions::new().read().directory())
+        .await
+        .is_ok());
+    assert_eq!(
+        test_data
+            .implementation
+            .open_file(&path, LinuxOpenOptions::new().read().sync())
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::Unsupported)
+    );
+}
+
+#[tokio::test]
+async fn opened_file_should_support_positional_io() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    let mut handle: Box<dyn LinuxFile> = Box::new(
+        test_data
+            .implementation
+            .open_file(&path, LinuxOpenOptions::new().read().write())
+            .await
+            .expect("Call failed"),
+    );
+    handle.write_all(b"C").await.unwrap();
+    assert_eq!(handle.write_at(b"T", 3).await.unwrap(), 1);
+    let mut buf = [0u8; 3];
+    assert_eq!(handle.read_at(&mut buf, 3).await.unwrap(), 3);
+    assert_eq!(&buf, b"Ten");
+    // the cursor stays behind the first write
+    let mut rest = String::new();
+    handle.read_to_string(&mut rest).await.unwrap();
+    assert_eq!(rest, "onTent");
+    handle.set_len(4).await.expect("Call failed");
+    assert_eq!(handle.metadata().await.unwrap().size, Some(4));
+    handle.sync_all().await.expect("Call failed");
+    handle.close().await.expect("Call failed");
+    test_data.assert_file(&path, "ConT").await;
+}
+
 #[tokio::test]
 async fn create_file_should_persist() {
     let test_data = RusshData::setup().await;
@@ -104,6 +223,44 @@ async fn create_file_should_persist() {
     assert!(test_data.sftp.try_exists(path.to_string_lossy()).await.unwrap());
 }
 
+#[tokio::test]
+async fn write_file_atomic_should_replace_file_and_keep_mode() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("old content").await;
+    test_data
+        .implementation
+        .set_permissions(&path, LinuxPermissions::from_bits(0o640).unwrap())
+        .await
+        .unwrap();
+
+    test_data
+        .implementation
+        .write_file_atomic(&path, b"new content")
+        .await
+        .expect("Call failed");
+
+    test_data.assert_file(&path, "new content").await;
+    assert_eq!(
+        test_data
+            .implementation
+            .read_to_string(&path)
+            .await
+            .expect("Call failed"),
+        "new content"
+    );
+    assert_eq!(
+        test_data
+            .sftp
+            .metadata(path.to_string_lossy())
+            .await
+            .unwrap()
+            .permissions
+            .unwrap()
+            & 0o777,
+        0o640
+    );
+}
+
 #[tokio::test]
 async fn rename_file_should_perform_change() {
     let test_data = RusshData::setup().await;
@@ -124,15 +281,346 @@ async fn copy_file_should_perform_change() {
     let test_data = RusshData::setup().await;
     let old_path = test_data.init_file("content").await;
     let new_path = gen_tmp_path();
-    test_data
+    let bytes = test_data
         .implementation
         .copy_file(&old_path, &new_path)
         .await
         .expect("Call failed");
+    assert_eq!(bytes, Some(7));
     test_data.assert_file(&old_path, "content").await;
     test_data.assert_file(&new_path, "content").await;
 }
 
+#[tokio::test]
+async fn copy_file_should_escape_paths() {
+    let test_data = RusshData::setup().await;
+    let old_path = test_data.init_file("content").await;
+    let mut new_path = gen_tmp_path();
+    new_path.push(" with 'quotes'");
+    test_data
+        .implementation
+        .copy_file(&old_path, &new_path)
+        .await
+        .expect("Call failed");
+    test_data.assert_file(&new_path, "content").await;
+}
+
+#[tokio::test]
+async fn copy_dir_recursively_should_preserve_tree() {
+    let test_data = RusshData::setup().await;
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    test_data.sftp.create_dir(src.to_string_lossy()).await.unwrap();
+    test_data
+        .sftp
+        .create_dir(src.join("nested").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .create(src.join("nested/file").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .write(src.join("nested/file").to_string_lossy(), b"content")
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .symlink("nested/file", src.join("link").to_string_lossy())
+        .await
+        .unwrap();
+
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let report = test_data
+        .implementation
+        .copy_dir_recursively(&src_path, &dst_path, &LinuxCopyOptions::new())
+        .await
+        .expect("Call failed");
+
+    assert_eq!(
+        report,
+        LinuxCopyReport {
+            files: 1,
+            dirs: 2,
+            symlinks: 1,
+            bytes: 7
+        }
+    );
+    test_data
+        .assert_file(dst.join("nested/file").as_os_str(), "content")
+        .await;
+    assert_eq!(
+        test_data
+            .sftp
+            .read_link(dst.join("link").to_string_lossy())
+            .await
+            .unwrap(),
+        "nested/file"
+    );
+}
+
+#[tokio::test]
+async fn transfer_should_copy_from_native() {
+    let test_data = RusshData::setup().await;
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("nested/file"), "content").await.unwrap();
+
+    let dst_path = gen_tmp_path();
+    let report = transfer(
+        &NativeLinux::new(),
+        &src_path,
+        &test_data.implementation,
+        &dst_path,
+        &LinuxCopyOptions::new(),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.files, 1);
+    assert_eq!(report.dirs, 2);
+    test_data
+        .assert_file(Path::new(&dst_path).join("nested/file").as_os_str(), "content")
+        .await;
+    remove_dir_all(&src_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn upload_dir_and_download_dir_should_roundtrip() {
+    let test_data = RusshData::setup().await;
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("nested/file"), "content").await.unwrap();
+    write(src.join("file"), "root").await.unwrap();
+
+    let remote_path = gen_tmp_path();
+    let upload_report = upload_dir(
+        &NativeLinux::new(),
+        &src_path,
+        &test_data.implementation,
+        &remote_path,
+        &LinuxArchiveOptions::new(),
+    )
+    .await
+    .expect("Call failed");
+    let expected_report = LinuxCopyReport {
+        files: 2,
+        dirs: 2,
+        symlinks: 0,
+        bytes: 11,
+    };
+    assert_eq!(upload_report, expected_report);
+    test_data
+        .assert_file(Path::new(&remote_path).join("nested/file").as_os_str(), "content")
+        .await;
+
+    let dst_path = gen_tmp_path();
+    let download_report = download_dir(
+        &test_data.implementation,
+        &remote_path,
+        &NativeLinux::new(),
+        &dst_path,
+        &LinuxArchiveOptions::new(),
+    )
+    .await
+    .expect("Call failed");
+    assert_eq!(download_report, expected_report);
+    assert_eq!(read_to_string(Path::new(&dst_path).join("file")).await.unwrap(), "root");
+    remove_dir_all(&src_path).await.unwrap();
+    remove_dir_all(&dst_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_dir_should_skip_unchanged_files_from_native() {
+    let test_data = RusshData::setup().await;
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("nested")).await.unwrap();
+    write(src.join("file"), "content").await.unwrap();
+    write(src.join("nested/file"), "nested").await.unwrap();
+    let dst_path = gen_tmp_path();
+
+    let first_report = sync_dir(
+        &NativeLinux::new(),
+        &src_path,
+        &test_data.implementation,
+        &dst_path,
+        &LinuxSyncOptions::new(),
+    )
+    .await
+    .expect("Call failed");
+    write(src.join("file"), "changed content").await.unwrap();
+    let second_report = sync_dir(
+        &NativeLinux::new(),
+        &src_path,
+        &test_data.implementation,
+        &dst_path,
+        &LinuxSyncOptions::new(),
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(first_report.created.len(), 3);
+    assert_eq!(second_report.updated, vec![OsString::from("file")]);
+    assert_eq!(second_report.unchanged, 2);
+    test_data
+        .assert_file(Path::new(&dst_path).join("file").as_os_str(), "changed content")
+        .await;
+    remove_dir_all(&src_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_dir_should_delete_extraneous_dirs_with_spaces() {
+    let test_data = RusshData::setup().await;
+    let src_path = gen_tmp_path();
+    let src = Path::new(&src_path);
+    create_dir_all(src.join("extra")).await.unwrap();
+    write(src.join("extra/file"), "content").await.unwrap();
+    let dst_path = gen_tmp_path();
+    let dst = Path::new(&dst_path);
+    let extraneous_path = dst.join("extra dir");
+    test_data
+        .implementation
+        .create_dir_recursively(extraneous_path.join("nested").as_os_str())
+        .await
+        .unwrap();
+    let mut sync_options = LinuxSyncOptions::new();
+    sync_options.delete_extraneous(true);
+
+    let report = sync_dir(
+        &NativeLinux::new(),
+        &src_path,
+        &test_data.implementation,
+        &dst_path,
+        &sync_options,
+    )
+    .await
+    .expect("Call failed");
+
+    assert_eq!(report.deleted, vec![OsString::from("extra dir")]);
+    assert!(!test_data
+        .implementation
+        .exists(extraneous_path.as_os_str())
+        .await
+        .unwrap());
+    test_data
+        .assert_file(dst.join("extra/file").as_os_str(), "content")
+        .await;
+    remove_dir_all(&src_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn checksum_should_be_computed_remotely() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    assert_eq!(
+        test_data
+            .implementation
+            .checksum(&path, LinuxChecksumAlgorithm::Sha256)
+            .await
+            .expect("Call failed"),
+        "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73"
+    );
+    assert_eq!(
+        test_data
+            .implementation
+            .checksum(&path, LinuxChecksumAlgorithm::Md5)
+            .await
+            .expect("Call failed"),
+        "9a0364b9e99bb480dd25e1f0284c8555"
+    );
+}
+
+#[tokio::test]
+async fn checksum_tree_should_hash_regular_files() {
+    let test_data = RusshData::setup().await;
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    test_data
+        .implementation
+        .create_dir_recursively(root.join("nested").as_os_str())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .create(root.join("file").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .write(root.join("file").to_string_lossy(), b"content")
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .create(root.join("nested/other file").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .write(root.join("nested/other file").to_string_lossy(), b"other")
+        .await
+        .unwrap();
+
+    let checksums = test_data
+        .implementation
+        .checksum_tree(&root_path, LinuxChecksumAlgorithm::Sha256)
+        .await
+        .expect("Call failed");
+
+    assert_eq!(
+        checksums.into_iter().collect::<Vec<_>>(),
+        vec![
+            (
+                OsString::from("file"),
+                "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73".to_string()
+            ),
+            (
+                OsString::from("nested/other file"),
+                "d9298a10d1b0735837dc4bd85dac641b0f3cef27a47e5d53a54f2f3f5b2fcffa".to_string()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn upload_and_download_file_should_roundtrip_in_parallel_chunks() {
+    let test_data = RusshData::setup().await;
+    let local_path = gen_tmp_path();
+    let content = (0..100_000).map(|index| (index % 251) as u8).collect::<Vec<_>>();
+    write(&local_path, &content).await.unwrap();
+    let mut transfer_options = LinuxTransferOptions::new();
+    transfer_options
+        .chunk_size(8192)
+        .parallel_chunks(4)
+        .verification(LinuxTransferVerification::Sha256);
+
+    let remote_path = gen_tmp_path();
+    let upload_report = upload_file(&local_path, &test_data.implementation, &remote_path, &transfer_options)
+        .await
+        .expect("Upload failed");
+    let downloaded_path = gen_tmp_path();
+    let download_report = download_file(
+        &test_data.implementation,
+        &remote_path,
+        &downloaded_path,
+        &transfer_options,
+    )
+    .await
+    .expect("Download failed");
+
+    assert_eq!(upload_report.total_bytes, 100_000);
+    assert_eq!(upload_report.sha256, download_report.sha256);
+    assert_eq!(read(&downloaded_path).await.unwrap(), content);
+    remove_file(&local_path).await.unwrap();
+    remove_file(&downloaded_path).await.unwrap();
+}
+
 #[tokio::test]
 async fn canonicalize_should_perform_transformation() {
     let test_data = RusshData::setup().await;
@@ -198,6 +686,200 @@ async fn read_link_should_return_correct_source_path() {
     );
 }
 
+#[tokio::test]
+async fn create_fifo_and_device_node_should_establish_special_files() {
+    let test_data = RusshData::setup().await;
+    let fifo_path = gen_tmp_path();
+    let device_path = gen_tmp_path();
+    test_data
+        .implementation
+        .create_fifo(&fifo_path, LinuxPermissions::from_bits(0o640).unwrap())
+        .await
+        .expect("Call failed");
+    test_data
+        .implementation
+        .create_device_node(
+            &device_path,
+            LinuxFileType::CharDevice,
+            1,
+            3,
+            LinuxPermissions::from_bits(0o666).unwrap(),
+        )
+        .await
+        .expect("Call failed");
+
+    let fifo_metadata = test_data.implementation.get_metadata(&fifo_path).await.unwrap();
+    assert_eq!(fifo_metadata.file_type, Some(LinuxFileType::Fifo));
+    assert_eq!(fifo_metadata.permissions.unwrap().bits() & 0o777, 0o640);
+    let device_metadata = test_data
+        .implementation
+        .get_extended_metadata(&device_path)
+        .await
+        .unwrap();
+    assert_eq!(device_metadata.file_type, Some(LinuxFileType::CharDevice));
+    assert_eq!(device_metadata.special_device_id, Some(259));
+    let entries = test_data.implementation.list_dir(OsStr::new("/tmp")).await.unwrap();
+    entries_contain(&entries, LinuxFileType::Fifo, &fifo_path);
+    entries_contain(&entries, LinuxFileType::CharDevice, &device_path);
+}
+
+#[tokio::test]
+async fn set_symlink_owner_should_not_follow_symlink() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    test_data
+        .sftp
+        .symlink(gen_tmp_path().to_string_lossy(), path.to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .implementation
+        .set_symlink_owner(&path, None, Some(0))
+        .await
+        .expect("Call failed");
+    let metadata = test_data.implementation.get_symlink_metadata(&path).await.unwrap();
+    assert_eq!(metadata.group_id, Some(0));
+}
+
+#[tokio::test]
+async fn set_len_and_allocate_should_resize() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    test_data.implementation.set_len(&path, 3).await.expect("Call failed");
+    assert_eq!(
+        test_data.implementation.get_metadata(&path).await.unwrap().size,
+        Some(3)
+    );
+    test_data
+        .implementation
+        .allocate(&path, 0, 65536)
+        .await
+        .expect("Call failed");
+    assert_eq!(
+        test_data.implementation.get_metadata(&path).await.unwrap().size,
+        Some(65536)
+    );
+}
+
+#[tokio::test]
+async fn sync_dir_should_succeed() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    test_data.sftp.create_dir(path.to_string_lossy()).await.unwrap();
+    test_data.implementation.sync_dir(&path).await.expect("Call failed");
+}
+
+#[tokio::test]
+async fn acls_should_round_trip() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    test_data.sftp.create_dir(path.to_string_lossy()).await.unwrap();
+    test_data
+        .implementation
+        .set_acl(&path, &extended_acl())
+        .await
+        .expect("Call failed");
+    assert_eq!(test_data.implementation.get_acl(&path).await.unwrap(), extended_acl());
+    // without named entries and a default ACL, only the mode remains
+    test_data
+        .implementation
+        .set_acl(&path, &mode_acl())
+        .await
+        .expect("Call failed");
+    assert_eq!(test_data.implementation.get_acl(&path).await.unwrap(), mode_acl());
+    assert_eq!(
+        test_data
+            .implementation
+            .get_metadata(&path)
+            .await
+            .unwrap()
+            .permissions
+            .unwrap()
+            .bits()
+            & 0o777,
+        0o750
+    );
+}
+
+#[tokio::test]
+async fn lock_file_should_exclude_conflicting_locks() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    let timeout = Some(Duration::from_millis(500));
+    let lock = test_data
+        .implementation
+        .lock_file(&path, LinuxLockMode::Exclusive, None)
+        .await
+        .expect("Call failed");
+    assert!(test_data.implementation.exists(&path).await.unwrap());
+    assert_eq!(
+        test_data
+            .implementation
+            .lock_file(&path, LinuxLockMode::Shared, timeout)
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::TimedOut)
+    );
+    lock.unlock().await.expect("Call failed");
+
+    let shared_lock = test_data
+        .implementation
+        .lock_file(&path, LinuxLockMode::Shared, timeout)
+        .await
+        .unwrap();
+    let other_shared_lock = test_data
+        .implementation
+        .lock_file(&path, LinuxLockMode::Shared, timeout)
+        .await
+        .unwrap();
+    assert_eq!(
+        test_data
+            .implementation
+            .lock_file(&path, LinuxLockMode::Exclusive, Some(Duration::ZERO))
+            .await
+            .err()
+            .map(|err| err.kind()),
+        Some(io::ErrorKind::TimedOut)
+    );
+    drop(shared_lock);
+    drop(other_shared_lock);
+    // dropped locks are released on the host in the background, so the exclusive lock has to wait for them
+    test_data
+        .implementation
+        .lock_file(&path, LinuxLockMode::Exclusive, timeout)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn xattrs_should_round_trip() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    let name = OsStr::new("user.remoteify");
+    test_data
+        .implementation
+        .set_xattr(&path, name, &[0, 255, b'a'])
+        .await
+        .expect("Call failed");
+    assert_eq!(
+        test_data.implementation.get_xattr(&path, name).await.unwrap(),
+        Some(vec![0, 255, b'a'])
+    );
+    assert!(test_data
+        .implementation
+        .list_xattrs(&path)
+        .await
+        .unwrap()
+        .contains(&name.to_os_string()));
+    test_data
+        .implementation
+        .remove_xattr(&path, name)
+        .await
+        .expect("Call failed");
+    assert_eq!(test_data.implementation.get_xattr(&path, name).await.unwrap(), None);
+}
+
 #[tokio::test]
 async fn set_permissions_should_perform_change() {
     let test_data = RusshData::setup().await;
@@ -271,6 +953,106 @@ async fn list_dir_returns_correct_results() {
     entries_contain(&entries, LinuxFileType::Symlink, &symlink_path);
 }
 
+#[tokio::test]
+async fn read_dir_stream_returns_all_entries() {
+    let test_data = RusshData::setup().await;
+    let dir_path = gen_tmp_path();
+    test_data.sftp.create_dir(dir_path.to_string_lossy()).await.unwrap();
+    for index in 0..300 {
+        let file_path = Path::new(&dir_path).join(index.to_string());
+        test_data.sftp.create(file_path.to_string_lossy()).await.unwrap();
+    }
+    let subdir_path = Path::new(&dir_path).join("subdir");
+    test_data.sftp.create_dir(subdir_path.to_string_lossy()).await.unwrap();
+
+    let entries: Vec<LinuxDirEntry> = test_data
+        .implementation
+        .read_dir_stream(&dir_path)
+        .await
+        .expect("Call failed")
+        .try_collect()
+        .await
+        .expect("Stream failed");
+
+    assert_eq!(entries.len(), 301);
+    entries_contain(
+        &entries,
+        LinuxFileType::File,
+        Path::new(&dir_path).join("0").as_os_str(),
+    );
+    entries_contain(&entries, LinuxFileType::Dir, subdir_path.as_os_str());
+}
+
+#[tokio::test]
+async fn walk_dir_returns_entries_with_metadata() {
+    let test_data = RusshData::setup().await;
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    test_data.sftp.create_dir(root_path.to_string_lossy()).await.unwrap();
+    test_data
+        .sftp
+        .create_dir(root.join("dir").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .create(root.join("dir/file").to_string_lossy())
+        .await
+        .unwrap();
+    test_data
+        .sftp
+        .create(root.join("other").to_string_lossy())
+        .await
+        .unwrap();
+
+    let entries: Vec<LinuxWalkEntry> = test_data
+        .implementation
+        .walk_dir(&root_path, &LinuxWalkOptions::new())
+        .try_collect()
+        .await
+        .expect("Walk failed");
+    let names = entries
+        .iter()
+        .map(|walk_entry| (walk_entry.dir_entry.name.clone(), walk_entry.depth))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![("dir".into(), 1), ("file".into(), 2), ("other".into(), 1)]);
+    assert_eq!(entries[0].metadata.file_type, Some(LinuxFileType::Dir));
+    assert_eq!(entries[1].metadata.file_type, Some(LinuxFileType::File));
+}
+
+#[tokio::test]
+async fn glob_expands_patterns_in_order() {
+    let test_data = RusshData::setup().await;
+    let root_path = gen_tmp_path();
+    let root = Path::new(&root_path);
+    test_data.sftp.create_dir(root_path.to_string_lossy()).await.unwrap();
+    test_data
+        .sftp
+        .create_dir(root.join("nested").to_string_lossy())
+        .await
+        .unwrap();
+    for file in ["a.gz", "b.txt", "nested/c.gz"] {
+        test_data.sftp.create(root.join(file).to_string_lossy()).await.unwrap();
+    }
+
+    let pattern = format!("{}/**/*.{{gz,txt}}", root_path.to_string_lossy());
+    let entries: Vec<LinuxDirEntry> = test_data
+        .implementation
+        .glob(&pattern)
+        .try_collect()
+        .await
+        .expect("Glob failed");
+    let paths = entries.into_iter().map(|dir_entry| dir_entry.path).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            root.join("a.gz").into_os_string(),
+            root.join("b.txt").into_os_string(),
+            root.join("nested/c.gz").into_os_string()
+        ]
+    );
+}
+
 #[tokio::test]
 async fn remove_dir_should_persist() {
     let test_data = RusshData::setup().await;
@@ -303,6 +1085,39 @@ async fn get_metadata_should_return_correct_result() {
     assert_metadata(expected_metadata, actual_metadata, LinuxFileType::File);
 }
 
+#[tokio::test]
+async fn get_extended_metadata_should_fill_in_stat_fields() {
+    let test_data = RusshData::setup().await;
+    let path = test_data.init_file("content").await;
+    let expected_metadata = test_data.sftp.metadata(path.to_string_lossy()).await.unwrap();
+    let actual_metadata = test_data
+        .implementation
+        .get_extended_metadata(&path)
+        .await
+        .expect("Call failed");
+    assert_extended_metadata(&actual_metadata);
+    assert_metadata(expected_metadata, actual_metadata, LinuxFileType::File);
+}
+
+#[tokio::test]
+async fn list_dir_with_extended_metadata_should_fill_in_stat_fields() {
+    let test_data = RusshData::setup().await;
+    let dir_path = gen_tmp_path();
+    test_data.sftp.create_dir(dir_path.to_string_lossy()).await.unwrap();
+    let file_path = Path::new(&dir_path).join("file");
+    test_data.sftp.create(file_path.to_string_lossy()).await.unwrap();
+
+    let entries = test_data
+        .implementation
+        .list_dir_with_extended_metadata(&dir_path)
+        .await
+        .expect("Call failed");
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0.path, file_path.as_os_str());
+    assert_extended_metadata(&entries[0].1);
+}
+
 #[tokio::test]
 async fn get_symlink_metadata_should_return_correct_result() {
     let test_data = RusshData::setup().await;
@@ -326,6 +1141,65 @@ async fn get_symlink_metadata_should_return_correct_result() {
     assert_metadata(expected_metadata, actual_metadata, LinuxFileType::Symlink);
 }
 
+#[tokio::test]
+async fn fs_stats_should_report_capacity() {
+    let test_data = RusshData::setup().await;
+    let fs_stats = test_data
+        .implementation
+        .fs_stats(OsStr::new("/tmp"))
+        .await
+        .expect("Call failed");
+    assert!(fs_stats.total_blocks > 0);
+    assert!(fs_stats.available_blocks <= fs_stats.free_blocks);
+}
+
+#[tokio::test]
+async fn disk_usage_should_sum_tree() {
+    let test_data = RusshData::setup().await;
+    let path = gen_tmp_path();
+    let file_path = Path::new(&path).join("file");
+    test_data.sftp.create_dir(path.to_string_lossy()).await.unwrap();
+    test_data.sftp.create(file_path.to_string_lossy()).await.unwrap();
+    test_data
+        .sftp
+        .write(file_path.to_string_lossy(), &[1u8; 10000])
+        .await
+        .unwrap();
+    let usage = test_data.implementation.disk_usage(&path).await.expect("Call failed");
+    assert!(usage >= 10000);
+}
+
+#[tokio::test]
+async fn lookup_user_and_group_should_resolve_ids() {
+    let test_data = RusshData::setup().await;
+    assert_eq!(
+        test_data
+            .implementation
+            .lookup_user(0)
+            .await
+            .expect("Call failed")
+            .as_deref(),
+        Some("root")
+    );
+    assert_eq!(
+        test_data
+            .implementation
+            .lookup_group(0)
+            .await
+            .expect("Call failed")
+            .as_deref(),
+        Some("root")
+    );
+    assert_eq!(
+        test_data
+            .implementation
+            .lookup_user(u32::MAX - 1)
+            .await
+            .expect("Call failed"),
+        None
+    );
+}
+
 fn assert_metadata(expected_metadata: FileAttributes, actual_metadata: LinuxFileMetadata, _file_type: LinuxFileType) {
     assert!(matches!(actual_metadata.file_type.unwrap(), _file_type));
     assert_eq!(actual_metadata.size.unwrap(), expected_metadata.size.unwrap());
@@ -333,17 +1207,30 @@ fn assert_metadata(expected_metadata: FileAttributes, actual_metadata: LinuxFile
         actual_metadata.permissions.unwrap().bits(),
         expected_metadata.permissions.unwrap()
     );
+    // SFTP only reports whole seconds, while extended metadata fills in nanoseconds
     assert_eq!(
-        actual_metadata.modified_time.unwrap(),
-        expected_metadata.modified().unwrap()
+        unix_secs(actual_metadata.modified_time.unwrap()),
+        unix_secs(expected_metadata.modified().unwrap())
     );
     assert_eq!(
-        actual_metadata.accessed_time.unwrap(),
-        expected_metadata.modified().unwrap()
+        unix_secs(actual_metadata.accessed_time.unwrap()),
+        unix_secs(expected_metadata.accessed().unwrap())
     );
     assert_eq!(actual_metadata.created_time, None);
     assert_eq!(actual_metadata.user_id.unwrap(), expected_metadata.uid.unwrap());
-    assert_eq!(actual_metadata.user_name, None);
+    assert_eq!(actual_metadata.user_name.as_deref(), Some("root"));
     assert_eq!(actual_metadata.group_id.unwrap(), expected_metadata.gid.unwrap());
-    assert_eq!(actual_metadata.group_name, None);
+    assert_eq!(actual_metadata.group_name.as_deref(), Some("root"));
+}
+
+fn assert_extended_metadata(actual_metadata: &LinuxFileMetadata) {
+    assert!(actual_metadata.changed_time.is_some());
+    assert!(actual_metadata.inode.is_some());
+    assert!(actual_metadata.device_id.is_some());
+    assert_eq!(actual_metadata.hard_link_count, Some(1));
+    assert!(actual_metadata.block_size.is_some());
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_secs()
 }